@@ -0,0 +1,106 @@
+// Compares lockstep batch traversal of trees (`Resolve::resolve_batch`) with traversal of one
+// sample at a time (`Resolve::resolve`), and QuickScorer prediction of random forests with
+// traversal of their trees. Run with `cargo bench --bench resolve`.
+use rafor::{prelude::*, rf, BlockTree, CompactTree, Resolve, Trainable};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use std::{hint::black_box, time::Instant};
+
+const NUM_FEATURES: usize = 16;
+const NUM_SAMPLES: usize = 100_000;
+const REPEATS: usize = 20;
+
+// Builds a complete tree of given depth with random features and thresholds in [0, 1).
+fn random_tree<P: Trainable>(depth: usize, rng: &mut SmallRng) -> P {
+    let mut tree = P::new();
+    let mut handles = vec![tree.root()];
+    for _ in 0..depth {
+        let mut next = Vec::with_capacity(2 * handles.len());
+        for handle in handles.iter() {
+            let feature = rng.random_range(0..NUM_FEATURES) as u16;
+            let (left, right) = tree.split(handle, feature, rng.random());
+            next.push(left);
+            next.push(right);
+        }
+        handles = next;
+    }
+    for (i, handle) in handles.iter().enumerate() {
+        tree.set_leaf_value(handle, i as u32);
+    }
+    tree
+}
+
+// Returns the best time in seconds of REPEATS runs of `f`.
+fn best_time(mut f: impl FnMut()) -> f64 {
+    (0..REPEATS)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed().as_secs_f64()
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+fn compare<P: Trainable + Resolve>(name: &str, depth: usize, samples: &[f32]) {
+    let tree: P = random_tree(depth, &mut SmallRng::seed_from_u64(42));
+    let mut out = vec![0; samples.len() / NUM_FEATURES];
+    let each = best_time(|| {
+        for (o, sample) in out.iter_mut().zip(samples.chunks_exact(NUM_FEATURES)) {
+            *o = tree.resolve(black_box(sample));
+        }
+        black_box(&out);
+    });
+    let batch = best_time(|| {
+        tree.resolve_batch(black_box(samples), NUM_FEATURES, &mut out);
+        black_box(&out);
+    });
+    println!(
+        "{name} depth {depth}: resolve {:.2} ms, resolve_batch {:.2} ms, speedup {:.2}",
+        each * 1e3,
+        batch * 1e3,
+        each / batch
+    );
+}
+
+// Compares prediction of a forest of `num_trees` trees trained on the first samples with its
+// QuickScorer representation, both on one thread.
+fn compare_quick_scorer(depth: usize, num_trees: usize, samples: &[f32]) {
+    let train = &samples[..10_000 * NUM_FEATURES];
+    let targets: Vec<f32> = train
+        .chunks_exact(NUM_FEATURES)
+        .map(|s| s[0] + s[1] * s[2])
+        .collect();
+    let forest = rf::Regressor::<CompactTree>::trainer()
+        .with_max_depth(depth)
+        .with_trees(num_trees)
+        .train(train, &targets);
+    let quick = forest.quick_scorer();
+    let mut out = vec![0.; samples.len() / NUM_FEATURES];
+    let forest_time = best_time(|| {
+        forest.predict_into(black_box(samples), &mut out);
+        black_box(&out);
+    });
+    let quick_time = best_time(|| {
+        quick.predict_into(black_box(samples), &mut out);
+        black_box(&out);
+    });
+    println!(
+        "{num_trees} trees depth {depth}: CompactTree {:.2} ms, QuickScorer {:.2} ms, speedup {:.2}",
+        forest_time * 1e3,
+        quick_time * 1e3,
+        forest_time / quick_time
+    );
+}
+
+fn main() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let samples: Vec<f32> = (0..NUM_SAMPLES * NUM_FEATURES)
+        .map(|_| rng.random())
+        .collect();
+    for depth in [6, 12, 18] {
+        compare::<BlockTree>("BlockTree", depth, &samples);
+        compare::<CompactTree>("CompactTree", depth, &samples);
+    }
+    for depth in [4, 6, 8] {
+        compare_quick_scorer(depth, 50, &samples);
+    }
+}
@@ -0,0 +1,39 @@
+// Generated by rafor.
+pub const POWER_NUM_FEATURES: usize = 4;
+
+fn power_tree_0(x: &[f32]) -> f32 {
+    if x[0] <= 17.814999 {
+        if x[0] <= 11.684999 {
+            if x[0] <= 8.655 {
+                483.82108
+            } else {
+                476.21265
+            }
+        } else {
+            if x[0] <= 14.445 {
+                468.94318
+            } else {
+                462.05313
+            }
+        }
+    } else {
+        if x[0] <= 22.765 {
+            if x[1] <= 47.335 {
+                457.65247
+            } else {
+                449.07385
+            }
+        } else {
+            if x[1] <= 66.21 {
+                442.96747
+            } else {
+                434.79297
+            }
+        }
+    }
+}
+
+pub fn power_predict(x: &[f32]) -> f32 {
+    assert!(x.len() == POWER_NUM_FEATURES);
+    (power_tree_0(x)) / 1.0
+}
@@ -0,0 +1,110 @@
+// Generated by rafor.
+pub const WINE_NUM_FEATURES: usize = 11;
+pub const WINE_NUM_CLASSES: usize = 6;
+pub const WINE_CLASSES: [i64; WINE_NUM_CLASSES] = [3, 4, 5, 6, 7, 8];
+
+const WINE_TREE_0: [[f32; WINE_NUM_CLASSES]; 16] = [
+    [0.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 0.051282052, 0.53846157, 0.33333334, 0.07692308],
+    [0.0, 0.0, 0.013333334, 0.2, 0.68, 0.10666667],
+    [0.0, 0.01980198, 0.11881188, 0.7029703, 0.14851485, 0.00990099],
+    [0.0, 0.028571429, 0.08571429, 0.34285715, 0.54285717, 0.0],
+    [0.0, 0.023809524, 0.24603175, 0.63095236, 0.0952381, 0.003968254],
+    [0.024390243, 0.11382114, 0.45528457, 0.36585367, 0.040650405, 0.0],
+    [0.0, 0.013333334, 0.26666668, 0.49333334, 0.22666667, 0.0],
+    [0.0, 0.0, 0.10526316, 0.31578946, 0.5263158, 0.05263158],
+    [0.0, 0.022222223, 0.8333333, 0.14444445, 0.0, 0.0],
+    [0.011049724, 0.03314917, 0.59116024, 0.33701658, 0.027624309, 0.0],
+    [0.0046296297, 0.009259259, 0.3472222, 0.5462963, 0.083333336, 0.009259259],
+    [0.0, 0.0, 0.9791667, 0.020833334, 0.0, 0.0],
+    [0.0, 0.0, 0.71428573, 0.2857143, 0.0, 0.0],
+    [0.02173913, 0.054347824, 0.57608694, 0.33695653, 0.010869565, 0.0],
+    [0.009708738, 0.067961164, 0.7621359, 0.1553398, 0.004854369, 0.0],
+];
+
+fn wine_tree_0(x: &[f32]) -> usize {
+    if x[10] <= 10.25 {
+        if x[9] <= 0.575 {
+            if x[6] <= 98.5 {
+                if x[10] <= 9.75 {
+                    15
+                } else {
+                    14
+                }
+            } else {
+                if x[4] <= 0.0725 {
+                    13
+                } else {
+                    12
+                }
+            }
+        } else {
+            if x[6] <= 81.5 {
+                if x[1] <= 0.5475 {
+                    11
+                } else {
+                    10
+                }
+            } else {
+                if x[3] <= 14.6 {
+                    9
+                } else {
+                    0
+                }
+            }
+        }
+    } else {
+        if x[10] <= 11.55 {
+            if x[1] <= 0.375 {
+                if x[8] <= 3.2649999 {
+                    8
+                } else {
+                    7
+                }
+            } else {
+                if x[9] <= 0.585 {
+                    6
+                } else {
+                    5
+                }
+            }
+        } else {
+            if x[9] <= 0.685 {
+                if x[6] <= 15.5 {
+                    4
+                } else {
+                    3
+                }
+            } else {
+                if x[5] <= 18.5 {
+                    2
+                } else {
+                    1
+                }
+            }
+        }
+    }
+}
+
+pub fn wine_proba(x: &[f32]) -> [f32; WINE_NUM_CLASSES] {
+    assert!(x.len() == WINE_NUM_FEATURES);
+    let mut p = [0.0; WINE_NUM_CLASSES];
+    for (p, v) in p.iter_mut().zip(WINE_TREE_0[wine_tree_0(x)].iter()) {
+        *p += v;
+    }
+    for p in p.iter_mut() {
+        *p /= 1.0;
+    }
+    p
+}
+
+pub fn wine_predict(x: &[f32]) -> i64 {
+    let p = wine_proba(x);
+    let mut best = 0;
+    for i in 1..WINE_NUM_CLASSES {
+        if p[i] > p[best] {
+            best = i;
+        }
+    }
+    WINE_CLASSES[best]
+}
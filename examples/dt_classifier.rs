@@ -12,7 +12,7 @@ fn main() {
         0.4, 2.1
     ];
     let targets = [1, 5, 1, -15, 5];
-    let predictor = Classifier::trainer()
+    let predictor = <Classifier>::trainer()
         .with_max_depth(15)
         .train(&dataset, &targets);
 
@@ -1,4 +1,3 @@
-use num_cpus; // Requires num_cpus dependency in Cargo.toml
 use rafor::prelude::*; // Required for .with_option builders.
 use rafor::rf::Regressor;
 
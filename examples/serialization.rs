@@ -1,5 +1,4 @@
-use std::fs::File;
-use rafor::rf::Classifier;
+use rafor::{io, rf::Classifier};
 
 fn main() {
     let dataset = [0.7, 0.0, 0.8, 1.0, 0.7, 0.0];
@@ -7,16 +6,10 @@ fn main() {
     let predictor: Classifier = Classifier::trainer().train(&dataset, &targets);
 
     // Storing model.
-    let mut fout = File::create("model.bin").unwrap();
-    let config = bincode::config::standard();
-    // Requires dependency bincode with 'serde' feature in Cargo.toml:
-    // bincode = { version = "2.0", features = ["serde"] }
-    let _ = bincode::serde::encode_into_std_write(&predictor, &mut fout, config);
+    io::save(&predictor, "model.bin").unwrap();
 
-    // Loading stored model.
-    let mut fin = File::open("model.bin").unwrap();
-    let config = bincode::config::standard();
-    let predictor: Classifier = bincode::serde::decode_from_std_read(&mut fin, config).unwrap();
+    // Loading stored model. The header and the checksum are verified.
+    let predictor: Classifier = io::load("model.bin").unwrap();
 
     let predictions = predictor.predict_batch(&dataset, 1);
     assert_eq!(&predictions, &[1, 5, 1]);
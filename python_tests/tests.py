@@ -1,3 +1,5 @@
+import json
+import math
 import pandas as pd
 import sys
 
@@ -150,6 +152,83 @@ def random_forest_regressor_depth10():
     random_forest_regressor(max_depth=10)
 
 
+def dump(model, path):
+    trees = getattr(model, "estimators_", [model])
+    arrays = ("children_left", "children_right", "feature", "threshold", "value",
+              "missing_go_to_left")
+    document = {
+        "estimator": type(model).__name__,
+        "n_features_in": int(model.n_features_in_),
+        "trees": [{a: getattr(t.tree_, a).tolist() for a in arrays if hasattr(t.tree_, a)}
+                  for t in trees],
+    }
+    if hasattr(model, "classes_"):
+        document["classes"] = model.classes_.tolist()
+    with open(path, "w") as f:
+        json.dump(document, f)
+        f.write("\n")
+
+
+# Writes a forest trained on wine samples with missing values and its probabilities for samples
+# around its thresholds, NaNs of samples are written as nulls.
+def dump_models():
+    df = pd.read_csv('../datasets/winequality-red.csv', sep=';')
+    df = df[df["quality"].between(4, 6)]
+    X = df[["alcohol", "pH", "sulphates"]].astype("float32")
+    X = X.mask(X.index.to_series().mod(7) == 0)
+    y = df["quality"]
+    clf = RandomForestClassifier(random_state=42, max_depth=2, n_estimators=2)
+    clf.fit(X, y)
+    dump(clf, "../datasets/models/sklearn_forest.json")
+    nan = float("nan")
+    samples = [[10.5, 3.2, 0.55], [10.6, 3.3, 0.6], [nan, 3.5, 0.6], [11.0, nan, 0.7],
+               [11.0, 3.25, nan], [nan, nan, nan], [9.8, 3.45, 0.75], [12.4, 2.9, 0.4]]
+    proba = clf.predict_proba(pd.DataFrame(samples, columns=X.columns, dtype="float32"))
+    with open("../datasets/models/sklearn_forest_proba.json", "w") as f:
+        json.dump({"samples": [[None if math.isnan(x) else x for x in s] for s in samples],
+                   "proba": proba.tolist()}, f)
+        f.write("\n")
+
+
+# Writes boosted models trained on wine samples with missing values and their predictions for the
+# samples of the forest above.
+def dump_boosted_models():
+    import lightgbm
+    import xgboost
+    df = pd.read_csv('../datasets/winequality-red.csv', sep=';')
+    X = df[["alcohol", "pH", "sulphates"]].astype("float32")
+    X = X.mask(X.index.to_series().mod(7) == 0)
+    nan = float("nan")
+    samples = pd.DataFrame([[10.5, 3.2, 0.55], [10.6, 3.3, 0.6], [nan, 3.5, 0.6],
+                            [11.0, nan, 0.7], [11.0, 3.25, nan], [nan, nan, nan],
+                            [9.8, 3.45, 0.75], [12.4, 2.9, 0.4]], columns=X.columns,
+                           dtype="float32")
+    predictions = {"samples": [[None if math.isnan(x) else x for x in s]
+                               for s in samples.values.tolist()]}
+    models = [
+        ("xgboost_binary.json", xgboost.XGBClassifier(n_estimators=2, max_depth=2),
+         df["quality"] >= 6),
+        ("xgboost_multiclass.json", xgboost.XGBClassifier(n_estimators=2, max_depth=1),
+         df["quality"].clip(5, 7) - 5),
+        ("lightgbm_regression.txt", lightgbm.LGBMRegressor(n_estimators=2, num_leaves=3,
+                                                           verbose=-1), df["quality"]),
+    ]
+    for name, model, y in models:
+        model.fit(X, y)
+        if name.endswith(".json"):
+            model.get_booster().save_model("../datasets/models/" + name)
+        else:
+            model.booster_.save_model("../datasets/models/" + name)
+        if hasattr(model, "predict_proba"):
+            proba = model.predict_proba(samples)
+            predictions[name] = (proba[:, 1] if proba.shape[1] == 2 else proba).tolist()
+        else:
+            predictions[name] = model.predict(samples).tolist()
+    with open("../datasets/models/boosted_predictions.json", "w") as f:
+        json.dump(predictions, f)
+        f.write("\n")
+
+
 def run_tests():
     decision_tree_classifier_overfit_self()
     decision_tree_classifier_depth10()
@@ -169,5 +248,9 @@ def run_tests():
 
 
 if __name__ == "__main__":
-    run_tests()
+    if sys.argv[1:] == ["dump"]:
+        dump_models()
+        dump_boosted_models()
+    else:
+        run_tests()
 
@@ -0,0 +1,470 @@
+//! Zero-copy random forest archives.
+//!
+//! An archive stores trees in their memory layout, so [ArchivedClassifier] and
+//! [ArchivedRegressor] predict directly from archive bytes without deserializing trees. Archives are
+//! intended for memory mapped files: processes mapping the same archive share one copy of a forest
+//! in the page cache, and opening a forest only checks it with `validate()`.
+//!
+//! The check visits every node, so opening reads the whole archive. For archives from a trusted
+//! source, e.g. written by [save] of the same application, `new_unchecked` checks only the header
+//! and the bounds of sections, so the pages of trees are read when predictions need them.
+//! ```text
+//! header, 64 bytes:
+//!   magic:        8 bytes "RAFORARC"
+//!   version:      u32, ARCHIVE_VERSION
+//!   task:         u8, io::Task
+//!   layout:       u8, io::Layout
+//!   reserved:     2 bytes
+//!   num_features: u64
+//!   num_classes:  u64, 0 for regressors
+//!   num_trees:    u64
+//!   labels:       u64 offset, u64 size of classes mapping serialized by bincode
+//!   trees:        u64 offset of trees table
+//! trees table, 48 bytes per tree:
+//!   nodes:        u64 offset, u64 size of tree nodes
+//!   proba:        u64 offset, u64 size of leaf probability vectors, f32 each
+//!   one_hot:      u64 offset, u64 size of classes of one-hot leaves, u32 each
+//! sections, each is aligned by 64 bytes
+//! ```
+//! All numbers are little-endian, archives can be used on little-endian targets only. The bytes
+//! passed to archived models must be aligned by 64, as memory mapped files are.
+//! ```
+//! use rafor::{archive, rf};
+//! use std::fs::File;
+//! let dataset = [0.7, 0.0, 0.8, 1.0, 0.7, 0.0];
+//! let targets = [1, 5, 1];
+//! let predictor: rf::Classifier = rf::Classifier::trainer().train(&dataset, &targets);
+//! let path = std::env::temp_dir().join("rafor_archive_example.bin");
+//! archive::save(&predictor, &path).unwrap();
+//!
+//! // SAFETY: the file is not modified while mapped.
+//! let mmap = unsafe { archive::Mmap::map(&File::open(&path).unwrap()).unwrap() };
+//! let archived: archive::ArchivedClassifier = archive::ArchivedClassifier::new(&mmap).unwrap();
+//! assert_eq!(archived.predict_batch(&dataset, 1), [1, 5, 1]);
+//! ```
+use crate::{
+    classify,
+    decision_tree::{BlockTreeView, ClassifierView, CompactTreeView, RegressorView},
+    ensemble_predictor::{self, Parallelism},
+    executor::ScopedThreads,
+    io::{Error, Header, Layout, ModelKind, Task, TreeLayout},
+    rf, BlockTree, ClassDecode, ClassTarget, ClassesMapping, CompactTree, FloatTarget,
+    InvalidModel, Label, Predictor, Resolve, Validate,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    mem::size_of,
+    path::Path,
+};
+
+pub use memmap2::Mmap;
+
+/// The version of written archives.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+const MAGIC: &[u8; 8] = b"RAFORARC";
+const HEADER_SIZE: usize = 64;
+const TREE_ENTRY_SIZE: usize = 48;
+const SECTION_ALIGN: usize = 64;
+
+/// Trees that can be used in place in archive memory.
+pub trait ArchiveTree: Predictor + TreeLayout {
+    /// A borrowed tree.
+    type View<'a>: Resolve + Validate + Copy + Send + Sync;
+
+    /// Appends the archived tree to `out`.
+    fn write_archived(&self, out: &mut Vec<u8>);
+
+    /// Returns the tree stored in `bytes` by `write_archived`.
+    fn view_archived(bytes: &[u8]) -> Result<Self::View<'_>, InvalidModel>;
+}
+
+impl ArchiveTree for BlockTree {
+    type View<'a> = BlockTreeView<'a>;
+
+    fn write_archived(&self, out: &mut Vec<u8>) {
+        BlockTree::write_archived(self, out);
+    }
+
+    fn view_archived(bytes: &[u8]) -> Result<Self::View<'_>, InvalidModel> {
+        BlockTreeView::from_archived(bytes)
+    }
+}
+
+impl ArchiveTree for CompactTree {
+    type View<'a> = CompactTreeView<'a>;
+
+    fn write_archived(&self, out: &mut Vec<u8>) {
+        CompactTree::write_archived(self, out);
+    }
+
+    fn view_archived(bytes: &[u8]) -> Result<Self::View<'_>, InvalidModel> {
+        CompactTreeView::from_archived(bytes)
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A random forest that can be stored in archives.
+pub trait Archivable: sealed::Sealed {
+    /// Returns the archive of the forest.
+    fn to_archive(&self) -> Vec<u8>;
+}
+
+/// Writes the archive of `model` to a file at `path`.
+pub fn save(model: &impl Archivable, path: impl AsRef<Path>) -> Result<(), Error> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&model.to_archive())?;
+    writer.flush()?;
+    Ok(())
+}
+
+impl<P: ArchiveTree + Send + Sync, L: Label + Serialize> sealed::Sealed for rf::Classifier<P, L> {}
+
+impl<P: ArchiveTree + Send + Sync, L: Label + Serialize> Archivable for rf::Classifier<P, L> {
+    fn to_archive(&self) -> Vec<u8> {
+        let mut writer = ArchiveWriter::new(Task::CLASSIFIER, P::LAYOUT, self.num_features());
+        writer.put(24, self.num_classes() as u64);
+        let table: Vec<[u64; 6]> = self
+            .trees()
+            .iter()
+            .map(|tree| {
+                let tree = tree.view();
+                let nodes = writer.section(|out| tree.tree.write_archived(out));
+                let proba = writer.section(|out| {
+                    tree.proba
+                        .iter()
+                        .for_each(|p| out.extend_from_slice(&p.to_le_bytes()))
+                });
+                let one_hot = writer.section(|out| {
+                    tree.one_hot
+                        .iter()
+                        .for_each(|c| out.extend_from_slice(&c.to_le_bytes()))
+                });
+                [
+                    nodes[0], nodes[1], proba[0], proba[1], one_hot[0], one_hot[1],
+                ]
+            })
+            .collect();
+        let labels = writer.section(|out| {
+            out.extend(
+                bincode::serde::encode_to_vec(self.classes_map(), bincode::config::standard())
+                    .unwrap(),
+            )
+        });
+        writer.put(40, labels[0]);
+        writer.put(48, labels[1]);
+        writer.finish(&table)
+    }
+}
+
+impl<P: ArchiveTree + Send + Sync> sealed::Sealed for rf::Regressor<P> {}
+
+impl<P: ArchiveTree + Send + Sync> Archivable for rf::Regressor<P> {
+    fn to_archive(&self) -> Vec<u8> {
+        let mut writer = ArchiveWriter::new(Task::REGRESSOR, P::LAYOUT, self.num_features());
+        let table: Vec<[u64; 6]> = self
+            .trees()
+            .iter()
+            .map(|tree| {
+                let nodes = writer.section(|out| tree.predictor().write_archived(out));
+                [nodes[0], nodes[1], 0, 0, 0, 0]
+            })
+            .collect();
+        writer.finish(&table)
+    }
+}
+
+// Builds an archive section by section.
+struct ArchiveWriter {
+    bytes: Vec<u8>,
+}
+
+impl ArchiveWriter {
+    fn new(task: Task, layout: Layout, num_features: usize) -> Self {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&ARCHIVE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&[task as u8, layout as u8, 0, 0]);
+        bytes.extend_from_slice(&(num_features as u64).to_le_bytes());
+        bytes.resize(HEADER_SIZE, 0);
+        Self { bytes }
+    }
+
+    // Appends a section aligned by SECTION_ALIGN, returns its offset and size.
+    fn section(&mut self, write: impl FnOnce(&mut Vec<u8>)) -> [u64; 2] {
+        self.bytes
+            .resize(self.bytes.len().next_multiple_of(SECTION_ALIGN), 0);
+        let offset = self.bytes.len();
+        write(&mut self.bytes);
+        [offset as u64, (self.bytes.len() - offset) as u64]
+    }
+
+    fn put(&mut self, offset: usize, value: u64) {
+        self.bytes[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    // Appends the trees table and returns the archive.
+    fn finish(mut self, table: &[[u64; 6]]) -> Vec<u8> {
+        let trees = self.section(|out| {
+            for v in table.iter().flatten() {
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+        });
+        self.put(32, table.len() as u64);
+        self.put(56, trees[0]);
+        self.bytes
+    }
+}
+
+// Parsed archive header with access to sections.
+struct ArchiveReader<'a> {
+    bytes: &'a [u8],
+    num_features: usize,
+    num_classes: usize,
+}
+
+impl<'a> ArchiveReader<'a> {
+    fn new(bytes: &'a [u8], task: Task, layout: Layout) -> Result<Self, Error> {
+        if bytes.len() < HEADER_SIZE || !bytes.starts_with(MAGIC) {
+            return Err(Error::Codec("not an archive".to_string()));
+        }
+        if !bytes.as_ptr().cast::<[u8; SECTION_ALIGN]>().is_aligned() {
+            return Err(Error::Codec(
+                "archive is not aligned by 64 bytes".to_string(),
+            ));
+        }
+        let version = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+        if version > ARCHIVE_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let reader = Self {
+            bytes,
+            num_features: u64_at(bytes, 16) as usize,
+            num_classes: u64_at(bytes, 24) as usize,
+        };
+        let header = |task, layout| Header {
+            version,
+            kind: ModelKind::FOREST,
+            task,
+            layout,
+            num_features: reader.num_features,
+        };
+        let found_task = Task::from_byte(bytes[12]);
+        let found_layout = Layout::from_byte(bytes[13]);
+        if found_task != Some(task) || found_layout != Some(layout) {
+            return Err(Error::Mismatch {
+                expected: header(task, layout),
+                found: header(
+                    found_task.ok_or(Error::Codec("invalid task".to_string()))?,
+                    found_layout.ok_or(Error::Codec("invalid layout".to_string()))?,
+                ),
+            });
+        }
+        Ok(reader)
+    }
+
+    // Returns the section at `offset` of the header or of a table entry.
+    fn section(&self, offset: usize) -> Result<&'a [u8], Error> {
+        let start = u64_at(self.bytes, offset) as usize;
+        let size = u64_at(self.bytes, offset + 8) as usize;
+        start
+            .checked_add(size)
+            .and_then(|end| self.bytes.get(start..end))
+            .ok_or_else(|| Error::Codec(format!("section at {start} is out of bounds")))
+    }
+
+    // Returns offsets of table entries of trees.
+    fn trees(&self) -> Result<impl Iterator<Item = usize>, Error> {
+        let num_trees = u64_at(self.bytes, 32) as usize;
+        let start = u64_at(self.bytes, 56) as usize;
+        let end = num_trees
+            .checked_mul(TREE_ENTRY_SIZE)
+            .and_then(|size| size.checked_add(start))
+            .filter(|&end| end <= self.bytes.len() && start >= HEADER_SIZE)
+            .ok_or_else(|| Error::Codec("trees table is out of bounds".to_string()))?;
+        if num_trees == 0 {
+            return Err(Error::Invalid(InvalidModel(
+                "forest has no trees".to_string(),
+            )));
+        }
+        Ok((start..end).step_by(TREE_ENTRY_SIZE))
+    }
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+// Plain numbers, any bytes of their size are valid values.
+trait Number: Sized {}
+
+impl Number for f32 {}
+
+impl Number for ClassTarget {}
+
+// Views `bytes` as numbers stored in little-endian order.
+fn numbers<T: Number>(bytes: &[u8]) -> Result<&[T], Error> {
+    let size = size_of::<T>();
+    if cfg!(target_endian = "big")
+        || !bytes.len().is_multiple_of(size)
+        || !bytes.as_ptr().cast::<T>().is_aligned()
+    {
+        return Err(Error::Codec("misaligned section".to_string()));
+    }
+    // SAFETY: `T` is a plain number, so any initialized bytes of its size are a valid value. The
+    // pointer is aligned and the length is a multiple of the size.
+    Ok(unsafe { std::slice::from_raw_parts(bytes.as_ptr().cast::<T>(), bytes.len() / size) })
+}
+
+/// A random forest classifier used in place in archive memory, see [archive](self).
+pub struct ArchivedClassifier<'a, P: ArchiveTree = BlockTree, L = i64> {
+    trees: Vec<ClassifierView<'a, P::View<'a>>>,
+    classes_map: ClassesMapping<L>,
+}
+
+impl<'a, P: ArchiveTree, L: Label + DeserializeOwned> ArchivedClassifier<'a, P, L> {
+    /// Opens a classifier stored in `bytes` aligned by 64 and checks it with `validate()`.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, Error> {
+        Self::open(bytes, true)
+    }
+
+    /// Opens a classifier stored in `bytes` aligned by 64 without checking its trees.
+    ///
+    /// # Safety
+    /// `bytes` must hold a valid archive, e.g. written by [save] or opened by `new` before.
+    /// Predictions of an archive with invalid trees may panic or never finish.
+    pub unsafe fn new_unchecked(bytes: &'a [u8]) -> Result<Self, Error> {
+        Self::open(bytes, false)
+    }
+
+    fn open(bytes: &'a [u8], validate: bool) -> Result<Self, Error> {
+        let archive = ArchiveReader::new(bytes, Task::CLASSIFIER, P::LAYOUT)?;
+        let trees = archive
+            .trees()?
+            .map(|entry| {
+                let view = ClassifierView {
+                    tree: P::view_archived(archive.section(entry)?).map_err(Error::Invalid)?,
+                    proba: numbers(archive.section(entry + 16)?)?,
+                    one_hot: numbers(archive.section(entry + 32)?)?,
+                    num_classes: archive.num_classes,
+                    num_features: archive.num_features,
+                };
+                if validate {
+                    view.validate().map_err(Error::Invalid)?;
+                }
+                Ok(view)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let (classes_map, _): (ClassesMapping<L>, usize) =
+            bincode::serde::decode_from_slice(archive.section(40)?, bincode::config::standard())
+                .map_err(|e| Error::Codec(e.to_string()))?;
+        classes_map
+            .validate(archive.num_classes)
+            .map_err(Error::Invalid)?;
+        Ok(Self { trees, classes_map })
+    }
+}
+
+impl<P: ArchiveTree, L: Label> ArchivedClassifier<'_, P, L> {
+    /// Predicts classes for a set of samples using `num_threads` threads.
+    pub fn predict_batch(&self, dataset: &[f32], num_threads: usize) -> Vec<L> {
+        classify(&self.proba(dataset, num_threads), &self.classes_map)
+    }
+
+    /// Predicts classes probabilities for each sample using `num_threads` threads. The length of
+    /// result vector is number_of_samples * num_classes().
+    pub fn proba(&self, dataset: &[f32], num_threads: usize) -> Vec<f32> {
+        let executor = &ScopedThreads(num_threads);
+        ensemble_predictor::predict(&self.trees, dataset, executor, Parallelism::AUTO)
+    }
+
+    /// Writes classes probabilities for each sample to `out` of length
+    /// number_of_samples * num_classes() on the calling thread.
+    pub fn proba_into(&self, dataset: &[f32], out: &mut [f32]) {
+        ensemble_predictor::predict_into(&self.trees, dataset, out, &ScopedThreads(1));
+    }
+
+    /// Returns a number of features.
+    pub fn num_features(&self) -> usize {
+        self.trees[0].num_features
+    }
+
+    /// Returns a number of trees in forest.
+    pub fn num_trees(&self) -> usize {
+        self.trees.len()
+    }
+}
+
+impl<P: ArchiveTree, L: Label> ClassDecode for ArchivedClassifier<'_, P, L> {
+    type Label = L;
+
+    fn get_decode_table(&self) -> &[L] {
+        self.classes_map.get_decode_table()
+    }
+}
+
+/// A random forest regressor used in place in archive memory, see [archive](self).
+pub struct ArchivedRegressor<'a, P: ArchiveTree = BlockTree> {
+    trees: Vec<RegressorView<P::View<'a>>>,
+}
+
+impl<'a, P: ArchiveTree> ArchivedRegressor<'a, P> {
+    /// Opens a regressor stored in `bytes` aligned by 64 and checks it with `validate()`.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, Error> {
+        Self::open(bytes, true)
+    }
+
+    /// Opens a regressor stored in `bytes` aligned by 64 without checking its trees.
+    ///
+    /// # Safety
+    /// `bytes` must hold a valid archive, e.g. written by [save] or opened by `new` before.
+    /// Predictions of an archive with invalid trees may panic or never finish.
+    pub unsafe fn new_unchecked(bytes: &'a [u8]) -> Result<Self, Error> {
+        Self::open(bytes, false)
+    }
+
+    fn open(bytes: &'a [u8], validate: bool) -> Result<Self, Error> {
+        let archive = ArchiveReader::new(bytes, Task::REGRESSOR, P::LAYOUT)?;
+        let trees = archive
+            .trees()?
+            .map(|entry| {
+                let view = RegressorView {
+                    tree: P::view_archived(archive.section(entry)?).map_err(Error::Invalid)?,
+                    num_features: archive.num_features,
+                };
+                if validate {
+                    view.validate().map_err(Error::Invalid)?;
+                }
+                Ok(view)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Self { trees })
+    }
+
+    /// Predicts regression values for a set of samples using `num_threads` threads.
+    pub fn predict_batch(&self, dataset: &[f32], num_threads: usize) -> Vec<FloatTarget> {
+        let executor = &ScopedThreads(num_threads);
+        ensemble_predictor::predict(&self.trees, dataset, executor, Parallelism::AUTO)
+    }
+
+    /// Writes regression values for a set of samples to `out` of length number_of_samples on the
+    /// calling thread.
+    pub fn predict_into(&self, dataset: &[f32], out: &mut [FloatTarget]) {
+        ensemble_predictor::predict_into(&self.trees, dataset, out, &ScopedThreads(1));
+    }
+
+    /// Returns a number of features.
+    pub fn num_features(&self) -> usize {
+        self.trees[0].num_features
+    }
+
+    /// Returns a number of trees in forest.
+    pub fn num_trees(&self) -> usize {
+        self.trees.len()
+    }
+}
@@ -0,0 +1,283 @@
+// LightGBM text model format. A tree lists its split nodes and its leaves separately: children
+// `c >= 0` are split nodes and children `c < 0` are leaves `!c`. Samples with
+// `sample[split_feature[i]] <= threshold[i]` go to the left child, and bits of `decision_type[i]`
+// tell whether a split is categorical, its default child and the kind of missing values.
+use super::{decode, leaf, split, Booster, DecodedNode, Objective, Split};
+use crate::{decision_tree::threshold_below, io::Error, Export, Predictor, TreeNode, Validate};
+use std::{collections::HashMap, fmt::Write, str::FromStr};
+
+const CATEGORICAL_MASK: u8 = 1;
+const DEFAULT_LEFT_MASK: u8 = 2;
+// Kinds of missing values stored in bits 2 and 3 of `decision_type`.
+const MISSING_NONE: u8 = 0;
+const MISSING_ZERO: u8 = 1;
+const MISSING_NAN: u8 = 2;
+
+type Section<'a> = HashMap<&'a str, &'a str>;
+
+fn codec(message: impl ToString) -> Error {
+    Error::Codec(message.to_string())
+}
+
+fn value<T: FromStr>(section: &Section, key: &str) -> Result<T, Error> {
+    let value = section.get(key).ok_or_else(|| codec(format!("no {key}")))?;
+    value
+        .parse()
+        .map_err(|_| codec(format!("invalid {key} {value}")))
+}
+
+fn values<T: FromStr>(section: &Section, key: &str) -> Result<Vec<T>, Error> {
+    let text = section.get(key).ok_or_else(|| codec(format!("no {key}")))?;
+    text.split_whitespace()
+        .map(|v| v.parse().map_err(|_| codec(format!("invalid {key} {v}"))))
+        .collect()
+}
+
+// Returns the objective written as a name followed by parameters, e.g. `binary sigmoid:1`.
+fn objective(text: &str, num_class: usize) -> Result<Objective, Error> {
+    let mut tokens = text.split_whitespace();
+    let name = tokens.next().unwrap_or("regression");
+    let params: HashMap<&str, &str> = tokens.filter_map(|t| t.split_once(':')).collect();
+    if text.split_whitespace().any(|t| t == "sqrt") {
+        return Err(codec(
+            "regression with sqrt transformation is not supported",
+        ));
+    }
+    let objective = match name {
+        "regression" | "regression_l1" | "huber" | "fair" | "quantile" | "mape" | "lambdarank"
+        | "rank_xendcg" => Objective::IDENTITY,
+        "binary" | "cross_entropy" => Objective::LOGISTIC,
+        "poisson" | "gamma" | "tweedie" => Objective::EXP,
+        "multiclass" => Objective::SOFTMAX,
+        name => return Err(codec(format!("unsupported objective {name}"))),
+    };
+    if params.get("sigmoid").is_some_and(|&s| s.parse() != Ok(1.)) {
+        return Err(codec("sigmoid parameters other than 1 are not supported"));
+    }
+    if objective == Objective::SOFTMAX && params.get("num_class") != Some(&&*num_class.to_string())
+    {
+        return Err(codec(format!("objective {text} of {num_class} classes")));
+    }
+    Ok(objective)
+}
+
+pub(super) fn read<P: Predictor + Validate>(s: &str) -> Result<Booster<P>, Error> {
+    let mut header = Section::new();
+    let mut trees: Vec<Section> = Vec::new();
+    for line in s.lines().map(str::trim) {
+        if line == "end of trees" {
+            break;
+        }
+        if line == "average_output" {
+            return Err(codec("averaged trees are not supported"));
+        }
+        if line.starts_with("Tree=") {
+            trees.push(Section::new());
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            trees.last_mut().unwrap_or(&mut header).insert(key, value);
+        }
+    }
+    let num_features = value::<usize>(&header, "max_feature_idx")? + 1;
+    let num_class = value(&header, "num_class")?;
+    let num_outputs: usize = value(&header, "num_tree_per_iteration")?;
+    let objective = objective(header.get("objective").unwrap_or(&""), num_class)?;
+    let roots = trees
+        .iter()
+        .enumerate()
+        .map(|(i, tree)| {
+            let root = read_tree(tree, num_features).map_err(|e| match e {
+                Error::Codec(e) => codec(format!("tree {i}: {e}")),
+                e => e,
+            })?;
+            Ok((root, i % num_outputs.max(1)))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Booster::from_parts(roots, vec![0.; num_outputs], objective, num_features)
+}
+
+// Arrays of a tree.
+#[derive(Default)]
+struct Tree {
+    split_feature: Vec<i64>,
+    threshold: Vec<f64>,
+    decision_type: Vec<u8>,
+    left_child: Vec<i64>,
+    right_child: Vec<i64>,
+    leaf_value: Vec<f64>,
+}
+
+fn read_tree(section: &Section, num_features: usize) -> Result<TreeNode, Error> {
+    if section.get("is_linear").is_some_and(|&v| v != "0") {
+        return Err(codec("linear trees are not supported"));
+    }
+    if value::<usize>(section, "num_cat")? != 0 {
+        return Err(codec("categorical splits are not supported"));
+    }
+    let num_leaves: usize = value(section, "num_leaves")?;
+    let leaf_value = values(section, "leaf_value")?;
+    if leaf_value.len() != num_leaves {
+        return Err(codec(format!(
+            "{} values of {num_leaves} leaves",
+            leaf_value.len()
+        )));
+    }
+    if num_leaves == 1 {
+        return Ok(leaf(leaf_value[0] as f32));
+    }
+    let tree = Tree {
+        split_feature: values(section, "split_feature")?,
+        threshold: values(section, "threshold")?,
+        decision_type: values(section, "decision_type")?,
+        left_child: values(section, "left_child")?,
+        right_child: values(section, "right_child")?,
+        leaf_value,
+    };
+    if [
+        tree.split_feature.len(),
+        tree.threshold.len(),
+        tree.decision_type.len(),
+        tree.left_child.len(),
+        tree.right_child.len(),
+    ] != [num_leaves - 1; 5]
+    {
+        return Err(codec("arrays of split nodes differ in length"));
+    }
+    let mut visited = vec![false; 2 * num_leaves - 1];
+    read_node(&tree, 0, num_features, &mut visited).map_err(codec)
+}
+
+// Reads a child `c`, `visited` holds split nodes followed by leaves.
+fn read_node(
+    tree: &Tree,
+    c: i64,
+    num_features: usize,
+    visited: &mut [bool],
+) -> Result<TreeNode, String> {
+    let num_splits = tree.split_feature.len();
+    let index = if c < 0 {
+        (!c as usize).checked_add(num_splits)
+    } else {
+        Some(c as usize).filter(|&i| i < num_splits)
+    };
+    let index = index
+        .filter(|&i| i < visited.len())
+        .ok_or_else(|| format!("invalid child {c}"))?;
+    if std::mem::replace(&mut visited[index], true) {
+        return Err(format!("child {c} has more than one parent"));
+    }
+    if c < 0 {
+        return Ok(leaf(tree.leaf_value[!c as usize] as f32));
+    }
+    let i = c as usize;
+    let decision_type = tree.decision_type[i];
+    if decision_type & CATEGORICAL_MASK != 0 {
+        return Err(format!("node {i} has categorical split"));
+    }
+    let default_left = match (decision_type >> 2) & 3 {
+        // Missing values are replaced by zeros.
+        MISSING_NONE => 0. <= tree.threshold[i],
+        MISSING_NAN => decision_type & DEFAULT_LEFT_MASK != 0,
+        MISSING_ZERO => return Err("zeros as missing values are not supported".to_string()),
+        kind => return Err(format!("node {i} has unknown missing type {kind}")),
+    };
+    let feature = tree.split_feature[i];
+    let feature = u16::try_from(feature)
+        .ok()
+        .filter(|&f| (f as usize) < num_features)
+        .ok_or_else(|| format!("node {i} has invalid feature {feature}"))?;
+    let s = Split {
+        feature,
+        threshold: threshold_below(tree.threshold[i]),
+        missing_left: default_left,
+    };
+    let left = read_node(tree, tree.left_child[i], num_features, visited)?;
+    let right = read_node(tree, tree.right_child[i], num_features, visited)?;
+    split(s, left, right, num_features)
+}
+
+pub(super) fn write<P: Predictor + Export + Validate>(booster: &Booster<P>) -> String {
+    let num_outputs = booster.num_outputs();
+    let n = booster.num_features;
+    let objective = match booster.objective {
+        Objective::IDENTITY => "regression".to_string(),
+        Objective::LOGISTIC => "binary sigmoid:1".to_string(),
+        Objective::EXP => "poisson".to_string(),
+        Objective::SOFTMAX => format!("multiclass num_class:{num_outputs}"),
+    };
+    let names: Vec<String> = (0..n).map(|f| format!("Column_{f}")).collect();
+    let mut out = String::new();
+    writeln!(out, "tree\nversion=v4").unwrap();
+    writeln!(out, "num_class={num_outputs}").unwrap();
+    writeln!(out, "num_tree_per_iteration={num_outputs}").unwrap();
+    writeln!(out, "label_index=0\nmax_feature_idx={}", n - 1).unwrap();
+    writeln!(out, "objective={objective}").unwrap();
+    writeln!(out, "feature_names={}", names.join(" ")).unwrap();
+    writeln!(out, "feature_infos={}\n", vec!["none"; n].join(" ")).unwrap();
+
+    // Trees of an iteration are stored in order of outputs, missing trees are zero leaves. Base
+    // scores are added to leaves of the first iteration.
+    let mut outputs: Vec<Vec<TreeNode>> = vec![Vec::new(); num_outputs];
+    for (root, group) in booster.export_trees() {
+        outputs[group].push(root);
+    }
+    let num_iterations = outputs.iter().map(Vec::len).max().unwrap().max(1);
+    for iteration in 0..num_iterations {
+        for (group, roots) in outputs.iter().enumerate() {
+            let zero = leaf(0.);
+            let root = roots.get(iteration).unwrap_or(&zero);
+            let offset = if iteration == 0 {
+                booster.base_score[group]
+            } else {
+                0.
+            };
+            let index = iteration * num_outputs + group;
+            write_tree(&mut out, index, root, n, offset);
+        }
+    }
+    out.push_str("end of trees\n");
+    out
+}
+
+fn write_tree(out: &mut String, index: usize, root: &TreeNode, num_features: usize, offset: f32) {
+    let mut tree = Tree::default();
+    write_node(&mut tree, root, num_features, offset);
+    let join = |values: &[String]| values.join(" ");
+    let text = |values: &[f64]| join(&values.iter().map(f64::to_string).collect::<Vec<_>>());
+    let ints = |values: &[i64]| join(&values.iter().map(i64::to_string).collect::<Vec<_>>());
+    writeln!(out, "Tree={index}").unwrap();
+    writeln!(out, "num_leaves={}\nnum_cat=0", tree.leaf_value.len()).unwrap();
+    if !tree.split_feature.is_empty() {
+        writeln!(out, "split_feature={}", ints(&tree.split_feature)).unwrap();
+        writeln!(out, "threshold={}", text(&tree.threshold)).unwrap();
+        let decision_type: Vec<i64> = tree.decision_type.iter().map(|&d| d as i64).collect();
+        writeln!(out, "decision_type={}", ints(&decision_type)).unwrap();
+        writeln!(out, "left_child={}", ints(&tree.left_child)).unwrap();
+        writeln!(out, "right_child={}", ints(&tree.right_child)).unwrap();
+    }
+    writeln!(out, "leaf_value={}", text(&tree.leaf_value)).unwrap();
+    writeln!(out, "is_linear=0\nshrinkage=1\n\n").unwrap();
+}
+
+// Appends a node to arrays and returns its child reference.
+fn write_node(tree: &mut Tree, node: &TreeNode, num_features: usize, offset: f32) -> i64 {
+    match decode(node, num_features) {
+        DecodedNode::Leaf(value) => {
+            tree.leaf_value
+                .push((f32::from_bits(value) + offset) as f64);
+            !(tree.leaf_value.len() as i64 - 1)
+        }
+        DecodedNode::Split(s, left, right) => {
+            let i = tree.split_feature.len();
+            tree.split_feature.push(s.feature as i64);
+            tree.threshold.push(s.threshold as f64);
+            let default_left = if s.missing_left { DEFAULT_LEFT_MASK } else { 0 };
+            tree.decision_type.push(MISSING_NAN << 2 | default_left);
+            tree.left_child.push(0);
+            tree.right_child.push(0);
+            tree.left_child[i] = write_node(tree, left, num_features, offset);
+            tree.right_child[i] = write_node(tree, right, num_features, offset);
+            i as i64
+        }
+    }
+}
@@ -0,0 +1,285 @@
+//! Gradient boosted tree ensembles trained by XGBoost or LightGBM.
+//!
+//! A [Booster] is an additive ensemble of regression trees: the raw score of an output is a base
+//! score plus the sum of leaf values of trees of the output, and predictions are raw scores
+//! transformed by the [Objective] of the model. Models are imported from and exported to
+//! * XGBoost JSON models, written by `Booster.save_model("model.json")`;
+//! * LightGBM text models, written by `Booster.save_model("model.txt")`.
+//!
+//! Only numerical splits are supported. Missing values are `NaN`s of samples, they go to the
+//! default child of a split. LightGBM models with zeros as missing values, categorical splits or
+//! linear trees are rejected, as well as XGBoost models with several targets or non-tree boosters.
+//! ```
+//! use rafor::boosting::{Booster, Objective};
+//! let model = r#"
+//! tree
+//! version=v4
+//! num_class=1
+//! num_tree_per_iteration=1
+//! label_index=0
+//! max_feature_idx=1
+//! objective=regression
+//! feature_names=a b
+//! feature_infos=none none
+//!
+//! Tree=0
+//! num_leaves=2
+//! num_cat=0
+//! split_feature=1
+//! threshold=0.5
+//! decision_type=10
+//! left_child=-1
+//! right_child=-2
+//! leaf_value=1 2
+//!
+//! end of trees
+//! "#;
+//! let booster: Booster = Booster::from_lightgbm_text(model).unwrap();
+//! assert_eq!(booster.objective(), Objective::IDENTITY);
+//! assert_eq!(booster.predict(&[0.0, 0.2, 0.0, 0.8, 0.0, f32::NAN], 1), [1.0, 2.0, 1.0]);
+//! let exported = Booster::<rafor::CompactTree>::from_xgboost_json(&booster.to_xgboost_json());
+//! assert_eq!(exported.unwrap().predict(&[0.0, f32::NAN], 1), [1.0]);
+//! ```
+use crate::{
+    decision_tree::{build, decode, split, DecodedNode, RegressorModel, Split},
+    executor::{Executor, ScopedThreads},
+    io::Error,
+    BlockTree, Export, InvalidModel, Predictor, TreeNode, Validate,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+mod lightgbm;
+mod xgboost;
+
+// Number of samples in a block processed by all trees in prediction.
+const SAMPLES_BLOCK: usize = 64;
+
+/// Transformation of raw scores into predictions.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Objective {
+    /// Raw scores are predictions, e.g. for squared error regression.
+    #[default]
+    IDENTITY,
+    /// Predicts probabilities of binary classification as sigmoid of raw scores.
+    LOGISTIC,
+    /// Predicts exponents of raw scores, e.g. for Poisson, gamma or Tweedie regression.
+    EXP,
+    /// Predicts probabilities of classes as softmax of raw scores of all outputs.
+    SOFTMAX,
+}
+
+impl Objective {
+    // Transforms raw scores of a sample.
+    fn transform(&self, scores: &mut [f32]) {
+        match self {
+            Objective::IDENTITY => {}
+            Objective::LOGISTIC => scores.iter_mut().for_each(|x| *x = 1. / (1. + (-*x).exp())),
+            Objective::EXP => scores.iter_mut().for_each(|x| *x = x.exp()),
+            Objective::SOFTMAX => {
+                let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                scores.iter_mut().for_each(|x| *x = (*x - max).exp());
+                let total: f32 = scores.iter().sum();
+                scores.iter_mut().for_each(|x| *x /= total);
+            }
+        }
+    }
+}
+
+/// Additive ensemble of regression trees.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Booster<P: Predictor = BlockTree> {
+    trees: Vec<RegressorModel<P>>,
+    // Output of each tree.
+    groups: Vec<usize>,
+    // Raw score of each output before adding trees.
+    base_score: Vec<f32>,
+    objective: Objective,
+    num_features: usize,
+}
+
+fn leaf(value: f32) -> TreeNode {
+    TreeNode::Leaf(value.to_bits())
+}
+
+impl<P: Predictor + Validate> Booster<P> {
+    // Builds a model from trees made of `split` and `leaf` nodes and checks it with `validate()`.
+    fn from_parts(
+        roots: Vec<(TreeNode, usize)>,
+        base_score: Vec<f32>,
+        objective: Objective,
+        num_features: usize,
+    ) -> Result<Self, Error> {
+        let (trees, groups) = roots
+            .iter()
+            .map(|(root, group)| {
+                let tree = RegressorModel::from_parts(build(root), num_features);
+                (tree, *group)
+            })
+            .unzip();
+        let model = Self {
+            trees,
+            groups,
+            base_score,
+            objective,
+            num_features,
+        };
+        model.validate().map_err(Error::Invalid)?;
+        Ok(model)
+    }
+
+    /// Checks that the model is consistent and predicts finite raw scores.
+    pub fn validate(&self) -> Result<(), InvalidModel> {
+        if self.base_score.is_empty() {
+            return Err(InvalidModel("model has no outputs".to_string()));
+        }
+        if self.objective != Objective::SOFTMAX && self.num_outputs() != 1 {
+            return Err(InvalidModel(format!(
+                "{:?} objective of {} outputs",
+                self.objective,
+                self.num_outputs()
+            )));
+        }
+        if let Some(score) = self.base_score.iter().find(|s| !s.is_finite()) {
+            return Err(InvalidModel(format!("base score {score} is not finite")));
+        }
+        if self.groups.len() != self.trees.len() {
+            return Err(InvalidModel("outputs of trees are not given".to_string()));
+        }
+        if let Some(group) = self.groups.iter().find(|&&g| g >= self.num_outputs()) {
+            return Err(InvalidModel(format!(
+                "tree of output {group} in model of {} outputs",
+                self.num_outputs()
+            )));
+        }
+        for tree in self.trees.iter() {
+            if tree.num_features() != self.num_features {
+                return Err(InvalidModel(format!(
+                    "tree of {} features in model of {} features",
+                    tree.num_features(),
+                    self.num_features
+                )));
+            }
+            tree.validate()?;
+        }
+        Ok(())
+    }
+}
+
+impl<P: Predictor + Export + Validate> Booster<P> {
+    /// Reads a model from XGBoost JSON model format. Models with `multi:softmax` objective predict
+    /// probabilities of classes like `multi:softprob`, where XGBoost predicts the most probable
+    /// class.
+    pub fn from_xgboost_json(s: &str) -> Result<Self, Error> {
+        xgboost::read(s)
+    }
+
+    /// Returns the model in XGBoost JSON model format.
+    pub fn to_xgboost_json(&self) -> String {
+        xgboost::write(self)
+    }
+
+    /// Reads a model from LightGBM text model format.
+    pub fn from_lightgbm_text(s: &str) -> Result<Self, Error> {
+        lightgbm::read(s)
+    }
+
+    /// Returns the model in LightGBM text model format. LightGBM models have no base score, so
+    /// base scores are added to leaf values of the first tree of each output.
+    pub fn to_lightgbm_text(&self) -> String {
+        lightgbm::write(self)
+    }
+
+    // Returns decodable trees and their outputs.
+    fn export_trees(&self) -> impl Iterator<Item = (TreeNode, usize)> + '_ {
+        self.trees
+            .iter()
+            .zip(self.groups.iter())
+            .map(|(tree, &group)| (tree.predictor().export(), group))
+    }
+}
+
+impl<P: Predictor> Booster<P> {
+    /// Predicts a set of samples using `num_threads` threads. Returns `num_outputs()` predictions
+    /// per sample.
+    pub fn predict(&self, dataset: &[f32], num_threads: usize) -> Vec<f32>
+    where
+        P: Sync,
+    {
+        self.predict_in(dataset, &ScopedThreads(num_threads))
+    }
+
+    /// Predicts a set of samples using given `executor`.
+    pub fn predict_in(&self, dataset: &[f32], executor: &dyn Executor) -> Vec<f32>
+    where
+        P: Sync,
+    {
+        let mut result = self.predict_raw_in(dataset, executor);
+        for scores in result.chunks_mut(self.num_outputs()) {
+            self.objective.transform(scores);
+        }
+        result
+    }
+
+    /// Returns raw scores of a set of samples, i.e. predictions before the transformation by the
+    /// objective, using `num_threads` threads.
+    pub fn predict_raw(&self, dataset: &[f32], num_threads: usize) -> Vec<f32>
+    where
+        P: Sync,
+    {
+        self.predict_raw_in(dataset, &ScopedThreads(num_threads))
+    }
+
+    /// Returns raw scores of a set of samples using given `executor`.
+    pub fn predict_raw_in(&self, dataset: &[f32], executor: &dyn Executor) -> Vec<f32>
+    where
+        P: Sync,
+    {
+        let n = self.num_features;
+        assert!(dataset.len().is_multiple_of(n));
+        let num_outputs = self.num_outputs();
+        let mut result = self.base_score.repeat(dataset.len() / n);
+        let blocks = Mutex::new(
+            result
+                .chunks_mut(SAMPLES_BLOCK * num_outputs)
+                .zip(dataset.chunks(SAMPLES_BLOCK * n)),
+        );
+        executor.execute(&|| loop {
+            let Some((out, samples)) = blocks.lock().unwrap().next() else {
+                break;
+            };
+            for (scores, sample) in out.chunks_mut(num_outputs).zip(samples.chunks(n)) {
+                for (tree, &group) in self.trees.iter().zip(self.groups.iter()) {
+                    scores[group] += tree.predict_one(sample);
+                }
+            }
+        });
+        result
+    }
+
+    /// Returns the objective of the model.
+    pub fn objective(&self) -> Objective {
+        self.objective
+    }
+
+    /// Returns raw scores of outputs before adding trees.
+    pub fn base_score(&self) -> &[f32] {
+        &self.base_score
+    }
+
+    /// Returns the number of features of samples.
+    pub fn num_features(&self) -> usize {
+        self.num_features
+    }
+
+    /// Returns the number of predictions per sample: the number of classes for `SOFTMAX`
+    /// objective, otherwise one.
+    pub fn num_outputs(&self) -> usize {
+        self.base_score.len()
+    }
+
+    /// Returns the number of trees.
+    pub fn num_trees(&self) -> usize {
+        self.trees.len()
+    }
+}
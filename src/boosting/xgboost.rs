@@ -0,0 +1,370 @@
+// XGBoost JSON model format. Samples with `sample[split_indices[i]] < split_conditions[i]` go to
+// the left child, and leaves hold their values in `split_conditions`. The base score is stored as
+// a prediction, i.e. after the transformation by the objective.
+use super::{decode, leaf, split, Booster, DecodedNode, Objective, Split};
+use crate::{io::Error, Export, Predictor, TreeNode, Validate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Parent of a root node.
+const NO_PARENT: i64 = i32::MAX as i64;
+
+// Version of XGBoost which wrote the format of exported models.
+const VERSION: [u32; 3] = [2, 1, 0];
+
+type Params = BTreeMap<String, String>;
+
+#[derive(Serialize, Deserialize)]
+struct Model {
+    learner: Learner,
+    #[serde(default)]
+    version: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Learner {
+    #[serde(default)]
+    attributes: Params,
+    #[serde(default)]
+    feature_names: Vec<String>,
+    #[serde(default)]
+    feature_types: Vec<String>,
+    gradient_booster: GradientBooster,
+    learner_model_param: Params,
+    objective: ObjectiveParams,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GradientBooster {
+    name: String,
+    // Absent for boosters other than gbtree.
+    #[serde(default)]
+    model: Option<Trees>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Trees {
+    gbtree_model_param: Params,
+    #[serde(default)]
+    iteration_indptr: Vec<usize>,
+    tree_info: Vec<usize>,
+    trees: Vec<Tree>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Tree {
+    id: usize,
+    tree_param: Params,
+    left_children: Vec<i64>,
+    right_children: Vec<i64>,
+    #[serde(default)]
+    parents: Vec<i64>,
+    split_indices: Vec<i64>,
+    split_conditions: Vec<f32>,
+    #[serde(default)]
+    split_type: Vec<u8>,
+    // Integers or booleans depending on the version of XGBoost.
+    default_left: Vec<serde_json::Value>,
+    #[serde(default)]
+    categories: Vec<i64>,
+    #[serde(default)]
+    categories_nodes: Vec<i64>,
+    #[serde(default)]
+    categories_segments: Vec<i64>,
+    #[serde(default)]
+    categories_sizes: Vec<i64>,
+    #[serde(default)]
+    base_weights: Vec<f32>,
+    #[serde(default)]
+    loss_changes: Vec<f32>,
+    #[serde(default)]
+    sum_hessian: Vec<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ObjectiveParams {
+    name: String,
+    #[serde(flatten)]
+    params: BTreeMap<String, serde_json::Value>,
+}
+
+fn codec(message: impl ToString) -> Error {
+    Error::Codec(message.to_string())
+}
+
+fn param<T: std::str::FromStr>(params: &Params, key: &str, default: Option<T>) -> Result<T, Error> {
+    match params.get(key) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| codec(format!("invalid {key} {value}"))),
+        None => default.ok_or_else(|| codec(format!("no {key}"))),
+    }
+}
+
+// Transforms a base score into a raw score.
+type Margin = fn(f32) -> f32;
+
+// Returns the objective and the transformation of base scores into raw scores.
+fn objective(name: &str) -> Result<(Objective, Margin), Error> {
+    let logit = |p: f32| -(1. / p - 1.).ln();
+    Ok(match name {
+        "reg:squarederror"
+        | "reg:squaredlogerror"
+        | "reg:pseudohubererror"
+        | "reg:absoluteerror"
+        | "reg:quantileerror" => (Objective::IDENTITY, |x| x),
+        "reg:logistic" | "binary:logistic" => (Objective::LOGISTIC, logit),
+        "binary:logitraw" => (Objective::IDENTITY, logit),
+        "count:poisson" | "reg:gamma" | "reg:tweedie" => (Objective::EXP, f32::ln),
+        // XGBoost predicts the most probable class for multi:softmax, probabilities are
+        // predicted here as for multi:softprob.
+        "multi:softprob" | "multi:softmax" => (Objective::SOFTMAX, |x| x),
+        name => return Err(codec(format!("unsupported objective {name}"))),
+    })
+}
+
+// Parses base scores written as a number or a list of numbers in brackets.
+fn base_score(text: &str) -> Result<Vec<f32>, Error> {
+    let list = text.trim_start_matches('[').trim_end_matches(']');
+    list.split(',')
+        .map(|v| {
+            v.trim()
+                .parse()
+                .map_err(|_| codec(format!("invalid base_score {text}")))
+        })
+        .collect()
+}
+
+pub(super) fn read<P: Predictor + Validate>(s: &str) -> Result<Booster<P>, Error> {
+    let model: Model = serde_json::from_str(s).map_err(codec)?;
+    let learner = &model.learner;
+    let trees = learner
+        .gradient_booster
+        .model
+        .as_ref()
+        .filter(|_| learner.gradient_booster.name == "gbtree")
+        .ok_or_else(|| {
+            codec(format!(
+                "unsupported booster {}",
+                learner.gradient_booster.name
+            ))
+        })?;
+    let params = &learner.learner_model_param;
+    let num_features: usize = param(params, "num_feature", None)?;
+    let num_class: usize = param(params, "num_class", Some(0))?;
+    if param(params, "num_target", Some(1))? != 1 {
+        return Err(codec("models with several targets are not supported"));
+    }
+    let (objective, margin) = objective(&learner.objective.name)?;
+    let num_outputs = num_class.max(1);
+    let mut base_score: Vec<f32> = base_score(&param::<String>(params, "base_score", None)?)?
+        .into_iter()
+        .map(margin)
+        .collect();
+    if base_score.len() == 1 {
+        base_score = vec![base_score[0]; num_outputs];
+    }
+    if trees.tree_info.len() != trees.trees.len() {
+        return Err(codec("outputs of trees are not given"));
+    }
+    let roots = trees
+        .trees
+        .iter()
+        .zip(trees.tree_info.iter())
+        .map(|(tree, &group)| Ok((read_tree(tree, num_features)?, group)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    Booster::from_parts(roots, base_score, objective, num_features)
+}
+
+fn read_tree(tree: &Tree, num_features: usize) -> Result<TreeNode, Error> {
+    let n = tree.left_children.len();
+    if [
+        tree.right_children.len(),
+        tree.split_indices.len(),
+        tree.split_conditions.len(),
+        tree.default_left.len(),
+    ] != [n; 4]
+    {
+        return Err(codec(format!(
+            "arrays of tree {} differ in length",
+            tree.id
+        )));
+    }
+    let mut visited = vec![false; n];
+    read_node(tree, 0, num_features, &mut visited)
+        .map_err(|e| codec(format!("tree {}: {e}", tree.id)))
+}
+
+fn read_node(
+    tree: &Tree,
+    id: i64,
+    num_features: usize,
+    visited: &mut [bool],
+) -> Result<TreeNode, String> {
+    let i = usize::try_from(id)
+        .ok()
+        .filter(|&i| i < visited.len())
+        .ok_or_else(|| format!("invalid node {id}"))?;
+    if std::mem::replace(&mut visited[i], true) {
+        return Err(format!("node {i} has more than one parent"));
+    }
+    if tree.left_children[i] == -1 {
+        return Ok(leaf(tree.split_conditions[i]));
+    }
+    if tree.split_type.get(i).is_some_and(|&t| t != 0) {
+        return Err(format!("node {i} has categorical split"));
+    }
+    let feature = tree.split_indices[i];
+    let feature = u16::try_from(feature)
+        .ok()
+        .filter(|&f| (f as usize) < num_features)
+        .ok_or_else(|| format!("node {i} has invalid feature {feature}"))?;
+    let default_left = &tree.default_left[i];
+    let default_left = default_left
+        .as_bool()
+        .or_else(|| default_left.as_u64().map(|v| v != 0))
+        .ok_or_else(|| format!("node {i} has invalid default_left {default_left}"))?;
+    let s = Split {
+        feature,
+        threshold: tree.split_conditions[i].next_down(),
+        missing_left: default_left,
+    };
+    let left = read_node(tree, tree.left_children[i], num_features, visited)?;
+    let right = read_node(tree, tree.right_children[i], num_features, visited)?;
+    split(s, left, right, num_features)
+}
+
+pub(super) fn write<P: Predictor + Export + Validate>(booster: &Booster<P>) -> String {
+    let num_outputs = booster.num_outputs();
+    let num_class = if booster.objective == Objective::SOFTMAX {
+        num_outputs
+    } else {
+        0
+    };
+    let num_class_text = num_class.to_string();
+    let (name, param, key, value) = match booster.objective {
+        Objective::IDENTITY => (
+            "reg:squarederror",
+            "reg_loss_param",
+            "scale_pos_weight",
+            "1",
+        ),
+        Objective::LOGISTIC => ("binary:logistic", "reg_loss_param", "scale_pos_weight", "1"),
+        Objective::EXP => (
+            "count:poisson",
+            "poisson_regression_param",
+            "max_delta_step",
+            "0.699999988",
+        ),
+        Objective::SOFTMAX => (
+            "multi:softprob",
+            "softmax_multiclass_param",
+            "num_class",
+            num_class_text.as_str(),
+        ),
+    };
+    let objective_params = BTreeMap::from([(param.to_string(), serde_json::json!({ key: value }))]);
+
+    let mut base_score = booster.base_score.clone();
+    for score in base_score.iter_mut() {
+        *score = match booster.objective {
+            Objective::LOGISTIC => 1. / (1. + (-*score).exp()),
+            Objective::EXP => score.exp(),
+            Objective::IDENTITY | Objective::SOFTMAX => *score,
+        };
+    }
+    let base_score = if base_score.iter().all(|&s| s == base_score[0]) {
+        base_score[0].to_string()
+    } else {
+        let scores: Vec<String> = base_score.iter().map(f32::to_string).collect();
+        format!("[{}]", scores.join(","))
+    };
+
+    let (trees, tree_info): (Vec<Tree>, Vec<usize>) = booster
+        .export_trees()
+        .enumerate()
+        .map(|(id, (root, group))| (write_tree(id, &root, booster.num_features), group))
+        .unzip();
+    let mut iteration_indptr: Vec<usize> = (0..trees.len()).step_by(num_outputs).collect();
+    iteration_indptr.push(trees.len());
+
+    let model = Model {
+        learner: Learner {
+            attributes: Params::new(),
+            feature_names: Vec::new(),
+            feature_types: Vec::new(),
+            gradient_booster: GradientBooster {
+                name: "gbtree".to_string(),
+                model: Some(Trees {
+                    gbtree_model_param: Params::from([
+                        ("num_parallel_tree".to_string(), "1".to_string()),
+                        ("num_trees".to_string(), trees.len().to_string()),
+                    ]),
+                    iteration_indptr,
+                    tree_info,
+                    trees,
+                }),
+            },
+            learner_model_param: Params::from([
+                ("base_score".to_string(), base_score),
+                ("boost_from_average".to_string(), "1".to_string()),
+                ("num_class".to_string(), num_class.to_string()),
+                ("num_feature".to_string(), booster.num_features.to_string()),
+                ("num_target".to_string(), "1".to_string()),
+            ]),
+            objective: ObjectiveParams {
+                name: name.to_string(),
+                params: objective_params,
+            },
+        },
+        version: VERSION.to_vec(),
+    };
+    serde_json::to_string(&model).unwrap()
+}
+
+fn write_tree(id: usize, root: &TreeNode, num_features: usize) -> Tree {
+    let mut tree = Tree {
+        id,
+        ..Tree::default()
+    };
+    write_node(&mut tree, root, NO_PARENT, num_features);
+    let num_nodes = tree.left_children.len();
+    tree.tree_param = Params::from([
+        ("num_deleted".to_string(), "0".to_string()),
+        ("num_feature".to_string(), num_features.to_string()),
+        ("num_nodes".to_string(), num_nodes.to_string()),
+        ("size_leaf_vector".to_string(), "1".to_string()),
+    ]);
+    tree
+}
+
+fn write_node(tree: &mut Tree, node: &TreeNode, parent: i64, num_features: usize) -> i64 {
+    let id = tree.left_children.len();
+    tree.left_children.push(-1);
+    tree.right_children.push(-1);
+    tree.parents.push(parent);
+    tree.split_indices.push(0);
+    tree.split_conditions.push(0.);
+    tree.split_type.push(0);
+    tree.default_left.push(0.into());
+    tree.base_weights.push(0.);
+    tree.loss_changes.push(0.);
+    tree.sum_hessian.push(0.);
+    match decode(node, num_features) {
+        DecodedNode::Leaf(value) => {
+            let value = f32::from_bits(value);
+            tree.split_conditions[id] = value;
+            tree.base_weights[id] = value;
+        }
+        DecodedNode::Split(s, left, right) => {
+            let left = write_node(tree, left, id as i64, num_features);
+            let right = write_node(tree, right, id as i64, num_features);
+            tree.left_children[id] = left;
+            tree.right_children[id] = right;
+            tree.split_indices[id] = s.feature as i64;
+            tree.split_conditions[id] = s.threshold.next_up();
+            tree.default_left[id] = (s.missing_left as u8).into();
+        }
+    }
+    id as i64
+}
@@ -1,33 +1,85 @@
-use crate::ClassTarget;
+use crate::{ClassTarget, InvalidModel};
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeSet, HashMap};
+use std::{
+    collections::BTreeSet,
+    fmt::{Debug, Display},
+};
+
+/// A class label of classifiers. Any ordered type can be used as a label, e.g. `i64`, `String`,
+/// `bool` or a custom enum. Classes are ordered by their labels.
+pub trait Label: Ord + Clone + Debug {}
+
+impl<T: Ord + Clone + Debug> Label for T {}
+
+/// An error returned when a label is not one of the classes of a model.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct UnknownLabel<L>(pub L);
+
+impl<L: Debug> Display for UnknownLabel<L> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown class label {:?}.", self.0)
+    }
+}
+
+impl<L: Debug> std::error::Error for UnknownLabel<L> {}
 
 #[derive(Default, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
-pub struct ClassesMapping {
-    decode_table: Vec<i64>,
+pub struct ClassesMapping<L = i64> {
+    decode_table: Vec<L>,
 }
 
-impl ClassesMapping {
-    pub fn with_encode(labels: &[i64]) -> (Self, Vec<ClassTarget>) {
-        let mut m = Self {
-            decode_table: Vec::new(),
-        };
-
-        let classes: BTreeSet<i64> = labels.iter().copied().collect();
-        m.decode_table = classes.iter().copied().collect();
-        let encode_table: HashMap<i64, usize> =
-            classes.iter().enumerate().map(|(i, &x)| (x, i)).collect();
-        let encoded_labels = labels
+impl<L: Label> ClassesMapping<L> {
+    /// Creates a mapping for given classes. Duplicates are ignored.
+    pub fn with_classes(classes: &[L]) -> Self {
+        let classes: BTreeSet<L> = classes.iter().cloned().collect();
+        Self {
+            decode_table: classes.into_iter().collect(),
+        }
+    }
+
+    /// Returns a mapping that holds classes of both mappings.
+    pub fn union(&self, other: &Self) -> Self {
+        let classes: BTreeSet<L> = self
+            .decode_table
             .iter()
-            .map(|v| *encode_table.get(v).unwrap() as ClassTarget)
+            .chain(other.decode_table.iter())
+            .cloned()
             .collect();
-        (m, encoded_labels)
+        Self {
+            decode_table: classes.into_iter().collect(),
+        }
+    }
+
+    // Checks that the mapping holds `num_classes` distinct ordered classes.
+    pub(crate) fn validate(&self, num_classes: usize) -> Result<(), InvalidModel> {
+        if self.decode_table.len() != num_classes {
+            return Err(InvalidModel(format!(
+                "{} class labels for {num_classes} classes",
+                self.decode_table.len()
+            )));
+        }
+        if !self.decode_table.is_sorted_by(|a, b| a < b) {
+            return Err(InvalidModel("class labels are not ordered".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Encodes labels with existing decode table. Panics if a label is not in the table.
+    pub fn encode_targets(&self, labels: &[L]) -> Vec<ClassTarget> {
+        ClassDecode::encode(self, labels)
+            .unwrap_or_else(|e| panic!("{e}"))
+            .into_iter()
+            .map(|c| c as ClassTarget)
+            .collect()
     }
 }
 
 pub trait ClassDecode {
+    /// Type of class labels.
+    type Label: Label;
+
     /// Returns a decode table of length num_classes().
-    fn get_decode_table(&self) -> &[i64];
+    fn get_decode_table(&self) -> &[Self::Label];
 
     /// Returns a number of classes stored int table.
     #[inline(always)]
@@ -35,16 +87,31 @@ pub trait ClassDecode {
         self.get_decode_table().len()
     }
 
-    /// Decodes a usize value into i64 according to decode table.
+    /// Decodes a usize value into a label according to decode table.
     #[inline(always)]
-    fn decode(&self, class_enc: usize) -> i64 {
-        self.get_decode_table()[class_enc]
+    fn decode(&self, class_enc: usize) -> Self::Label {
+        self.get_decode_table()[class_enc].clone()
+    }
+
+    /// Encodes labels into class indexes, i.e. the positions of classes in probability
+    /// distributions. Returns an error if a label is not one of the classes.
+    fn encode(&self, labels: &[Self::Label]) -> Result<Vec<usize>, UnknownLabel<Self::Label>> {
+        labels
+            .iter()
+            .map(|v| {
+                self.get_decode_table()
+                    .binary_search(v)
+                    .map_err(|_| UnknownLabel(v.clone()))
+            })
+            .collect()
     }
 }
 
-impl ClassDecode for ClassesMapping {
+impl<L: Label> ClassDecode for ClassesMapping<L> {
+    type Label = L;
+
     #[inline(always)]
-    fn get_decode_table(&self) -> &[i64] {
+    fn get_decode_table(&self) -> &[L] {
         &self.decode_table
     }
 }
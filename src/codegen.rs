@@ -0,0 +1,441 @@
+//! Ahead-of-time code generation of trained models.
+//!
+//! Models provide `to_rust_source(prefix)` and `to_c_source(prefix)` methods, which return a
+//! standalone source file without dependencies. Each tree is compiled into a function of nested
+//! `if` statements. The generated file defines:
+//! * `NUM_FEATURES` -- the length of a sample;
+//! * `predict(x)` -- predicted value or class label for a sample `x`;
+//! * for classifiers, `NUM_CLASSES`, the decode table `CLASSES` and `proba(x)` which returns
+//!   (in Rust) or writes to `out` of length `NUM_CLASSES` (in C) probabilities of classes.
+//!
+//! Names of all functions start with `prefix`, and names of all constants start with `prefix` in
+//! upper case, so several models can be compiled into one binary or C translation unit.
+//!
+//! Class labels are written with [SourceLiteral], which is implemented for integers, `bool` and
+//! `String`.
+//! ```
+//! use rafor::dt;
+//! let dataset = [0.7, 0.0, 0.8, 1.0, 0.7, 0.0];
+//! let targets = [1, 5, 1];
+//! let predictor = <dt::Classifier>::trainer().train(&dataset, &targets);
+//! let source = predictor.to_rust_source("wine_");
+//! assert!(source.contains("pub fn wine_predict(x: &[f32]) -> i64"));
+//! assert!(source.contains("pub const WINE_NUM_FEATURES: usize = 2;"));
+//! ```
+use crate::{
+    decision_tree::{ClassifierModel, RegressorModel},
+    Export, Predictor, TreeNode,
+};
+use std::fmt::Write;
+
+/// A class label that can be written as a literal of generated source.
+pub trait SourceLiteral {
+    /// Returns the Rust type of literals.
+    fn rust_type() -> &'static str;
+    /// Returns the C type of literals.
+    fn c_type() -> &'static str;
+    /// Returns the value as a literal of type `rust_type()`.
+    fn rust_literal(&self) -> String;
+    /// Returns the value as a literal of type `c_type()`.
+    fn c_literal(&self) -> String;
+}
+
+macro_rules! integer_literal {
+    ($($t:ty, $c:expr, $suffix:expr);*) => {
+        $(impl SourceLiteral for $t {
+            fn rust_type() -> &'static str {
+                stringify!($t)
+            }
+
+            fn c_type() -> &'static str {
+                $c
+            }
+
+            fn rust_literal(&self) -> String {
+                self.to_string()
+            }
+
+            fn c_literal(&self) -> String {
+                // C has no negative literals, and the magnitude of the minimum overflows the type.
+                if *self != 0 && *self == <$t>::MIN {
+                    format!("({}{} - 1)", *self + 1, $suffix)
+                } else {
+                    format!("{self}{}", $suffix)
+                }
+            }
+        })*
+    };
+}
+
+integer_literal!(
+    i8, "signed char", ""; i16, "short", ""; i32, "int", ""; i64, "long long", "LL";
+    u8, "unsigned char", "U"; u16, "unsigned short", "U"; u32, "unsigned", "U";
+    u64, "unsigned long long", "ULL"
+);
+
+impl SourceLiteral for bool {
+    fn rust_type() -> &'static str {
+        "bool"
+    }
+
+    fn c_type() -> &'static str {
+        "int"
+    }
+
+    fn rust_literal(&self) -> String {
+        self.to_string()
+    }
+
+    fn c_literal(&self) -> String {
+        (*self as i32).to_string()
+    }
+}
+
+impl SourceLiteral for String {
+    fn rust_type() -> &'static str {
+        "&str"
+    }
+
+    fn c_type() -> &'static str {
+        "const char *"
+    }
+
+    fn rust_literal(&self) -> String {
+        format!("{self:?}")
+    }
+
+    fn c_literal(&self) -> String {
+        let mut s = String::from("\"");
+        for b in self.bytes() {
+            match b {
+                b'"' | b'\\' => write!(s, "\\{}", b as char).unwrap(),
+                0x20..0x7f => s.push(b as char),
+                _ => write!(s, "\\{b:03o}").unwrap(),
+            }
+        }
+        s.push('"');
+        s
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Language {
+    Rust,
+    C,
+}
+
+// Writes `value` as a float literal.
+fn float(value: f32, lang: Language) -> String {
+    assert!(value.is_finite(), "Cannot write {value} as a literal.");
+    // Debug representation always contains a dot or an exponent and round-trips.
+    match lang {
+        Language::Rust => format!("{value:?}"),
+        Language::C => format!("{value:?}f"),
+    }
+}
+
+// Prefixes of names of generated items.
+struct Names<'a> {
+    // Prefix of functions.
+    function: &'a str,
+    // Prefix of constants, the upper case of `function`.
+    constant: String,
+}
+
+impl<'a> Names<'a> {
+    fn new(prefix: &'a str) -> Self {
+        assert!(
+            prefix
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_'),
+            "Prefix {prefix:?} is not a part of an identifier."
+        );
+        Self {
+            function: prefix,
+            constant: prefix.to_ascii_uppercase(),
+        }
+    }
+}
+
+// Writes a function of nested ifs `name` returning the leaf of a sample of `num_features`
+// features, leaves are written by `leaf`.
+fn write_tree(
+    src: &mut String,
+    lang: Language,
+    name: &str,
+    ret_type: &str,
+    tree: &TreeNode,
+    num_features: usize,
+    leaf: &dyn Fn(u32) -> String,
+) {
+    match lang {
+        Language::Rust => writeln!(src, "fn {name}(x: &[f32]) -> {ret_type} {{").unwrap(),
+        Language::C => writeln!(src, "static {ret_type} {name}(const float *x) {{").unwrap(),
+    }
+    write_node(src, lang, tree, num_features, 1, leaf);
+    src.push_str("}\n\n");
+}
+
+fn write_node(
+    src: &mut String,
+    lang: Language,
+    node: &TreeNode,
+    num_features: usize,
+    depth: usize,
+    leaf: &dyn Fn(u32) -> String,
+) {
+    let indent = "    ".repeat(depth);
+    match node {
+        TreeNode::Leaf(value) => match lang {
+            Language::Rust => writeln!(src, "{indent}{}", leaf(*value)).unwrap(),
+            Language::C => writeln!(src, "{indent}return {};", leaf(*value)).unwrap(),
+        },
+        TreeNode::Split {
+            feature,
+            threshold,
+            left,
+            right,
+        } => {
+            // Negated features keep the exact comparison of the tree, see `TreeNode`.
+            let value = match (*feature as usize).checked_sub(num_features) {
+                Some(feature) => format!("-x[{feature}]"),
+                None => format!("x[{feature}]"),
+            };
+            let condition = format!("{value} <= {}", float(*threshold, lang));
+            match lang {
+                Language::Rust => writeln!(src, "{indent}if {condition} {{").unwrap(),
+                Language::C => writeln!(src, "{indent}if ({condition}) {{").unwrap(),
+            }
+            write_node(src, lang, left, num_features, depth + 1, leaf);
+            writeln!(src, "{indent}}} else {{").unwrap();
+            write_node(src, lang, right, num_features, depth + 1, leaf);
+            writeln!(src, "{indent}}}").unwrap();
+        }
+    }
+}
+
+fn collect_leaves(node: &TreeNode, leaves: &mut Vec<u32>) {
+    match node {
+        TreeNode::Leaf(value) => leaves.push(*value),
+        TreeNode::Split { left, right, .. } => {
+            collect_leaves(left, leaves);
+            collect_leaves(right, leaves);
+        }
+    }
+}
+
+fn write_const(src: &mut String, lang: Language, name: &str, value: usize) {
+    match lang {
+        Language::Rust => writeln!(src, "pub const {name}: usize = {value};").unwrap(),
+        Language::C => writeln!(src, "#define {name} {value}").unwrap(),
+    }
+}
+
+fn header(lang: Language) -> String {
+    match lang {
+        Language::Rust => String::from("// Generated by rafor.\n"),
+        Language::C => String::from("/* Generated by rafor. */\n"),
+    }
+}
+
+// Generates source of a classifier. Each tree returns the row of its probabilities table
+// `TREE_<id>`.
+pub(crate) fn classifier<P: Predictor + Export, L: SourceLiteral>(
+    trees: &[ClassifierModel<P>],
+    classes: &[L],
+    lang: Language,
+    prefix: &str,
+) -> String {
+    let Names {
+        function: f,
+        constant: c,
+    } = Names::new(prefix);
+    let mut src = header(lang);
+    let num_classes = trees[0].num_classes();
+    assert!(classes.len() == num_classes);
+    write_const(
+        &mut src,
+        lang,
+        &format!("{c}NUM_FEATURES"),
+        trees[0].num_features(),
+    );
+    write_const(&mut src, lang, &format!("{c}NUM_CLASSES"), num_classes);
+    let classes_type = match lang {
+        Language::Rust => {
+            let literals: Vec<String> = classes.iter().map(|c| c.rust_literal()).collect();
+            writeln!(
+                src,
+                "pub const {c}CLASSES: [{}; {c}NUM_CLASSES] = [{}];\n",
+                L::rust_type(),
+                literals.join(", ")
+            )
+            .unwrap();
+            L::rust_type()
+        }
+        Language::C => {
+            let literals: Vec<String> = classes.iter().map(|c| c.c_literal()).collect();
+            writeln!(
+                src,
+                "static {} const {c}CLASSES[{c}NUM_CLASSES] = {{{}}};\n",
+                L::c_type(),
+                literals.join(", ")
+            )
+            .unwrap();
+            L::c_type()
+        }
+    };
+
+    for (id, tree) in trees.iter().enumerate() {
+        let node = tree.predictor().export();
+        // Rows of the table are distinct leaf values of the tree.
+        let mut values = Vec::new();
+        collect_leaves(&node, &mut values);
+        values.sort();
+        values.dedup();
+        let rows: Vec<String> = values
+            .iter()
+            .map(|&v| {
+                let row = tree.leaf(v);
+                let row: Vec<String> = row.iter().map(|&p| float(p, lang)).collect();
+                match lang {
+                    Language::Rust => format!("    [{}],\n", row.join(", ")),
+                    Language::C => format!("    {{{}}},\n", row.join(", ")),
+                }
+            })
+            .collect();
+        match lang {
+            Language::Rust => writeln!(
+                src,
+                "const {c}TREE_{id}: [[f32; {c}NUM_CLASSES]; {}] = [\n{}];\n",
+                rows.len(),
+                rows.concat()
+            ),
+            Language::C => writeln!(
+                src,
+                "static const float {c}TREE_{id}[{}][{c}NUM_CLASSES] = {{\n{}}};\n",
+                rows.len(),
+                rows.concat()
+            ),
+        }
+        .unwrap();
+        let ret_type = match lang {
+            Language::Rust => "usize",
+            Language::C => "unsigned",
+        };
+        write_tree(
+            &mut src,
+            lang,
+            &format!("{f}tree_{id}"),
+            ret_type,
+            &node,
+            trees[0].num_features(),
+            &|v| values.binary_search(&v).unwrap().to_string(),
+        );
+    }
+
+    let num_trees = float(trees.len() as f32, lang);
+    match lang {
+        Language::Rust => {
+            writeln!(
+                src,
+                "pub fn {f}proba(x: &[f32]) -> [f32; {c}NUM_CLASSES] {{"
+            )
+            .unwrap();
+            writeln!(src, "    assert!(x.len() == {c}NUM_FEATURES);").unwrap();
+            writeln!(src, "    let mut p = [0.0; {c}NUM_CLASSES];").unwrap();
+            for id in 0..trees.len() {
+                writeln!(src, "    for (p, v) in p.iter_mut().zip({c}TREE_{id}[{f}tree_{id}(x)].iter()) {{\n        *p += v;\n    }}").unwrap();
+            }
+            writeln!(
+                src,
+                "    for p in p.iter_mut() {{\n        *p /= {num_trees};\n    }}"
+            )
+            .unwrap();
+            src.push_str("    p\n}\n\n");
+            writeln!(src, "pub fn {f}predict(x: &[f32]) -> {classes_type} {{").unwrap();
+            writeln!(src, "    let p = {f}proba(x);\n    let mut best = 0;").unwrap();
+            writeln!(src, "    for i in 1..{c}NUM_CLASSES {{\n        if p[i] > p[best] {{\n            best = i;\n        }}\n    }}").unwrap();
+            writeln!(src, "    {c}CLASSES[best]\n}}").unwrap();
+        }
+        Language::C => {
+            writeln!(src, "void {f}proba(const float *x, float *out) {{").unwrap();
+            src.push_str("    unsigned i, row;\n");
+            writeln!(
+                src,
+                "    for (i = 0; i < {c}NUM_CLASSES; ++i) {{\n        out[i] = 0.0f;\n    }}"
+            )
+            .unwrap();
+            for id in 0..trees.len() {
+                writeln!(src, "    row = {f}tree_{id}(x);\n    for (i = 0; i < {c}NUM_CLASSES; ++i) {{\n        out[i] += {c}TREE_{id}[row][i];\n    }}").unwrap();
+            }
+            writeln!(
+                src,
+                "    for (i = 0; i < {c}NUM_CLASSES; ++i) {{\n        out[i] /= {num_trees};\n    }}"
+            )
+            .unwrap();
+            src.push_str("}\n\n");
+            writeln!(src, "{classes_type} {f}predict(const float *x) {{").unwrap();
+            writeln!(
+                src,
+                "    float p[{c}NUM_CLASSES];\n    unsigned i, best = 0;\n    {f}proba(x, p);"
+            )
+            .unwrap();
+            writeln!(src, "    for (i = 1; i < {c}NUM_CLASSES; ++i) {{\n        if (p[i] > p[best]) {{\n            best = i;\n        }}\n    }}").unwrap();
+            writeln!(src, "    return {c}CLASSES[best];\n}}").unwrap();
+        }
+    }
+    src
+}
+
+// Generates source of a regressor. Each tree returns its prediction.
+pub(crate) fn regressor<P: Predictor + Export>(
+    trees: &[RegressorModel<P>],
+    lang: Language,
+    prefix: &str,
+) -> String {
+    let Names {
+        function: f,
+        constant: c,
+    } = Names::new(prefix);
+    let mut src = header(lang);
+    write_const(
+        &mut src,
+        lang,
+        &format!("{c}NUM_FEATURES"),
+        trees[0].num_features(),
+    );
+    src.push('\n');
+    let ret_type = match lang {
+        Language::Rust => "f32",
+        Language::C => "float",
+    };
+    for (id, tree) in trees.iter().enumerate() {
+        write_tree(
+            &mut src,
+            lang,
+            &format!("{f}tree_{id}"),
+            ret_type,
+            &tree.predictor().export(),
+            tree.num_features(),
+            &|v| float(f32::from_bits(v), lang),
+        );
+    }
+
+    let sum: Vec<String> = (0..trees.len())
+        .map(|id| format!("{f}tree_{id}(x)"))
+        .collect();
+    let num_trees = float(trees.len() as f32, lang);
+    match lang {
+        Language::Rust => {
+            writeln!(src, "pub fn {f}predict(x: &[f32]) -> f32 {{").unwrap();
+            writeln!(src, "    assert!(x.len() == {c}NUM_FEATURES);").unwrap();
+        }
+        Language::C => writeln!(src, "float {f}predict(const float *x) {{").unwrap(),
+    }
+    let statement = format!("({}) / {num_trees}", sum.join(" + "));
+    match lang {
+        Language::Rust => writeln!(src, "    {statement}\n}}").unwrap(),
+        Language::C => writeln!(src, "    return {statement};\n}}").unwrap(),
+    }
+    src
+}
@@ -2,6 +2,9 @@ use serde::{Deserialize, Serialize};
 
 use super::Resolve;
 use super::Trainable;
+use super::BATCH_LANES;
+use super::{feature_value, Export, InvalidModel, TreeNode, Validate};
+use std::mem::{offset_of, size_of};
 // Block decision tree consists of 7-vertex balanced trees, where some vertices may become unused
 // during training. Each 7-vertex tree is packed into block of size not larger than 64 bytes.
 // Blocks are aligned by 64 bytes, which makes blocks cache-friendly during inference.
@@ -31,11 +34,20 @@ struct Block {
     offsets: [u32; 4],
 }
 
+// Archives store blocks in memory layout of `Block`, see `write_archived`.
+const _: () = assert!(size_of::<Block>() == 64);
+
 #[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct BlockTree {
     tree: Vec<Block>,
 }
 
+/// A borrowed [BlockTree], e.g. a tree used in place in memory of an archive.
+#[derive(Clone, Copy, Debug)]
+pub struct BlockTreeView<'a> {
+    tree: &'a [Block],
+}
+
 #[derive(Clone, Default, Debug, PartialEq, Eq)]
 pub struct Handle {
     // Index of a block.
@@ -125,7 +137,7 @@ impl Trainable for BlockTree {
 impl Block {
     #[inline(always)]
     fn compare(&self, index: usize, sample: &[f32]) -> bool {
-        sample[self.features[index] as usize] <= self.values[index]
+        feature_value(sample, self.features[index] as usize) <= self.values[index]
     }
 }
 
@@ -134,9 +146,168 @@ impl BlockTree {
     pub fn num_blocks(&self) -> usize {
         self.tree.len()
     }
+
+    /// Returns a borrowed view of the tree.
+    #[inline(always)]
+    pub fn view(&self) -> BlockTreeView<'_> {
+        BlockTreeView { tree: &self.tree }
+    }
+
+    // Appends blocks to `out` in memory layout of `Block` on little-endian targets with zeroed
+    // padding.
+    pub(crate) fn write_archived(&self, out: &mut Vec<u8>) {
+        for b in self.tree.iter() {
+            let mut bytes = [0_u8; size_of::<Block>()];
+            let mut put = |offset: usize, value: &[u8]| {
+                bytes[offset..offset + value.len()].copy_from_slice(value);
+            };
+            for i in 0..7 {
+                put(
+                    offset_of!(Block, values) + 4 * i,
+                    &b.values[i].to_le_bytes(),
+                );
+                put(
+                    offset_of!(Block, features) + 2 * i,
+                    &b.features[i].to_le_bytes(),
+                );
+            }
+            for i in 0..4 {
+                put(
+                    offset_of!(Block, offsets) + 4 * i,
+                    &b.offsets[i].to_le_bytes(),
+                );
+            }
+            out.extend_from_slice(&bytes);
+        }
+    }
+}
+
+impl<'a> BlockTreeView<'a> {
+    // Views blocks written by `write_archived`, `bytes` must be aligned by 64.
+    pub(crate) fn from_archived(bytes: &'a [u8]) -> Result<Self, InvalidModel> {
+        if cfg!(target_endian = "big") {
+            return Err(InvalidModel(
+                "archives require little-endian target".to_string(),
+            ));
+        }
+        if !bytes.len().is_multiple_of(size_of::<Block>())
+            || !bytes.as_ptr().cast::<Block>().is_aligned()
+        {
+            return Err(InvalidModel("misaligned blocks".to_string()));
+        }
+        // SAFETY: `Block` is `repr(C)` and consists of numbers only, so any initialized bytes of its
+        // size are a valid `Block`. The pointer is aligned and the length is a multiple of the size.
+        let tree = unsafe {
+            std::slice::from_raw_parts(
+                bytes.as_ptr().cast::<Block>(),
+                bytes.len() / size_of::<Block>(),
+            )
+        };
+        Ok(Self { tree })
+    }
+
+    fn export_node(&self, block: usize, i: usize) -> TreeNode {
+        let b = &self.tree[block];
+        let (left, right) = if i < 3 {
+            (
+                self.export_node(block, 2 * i + 1),
+                self.export_node(block, 2 * i + 2),
+            )
+        } else if b.offsets[i - 3] == 0 {
+            return TreeNode::Leaf(b.values[i].to_bits());
+        } else {
+            let offset = b.offsets[i - 3] as usize;
+            (self.export_node(offset, 0), self.export_node(offset + 1, 0))
+        };
+
+        if left == right {
+            // Leaf values are propagated to lower nodes of a block, such nodes are not splits.
+            return left;
+        }
+        TreeNode::Split {
+            feature: b.features[i],
+            threshold: b.values[i],
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+}
+
+impl Export for BlockTree {
+    fn export(&self) -> TreeNode {
+        assert!(!self.tree.is_empty());
+        self.view().export_node(0, 0)
+    }
+}
+
+impl Validate for BlockTree {
+    fn validate(
+        &self,
+        num_features: usize,
+        is_leaf: &dyn Fn(u32) -> bool,
+    ) -> Result<(), InvalidModel> {
+        self.view().validate(num_features, is_leaf)
+    }
+}
+
+impl Validate for BlockTreeView<'_> {
+    fn validate(
+        &self,
+        num_features: usize,
+        is_leaf: &dyn Fn(u32) -> bool,
+    ) -> Result<(), InvalidModel> {
+        if self.tree.is_empty() {
+            return Err(InvalidModel("tree has no blocks".to_string()));
+        }
+        // Child blocks follow their parents, so blocks are checked after all their parents.
+        let mut has_parent = vec![false; self.tree.len()];
+        has_parent[0] = true;
+        for (index, b) in self.tree.iter().enumerate() {
+            let error = |e: String| Err(InvalidModel(format!("block {index}: {e}")));
+            if !has_parent[index] {
+                return error("unreachable block".to_string());
+            }
+            for i in 0..7 {
+                let offset = if i < 3 { 0 } else { b.offsets[i - 3] as usize };
+                if i >= 3 && offset == 0 {
+                    // Terminal node without children is a leaf.
+                    if !is_leaf(b.values[i].to_bits()) {
+                        return error(format!("invalid leaf value {:#x}", b.values[i].to_bits()));
+                    }
+                    continue;
+                }
+                if b.features[i] as usize >= 2 * num_features {
+                    return error(format!("feature {} is out of range", b.features[i]));
+                }
+                if i >= 3 {
+                    if offset <= index || offset + 1 >= self.tree.len() {
+                        return error(format!("invalid child blocks offset {offset}"));
+                    }
+                    if has_parent[offset] || has_parent[offset + 1] {
+                        return error(format!("child blocks {offset} have another parent"));
+                    }
+                    has_parent[offset] = true;
+                    has_parent[offset + 1] = true;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Resolve for BlockTree {
+    #[inline(always)]
+    fn resolve(&self, sample: &[f32]) -> u32 {
+        self.view().resolve(sample)
+    }
+
+    #[inline(always)]
+    fn resolve_batch(&self, samples: &[f32], num_features: usize, out: &mut [u32]) {
+        self.view().resolve_batch(samples, num_features, out);
+    }
+}
+
+impl Resolve for BlockTreeView<'_> {
     fn resolve(&self, sample: &[f32]) -> u32 {
         assert!(!self.tree.is_empty());
         let mut cur_block = 0;
@@ -175,4 +346,38 @@ impl Resolve for BlockTree {
             }
         }
     }
+
+    // Traverses BATCH_LANES samples in lockstep: each step advances every unfinished sample by one
+    // block, so memory loads of different samples overlap. Branchless selection of terminal node
+    // is used, since branches are unpredictable across samples.
+    fn resolve_batch(&self, samples: &[f32], num_features: usize, out: &mut [u32]) {
+        assert!(!self.tree.is_empty());
+        assert!(samples.len() == out.len() * num_features);
+        for (out, samples) in out
+            .chunks_mut(BATCH_LANES)
+            .zip(samples.chunks(BATCH_LANES * num_features))
+        {
+            let mut blocks = [0_usize; BATCH_LANES];
+            let mut done = [false; BATCH_LANES];
+            let mut num_done = 0;
+            while num_done < out.len() {
+                for (lane, sample) in samples.chunks_exact(num_features).enumerate() {
+                    if done[lane] {
+                        continue;
+                    }
+                    let b = &self.tree[blocks[lane]];
+                    let c0 = !b.compare(0, sample) as usize;
+                    let c1 = !b.compare(1 + c0, sample) as usize;
+                    let i = 2 * c0 + c1;
+                    if b.offsets[i] == 0 {
+                        out[lane] = b.values[i + 3].to_bits();
+                        done[lane] = true;
+                        num_done += 1;
+                    } else {
+                        blocks[lane] = b.offsets[i] as usize + !b.compare(i + 3, sample) as usize;
+                    }
+                }
+            }
+        }
+    }
 }
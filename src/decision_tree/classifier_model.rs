@@ -1,47 +1,246 @@
-use super::{splitter::GiniSplitter, trainer, Predictor, TrainConfig};
+use super::{
+    build, resolve_each, splitter::GiniSplitter, trainer, Export, InvalidModel, Predictor, Resolve,
+    TrainConfig, Validate,
+};
 
 use crate::{ClassTarget, SampleWeight, Trainset};
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
+// Maximal number of classes for which pure leaves are represented by windows of ONE_HOT.
+const MAX_ONE_HOT_CLASSES: usize = 1024;
+
+// Zeros with a single 1.0 in the middle. A window of length `num_classes` starting at
+// `MAX_ONE_HOT_CLASSES - 1 - c` is the one-hot probability vector of class `c`, so pure leaves of
+// all trees share this memory.
+static ONE_HOT: [f32; 2 * MAX_ONE_HOT_CLASSES - 1] = {
+    let mut one_hot = [0.; 2 * MAX_ONE_HOT_CLASSES - 1];
+    one_hot[MAX_ONE_HOT_CLASSES - 1] = 1.;
+    one_hot
+};
+
+// Leaf value `v` of the tree refers to one-hot vector of class `one_hot[v]` if `v < one_hot.len()`,
+// otherwise to row `v - one_hot.len()` of `proba`. Identical rows are stored once.
 #[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ClassifierModel<P: Predictor> {
     proba: Vec<f32>,
     num_classes: usize,
     predictor: P,
     num_features: usize,
+    // Models serialized before one-hot leaves were added have none.
+    #[serde(default)]
+    one_hot: Vec<ClassTarget>,
+}
+
+// Classifier model with borrowed tree and leaf tables, e.g. parts of a model stored in an archive.
+#[derive(Clone, Copy, Debug)]
+pub struct ClassifierView<'a, V> {
+    pub tree: V,
+    pub proba: &'a [f32],
+    pub one_hot: &'a [ClassTarget],
+    pub num_classes: usize,
+    pub num_features: usize,
+}
+
+impl<'a, V: Resolve> ClassifierView<'a, V> {
+    pub fn predict_into(&self, dataset: &[f32], out: &mut [f32]) {
+        assert!(dataset.len().is_multiple_of(self.num_features));
+        assert!(out.len() == dataset.len() / self.num_features * self.num_classes);
+        resolve_each(&self.tree, dataset, self.num_features, |i, leaf| {
+            out[i * self.num_classes..(i + 1) * self.num_classes].copy_from_slice(self.leaf(leaf));
+        });
+    }
+
+    // Adds probabilities for each sample to `out`.
+    pub fn accumulate(&self, dataset: &[f32], out: &mut [f32]) {
+        assert!(out.len() == dataset.len() / self.num_features * self.num_classes);
+        resolve_each(&self.tree, dataset, self.num_features, |i, leaf| {
+            let proba = self.leaf(leaf);
+            for (o, p) in out[i * self.num_classes..].iter_mut().zip(proba.iter()) {
+                *o += p;
+            }
+        });
+    }
+
+    // Returns probabilities of classes for a leaf value of the tree.
+    #[inline(always)]
+    pub fn leaf(&self, value: u32) -> &'a [f32] {
+        let i = value as usize;
+        if i < self.one_hot.len() {
+            let offset = MAX_ONE_HOT_CLASSES - 1 - self.one_hot[i] as usize;
+            &ONE_HOT[offset..offset + self.num_classes]
+        } else {
+            let row = (i - self.one_hot.len()) * self.num_classes;
+            &self.proba[row..row + self.num_classes]
+        }
+    }
+
+    #[inline(always)]
+    pub fn predict_one(&self, sample: &[f32]) -> &'a [f32] {
+        self.leaf(self.tree.resolve(sample))
+    }
+}
+
+impl<V: Validate> ClassifierView<'_, V> {
+    // Checks that probability vectors are distributions and the tree refers to existing vectors
+    // for samples of `num_features` features.
+    pub fn validate(&self) -> Result<(), InvalidModel> {
+        let error = |e: &str| Err(InvalidModel(e.to_string()));
+        if self.num_features == 0 {
+            return error("model has no features");
+        }
+        if self.num_classes == 0 || !self.proba.len().is_multiple_of(self.num_classes) {
+            return error("probability vectors do not match the number of classes");
+        }
+        if !self.one_hot.is_empty() && self.num_classes > MAX_ONE_HOT_CLASSES {
+            return error("too many classes for one-hot leaves");
+        }
+        if self.one_hot.iter().any(|&c| c as usize >= self.num_classes) {
+            return error("one-hot leaf class is out of range");
+        }
+        for row in self.proba.chunks(self.num_classes) {
+            if row.iter().any(|p| !(0. ..=1.).contains(p))
+                || (row.iter().sum::<f32>() - 1.).abs() > 1e-3
+            {
+                return error("probability vector is not a distribution");
+            }
+        }
+        let num_leaves = self.one_hot.len() + self.proba.len() / self.num_classes;
+        self.tree
+            .validate(self.num_features, &|v| (v as usize) < num_leaves)
+    }
 }
 
 #[derive(Default)]
 struct ProbabilityAggregator {
     proba: Vec<f32>,
     num_classes: usize,
+    // Rows of `proba` by bits of their values.
+    rows: HashMap<Vec<u32>, u32>,
+    // Number of one-hot leaf values preceding rows of `proba`.
+    num_one_hot: usize,
 }
 
 impl<P: Predictor> ClassifierModel<P> {
+    // Creates a model where leaf value `v` refers to row `v` of `proba`.
+    pub fn from_parts(
+        proba: Vec<f32>,
+        num_classes: usize,
+        predictor: P,
+        num_features: usize,
+    ) -> Self {
+        Self {
+            proba,
+            num_classes,
+            predictor,
+            num_features,
+            one_hot: Vec::new(),
+        }
+    }
+
     pub fn predict(&self, dataset: &[f32]) -> Vec<f32> {
-        assert!(dataset.len() % self.num_features == 0);
+        assert!(dataset.len().is_multiple_of(self.num_features));
         let num_samples = dataset.len() / self.num_features;
         let mut result = vec![0.; num_samples * self.num_classes];
-
-        for (r, sample) in result
-            .chunks_exact_mut(self.num_classes)
-            .zip(dataset.chunks_exact(self.num_features))
-        {
-            r.copy_from_slice(self.predict_one(sample));
-        }
+        self.predict_into(dataset, &mut result);
         result
     }
 
+    pub fn predict_into(&self, dataset: &[f32], out: &mut [f32]) {
+        self.view().predict_into(dataset, out);
+    }
+
+    // Adds probabilities for each sample to `out`.
+    pub fn accumulate(&self, dataset: &[f32], out: &mut [f32]) {
+        self.view().accumulate(dataset, out);
+    }
+
     #[inline(always)]
     pub fn num_features(&self) -> usize {
         self.num_features
     }
 
+    #[inline(always)]
+    pub fn num_classes(&self) -> usize {
+        self.num_classes
+    }
+
+    // Returns the tree, leaf values of the tree are passed to `leaf()`.
+    pub fn predictor(&self) -> &P {
+        &self.predictor
+    }
+
+    // Rebuilds the tree without splits whose subtrees give the same leaf value.
+    pub fn simplify(&mut self)
+    where
+        P: Export,
+    {
+        self.predictor = build(&self.predictor.export());
+    }
+
+    // Checks that probability vectors are distributions and the tree refers to existing vectors
+    // for samples of `num_features` features.
+    pub fn validate(&self) -> Result<(), InvalidModel>
+    where
+        P: Validate,
+    {
+        self.view().validate()
+    }
+
+    // Returns probabilities of classes for a leaf value of the tree.
+    #[inline(always)]
+    pub fn leaf(&self, value: u32) -> &[f32] {
+        self.view().leaf(value)
+    }
+
     #[inline(always)]
     pub fn predict_one(&self, sample: &[f32]) -> &[f32] {
-        let i = self.predictor.resolve(sample) as usize * self.num_classes;
-        &self.proba[i..i + self.num_classes]
+        self.view().predict_one(sample)
+    }
+
+    // Returns the model with borrowed tree and leaf tables.
+    #[inline(always)]
+    pub fn view(&self) -> ClassifierView<'_, &P> {
+        ClassifierView {
+            tree: &self.predictor,
+            proba: &self.proba,
+            one_hot: &self.one_hot,
+            num_classes: self.num_classes,
+            num_features: self.num_features,
+        }
+    }
+
+    // Moves probabilities of each class `i` to class `classes[i]` of `num_classes` classes.
+    pub fn remap_classes(&mut self, classes: &[ClassTarget], num_classes: usize) {
+        assert!(classes.len() == self.num_classes);
+        if num_classes > MAX_ONE_HOT_CLASSES && !self.one_hot.is_empty() {
+            // One-hot vectors do not fit into ONE_HOT window, store them as rows.
+            let mut proba = Vec::with_capacity(self.proba.len() + self.one_hot.len());
+            for &c in self.one_hot.iter() {
+                let mut row = vec![0.; self.num_classes];
+                row[c as usize] = 1.;
+                proba.extend_from_slice(&row);
+            }
+            proba.extend_from_slice(&self.proba);
+            self.proba = proba;
+            self.one_hot.clear();
+        }
+        for c in self.one_hot.iter_mut() {
+            *c = classes[*c as usize];
+        }
+
+        let mut proba = vec![0.; self.proba.len() / self.num_classes * num_classes];
+        for (dst, src) in proba
+            .chunks_exact_mut(num_classes)
+            .zip(self.proba.chunks_exact(self.num_classes))
+        {
+            for (&c, &p) in classes.iter().zip(src.iter()) {
+                dst[c as usize] = p;
+            }
+        }
+        self.proba = proba;
+        self.num_classes = num_classes;
     }
 
     pub fn train(
@@ -62,6 +261,7 @@ impl<P: Predictor> ClassifierModel<P> {
             num_classes: num_cls,
             predictor: tree,
             num_features: ts.num_features,
+            one_hot: (0..probability_aggr.num_one_hot as ClassTarget).collect(),
         }
     }
 }
@@ -71,24 +271,37 @@ impl ProbabilityAggregator {
         Self {
             proba: Vec::new(),
             num_classes,
+            rows: HashMap::new(),
+            num_one_hot: if num_classes <= MAX_ONE_HOT_CLASSES {
+                num_classes
+            } else {
+                0
+            },
         }
     }
 }
 
 impl trainer::Aggregator<ClassTarget> for ProbabilityAggregator {
     fn aggregate(&mut self, leaf_items: &[(ClassTarget, SampleWeight)]) -> u32 {
-        let mut bins = vec![0. as f64; self.num_classes];
+        let mut bins = vec![0_f64; self.num_classes];
         let mut total_weight: f64 = 0.;
         for &(x, w) in leaf_items.iter() {
             bins[x as usize] += w as f64;
             total_weight += w as f64;
         }
 
-        let offset = self.proba.len() / self.num_classes;
-        for x in bins.iter_mut() {
-            self.proba.push((*x / total_weight) as f32);
+        let row: Vec<f32> = bins.iter().map(|x| (x / total_weight) as f32).collect();
+        let pure_class = row.iter().position(|&p| p == 1.);
+        if let Some(c) = pure_class.filter(|_| self.num_one_hot > 0) {
+            return c as u32;
         }
 
-        offset as u32
+        let key: Vec<u32> = row.iter().map(|p| p.to_bits()).collect();
+        let offset = self.proba.len() / self.num_classes;
+        let row_index = *self.rows.entry(key).or_insert_with(|| {
+            self.proba.extend_from_slice(&row);
+            offset as u32
+        });
+        self.num_one_hot as u32 + row_index
     }
 }
@@ -1,15 +1,21 @@
+use std::fmt::Display;
+
 mod block_tree;
 mod classifier_model;
 mod compact_tree;
 mod metrics;
+mod quantized;
+mod quick_scorer;
 mod regressor_model;
 mod splitter;
 mod trainer;
 
-pub use block_tree::BlockTree;
-pub use classifier_model::ClassifierModel;
-pub use compact_tree::CompactTree;
-pub use regressor_model::RegressorModel;
+pub use block_tree::{BlockTree, BlockTreeView};
+pub use classifier_model::{ClassifierModel, ClassifierView};
+pub use compact_tree::{CompactTree, CompactTreeView};
+pub use quantized::{Codebooks, Codes, Precision, QuantizedTree};
+pub use quick_scorer::QuickScorer;
+pub use regressor_model::{RegressorModel, RegressorView};
 pub use trainer::MaxFeaturesPolicy;
 pub use trainer::TrainConfig;
 
@@ -26,8 +32,241 @@ pub trait Trainable {
     fn set_leaf_value(&mut self, handle: &Self::Handle, value: u32);
 }
 
+// Number of samples traversed in lockstep by `Resolve::resolve_batch`.
+const BATCH_LANES: usize = 8;
+
 pub trait Resolve {
     fn resolve(&self, sample: &[f32]) -> u32;
+
+    /// Resolves each sample of `samples` with `num_features` features into `out` of length
+    /// number_of_samples.
+    fn resolve_batch(&self, samples: &[f32], num_features: usize, out: &mut [u32]) {
+        assert!(samples.len() == out.len() * num_features);
+        for (o, sample) in out.iter_mut().zip(samples.chunks_exact(num_features)) {
+            *o = self.resolve(sample);
+        }
+    }
+}
+
+// Calls `f(i, leaf)` for each sample `i` of dataset, where `leaf` is resolved by `predictor` in
+// batches of BATCH_LANES samples.
+#[inline(always)]
+fn resolve_each<P: Resolve>(
+    predictor: &P,
+    dataset: &[f32],
+    num_features: usize,
+    mut f: impl FnMut(usize, u32),
+) {
+    assert!(dataset.len().is_multiple_of(num_features));
+    let mut leaves = [0_u32; BATCH_LANES];
+    for (k, chunk) in dataset.chunks(BATCH_LANES * num_features).enumerate() {
+        let leaves = &mut leaves[..chunk.len() / num_features];
+        predictor.resolve_batch(chunk, num_features, leaves);
+        for (j, &leaf) in leaves.iter().enumerate() {
+            f(k * BATCH_LANES + j, leaf);
+        }
+    }
+}
+
+/// A node of a decision tree in a representation independent of the tree type. Leaf values have
+/// the same meaning as in [Trainable::set_leaf_value].
+///
+/// A tree of samples of `n` features may split features `n..2 * n`, which are the negated features
+/// `0..n`, e.g. feature `n` of a sample is `-sample[0]`. Comparisons with NaN are false, so missing
+/// values go to the right child of any split. A split of a negated feature with swapped children
+/// makes the same decisions for other values, while sending missing values to the other child.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TreeNode {
+    Leaf(u32),
+    /// Samples with `sample[feature] <= threshold` go to the left child.
+    Split {
+        feature: u16,
+        threshold: f32,
+        left: Box<TreeNode>,
+        right: Box<TreeNode>,
+    },
+}
+
+/// A tree which structure can be exported as [TreeNode]. Splits whose subtrees give the same leaf
+/// for any sample are exported as a single leaf.
+pub trait Export {
+    fn export(&self) -> TreeNode;
+}
+
+// Returns feature `feature` of `sample`, features past the end of the sample are negated features,
+// see [TreeNode].
+#[inline(always)]
+pub(crate) fn feature_value(sample: &[f32], feature: usize) -> f32 {
+    match sample.get(feature) {
+        Some(&x) => x,
+        None => -sample[feature - sample.len()],
+    }
+}
+
+// A split which sends samples with `sample[feature] <= threshold` to the left child, and missing
+// values to the left child if `missing_left`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Split {
+    pub feature: u16,
+    pub threshold: f32,
+    pub missing_left: bool,
+}
+
+// A node of a tree decoded from `TreeNode`.
+pub(crate) enum DecodedNode<'a> {
+    Leaf(u32),
+    Split(Split, &'a TreeNode, &'a TreeNode),
+}
+
+// Returns a `TreeNode` of a split in a tree of `num_features` features. A split with missing values
+// going left is a split of the negated feature `num_features + feature` with swapped children:
+// `-x <= -next_up(t)` holds iff `x > t`.
+pub(crate) fn split(
+    split: Split,
+    left: TreeNode,
+    right: TreeNode,
+    num_features: usize,
+) -> Result<TreeNode, String> {
+    let (feature, threshold, left, right) = if split.missing_left {
+        let feature = u16::try_from(num_features + split.feature as usize).map_err(|_| {
+            format!(
+                "missing values of feature {} are not supported",
+                split.feature
+            )
+        })?;
+        (feature, -split.threshold.next_up(), right, left)
+    } else {
+        (split.feature, split.threshold, left, right)
+    };
+    Ok(TreeNode::Split {
+        feature,
+        threshold,
+        left: Box::new(left),
+        right: Box::new(right),
+    })
+}
+
+// Decodes a node of a tree of `num_features` features, splits of negated features are decoded as
+// built by `split`.
+pub(crate) fn decode(node: &TreeNode, num_features: usize) -> DecodedNode<'_> {
+    match node {
+        TreeNode::Leaf(value) => DecodedNode::Leaf(*value),
+        TreeNode::Split {
+            feature,
+            threshold,
+            left,
+            right,
+        } if *feature as usize >= num_features => DecodedNode::Split(
+            Split {
+                feature: (*feature as usize - num_features) as u16,
+                threshold: (-threshold).next_down(),
+                missing_left: true,
+            },
+            right,
+            left,
+        ),
+        TreeNode::Split {
+            feature,
+            threshold,
+            left,
+            right,
+        } => DecodedNode::Split(
+            Split {
+                feature: *feature,
+                threshold: *threshold,
+                missing_left: false,
+            },
+            left,
+            right,
+        ),
+    }
+}
+
+// Returns the largest f32 not above an f64 `threshold`. A split of f32 samples by the result makes
+// the same decisions as a split by `threshold`.
+pub(crate) fn threshold_below(threshold: f64) -> f32 {
+    let rounded = threshold as f32;
+    if rounded as f64 > threshold {
+        rounded.next_down()
+    } else {
+        rounded
+    }
+}
+
+// The maximal depth of trees read from other formats. Trees are imported and built recursively, so
+// the depth is limited to keep the stack bounded.
+pub(crate) const MAX_IMPORT_DEPTH: usize = 1024;
+
+// Builds a tree of type `P` with the structure of `node`.
+pub(crate) fn build<P: Trainable>(node: &TreeNode) -> P {
+    let mut tree = P::new();
+    let root = tree.root();
+    build_node(&mut tree, &root, node);
+    tree
+}
+
+fn build_node<P: Trainable>(tree: &mut P, handle: &P::Handle, node: &TreeNode) {
+    match node {
+        TreeNode::Leaf(value) => tree.set_leaf_value(handle, *value),
+        TreeNode::Split {
+            feature,
+            threshold,
+            left,
+            right,
+        } => {
+            let (left_handle, right_handle) = tree.split(handle, *feature, *threshold);
+            build_node(tree, &left_handle, left);
+            build_node(tree, &right_handle, right);
+        }
+    }
+}
+
+/// An error describing why a model can not be used for inference, e.g. a corrupted deserialized
+/// model.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct InvalidModel(pub String);
+
+impl Display for InvalidModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid model: {}.", self.0)
+    }
+}
+
+impl std::error::Error for InvalidModel {}
+
+/// A tree which structure can be checked before inference.
+pub trait Validate {
+    /// Checks that resolving any sample of `num_features` features terminates without panics and
+    /// returns a leaf value accepted by `is_leaf`. Split features must be below
+    /// `2 * num_features`, see [TreeNode]. Child nodes must follow their parents in memory, so
+    /// trees are acyclic, and each node must have a single parent.
+    fn validate(
+        &self,
+        num_features: usize,
+        is_leaf: &dyn Fn(u32) -> bool,
+    ) -> Result<(), InvalidModel>;
+}
+
+impl<T: Resolve> Resolve for &T {
+    #[inline(always)]
+    fn resolve(&self, sample: &[f32]) -> u32 {
+        (**self).resolve(sample)
+    }
+
+    #[inline(always)]
+    fn resolve_batch(&self, samples: &[f32], num_features: usize, out: &mut [u32]) {
+        (**self).resolve_batch(samples, num_features, out)
+    }
+}
+
+impl<T: Validate> Validate for &T {
+    fn validate(
+        &self,
+        num_features: usize,
+        is_leaf: &dyn Fn(u32) -> bool,
+    ) -> Result<(), InvalidModel> {
+        (**self).validate(num_features, is_leaf)
+    }
 }
 
 pub trait Predictor: Resolve + Trainable {}
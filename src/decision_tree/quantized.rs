@@ -0,0 +1,351 @@
+use super::{feature_value, TreeNode};
+use bitvec::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Number of bits of quantized values.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Precision {
+    U8,
+    #[default]
+    U16,
+}
+
+impl Precision {
+    // Returns the maximal code.
+    pub fn max_code(&self) -> u32 {
+        match self {
+            Precision::U8 => u8::MAX as u32,
+            Precision::U16 => u16::MAX as u32,
+        }
+    }
+}
+
+// Quantized values stored with given precision.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Codes {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+}
+
+impl Codes {
+    pub fn new(precision: Precision, codes: impl Iterator<Item = u32>) -> Self {
+        match precision {
+            Precision::U8 => Codes::U8(codes.map(|c| c as u8).collect()),
+            Precision::U16 => Codes::U16(codes.map(|c| c as u16).collect()),
+        }
+    }
+
+    #[inline(always)]
+    pub fn get(&self, i: usize) -> u32 {
+        match self {
+            Codes::U8(codes) => codes[i] as u32,
+            Codes::U16(codes) => codes[i] as u32,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Codes::U8(codes) => codes.len(),
+            Codes::U16(codes) => codes.len(),
+        }
+    }
+}
+
+// Thresholds of each feature used by quantized trees. A split `x <= threshold` is stored as index
+// `k` of the threshold in sorted codebook of the feature. A sample is quantized once: `x` becomes
+// the number of codebook thresholds below `x`, which is not greater than `k` iff `x <= codebook[k]`.
+// Negated features `num_features..2 * num_features` (see `TreeNode`) have codebooks of their own.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Codebooks {
+    codebooks: Vec<Vec<f32>>,
+}
+
+impl Codebooks {
+    // Creates codebooks of at most 2^bits thresholds for each feature. If a feature has more
+    // distinct thresholds, the codebook holds evenly spaced quantiles of them.
+    pub fn new<'a>(
+        trees: impl Iterator<Item = &'a TreeNode>,
+        num_features: usize,
+        precision: Precision,
+    ) -> Self {
+        let mut codebooks: Vec<Vec<f32>> = vec![Vec::new(); 2 * num_features];
+        for tree in trees {
+            collect_thresholds(tree, &mut codebooks);
+        }
+
+        let max_size = precision.max_code() as usize + 1;
+        for codebook in codebooks.iter_mut() {
+            codebook.sort_by(f32::total_cmp);
+            codebook.dedup();
+            if codebook.len() > max_size {
+                let n = codebook.len() - 1;
+                *codebook = (0..max_size)
+                    .map(|i| codebook[i * n / (max_size - 1)])
+                    .collect();
+            }
+        }
+        Self { codebooks }
+    }
+
+    pub fn num_features(&self) -> usize {
+        self.codebooks.len() / 2
+    }
+
+    // Checks that there are codebooks of features and negated features, each codebook holds
+    // at most 2^16 thresholds.
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.codebooks.len().is_multiple_of(2) {
+            return Err(format!("{} codebooks", self.codebooks.len()));
+        }
+        let max_size = Precision::U16.max_code() as usize + 1;
+        if let Some(feature) = self.codebooks.iter().position(|c| c.len() > max_size) {
+            return Err(format!("codebook of feature {feature} is too large"));
+        }
+        Ok(())
+    }
+
+    // Returns index of the codebook threshold of `feature` nearest to `threshold`, or zero if the
+    // codebook is empty.
+    pub fn encode(&self, feature: usize, threshold: f32) -> u32 {
+        let codebook = &self.codebooks[feature];
+        let i = codebook.partition_point(|&t| t < threshold);
+        if codebook.is_empty() {
+            0
+        } else if i == codebook.len()
+            || (i > 0 && threshold - codebook[i - 1] < codebook[i] - threshold)
+        {
+            (i - 1) as u32
+        } else {
+            i as u32
+        }
+    }
+
+    // Writes quantized features and negated features of a sample to `out` of length
+    // 2 * num_features(). NaN goes right of all splits.
+    pub fn quantize(&self, sample: &[f32], out: &mut [u32]) {
+        assert!(2 * sample.len() == self.codebooks.len());
+        assert!(out.len() == self.codebooks.len());
+        for (feature, (o, codebook)) in out.iter_mut().zip(self.codebooks.iter()).enumerate() {
+            let x = feature_value(sample, feature);
+            *o = if x.is_nan() {
+                u32::MAX
+            } else {
+                codebook.partition_point(|&t| t < x) as u32
+            };
+        }
+    }
+}
+
+fn collect_thresholds(node: &TreeNode, codebooks: &mut [Vec<f32>]) {
+    if let TreeNode::Split {
+        feature,
+        threshold,
+        left,
+        right,
+    } = node
+    {
+        codebooks[*feature as usize].push(*threshold);
+        collect_thresholds(left, codebooks);
+        collect_thresholds(right, codebooks);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Node {
+    left: u32,
+    right: u32,
+    feature: u16,
+    threshold: u16,
+    left_is_leaf: bool,
+    right_is_leaf: bool,
+}
+
+// A tree with quantized thresholds. Nodes are stored in BFS order and leaves are numbered in the
+// order they are met in BFS, so serialized tree holds neither child indexes nor leaf values.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "PackedTree", into = "PackedTree")]
+pub struct QuantizedTree {
+    nodes: Vec<Node>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct PackedTree {
+    features: Vec<u16>,
+    thresholds: Codes,
+    left_leaves_mask: BitVec,
+    right_leaves_mask: BitVec,
+}
+
+impl QuantizedTree {
+    // Creates a tree and returns it with the leaf values of `tree` in the order of leaf numbers.
+    pub fn new(tree: &TreeNode, codebooks: &Codebooks) -> (Self, Vec<u32>) {
+        let root = match tree {
+            // Single leaf is stored as a split with identical leaves.
+            TreeNode::Leaf(_) => TreeNode::Split {
+                feature: 0,
+                threshold: 0.0,
+                left: Box::new(tree.clone()),
+                right: Box::new(tree.clone()),
+            },
+            _ => tree.clone(),
+        };
+
+        let mut nodes = Vec::new();
+        let mut leaves = Vec::new();
+        let mut queue = vec![&root];
+        let mut i = 0;
+        while i < queue.len() {
+            let TreeNode::Split {
+                feature,
+                threshold,
+                left,
+                right,
+            } = queue[i]
+            else {
+                unreachable!();
+            };
+            let mut child = |node: &'_ TreeNode| match node {
+                TreeNode::Leaf(value) => {
+                    leaves.push(*value);
+                    (leaves.len() as u32 - 1, true)
+                }
+                _ => (0, false),
+            };
+            let (left_leaf, left_is_leaf) = child(left);
+            let (right_leaf, right_is_leaf) = child(right);
+            let mut node = Node {
+                left: left_leaf,
+                right: right_leaf,
+                feature: *feature,
+                threshold: codebooks.encode(*feature as usize, *threshold) as u16,
+                left_is_leaf,
+                right_is_leaf,
+            };
+            if !left_is_leaf {
+                node.left = queue.len() as u32;
+                queue.push(left);
+            }
+            if !right_is_leaf {
+                node.right = queue.len() as u32;
+                queue.push(right);
+            }
+            nodes.push(node);
+            i += 1;
+        }
+
+        (Self { nodes }, leaves)
+    }
+
+    // Returns the number of leaves.
+    pub fn num_leaves(&self) -> usize {
+        let leaves = self
+            .nodes
+            .iter()
+            .map(|n| n.left_is_leaf as usize + n.right_is_leaf as usize);
+        leaves.sum()
+    }
+
+    // Checks that split features and thresholds are indexes of `codebooks`. An empty codebook is
+    // referred to by threshold zero.
+    pub fn validate(&self, codebooks: &Codebooks) -> Result<(), String> {
+        for (i, node) in self.nodes.iter().enumerate() {
+            let Some(codebook) = codebooks.codebooks.get(node.feature as usize) else {
+                return Err(format!(
+                    "node {i}: feature {} is out of range",
+                    node.feature
+                ));
+            };
+            if node.threshold as usize >= codebook.len().max(1) {
+                return Err(format!(
+                    "node {i}: threshold {} is out of range",
+                    node.threshold
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // Returns the number of a leaf of a sample quantized by `Codebooks::quantize`.
+    #[inline(always)]
+    pub fn resolve(&self, quantized: &[u32]) -> usize {
+        let mut id = 0;
+        loop {
+            let node = &self.nodes[id];
+            let (next, is_leaf) = if quantized[node.feature as usize] <= node.threshold as u32 {
+                (node.left, node.left_is_leaf)
+            } else {
+                (node.right, node.right_is_leaf)
+            };
+            if is_leaf {
+                return next as usize;
+            }
+            id = next as usize;
+        }
+    }
+}
+
+impl From<QuantizedTree> for PackedTree {
+    fn from(tree: QuantizedTree) -> Self {
+        let max_threshold = tree.nodes.iter().map(|n| n.threshold).max().unwrap_or(0);
+        let precision = if max_threshold <= u8::MAX as u16 {
+            Precision::U8
+        } else {
+            Precision::U16
+        };
+        PackedTree {
+            features: tree.nodes.iter().map(|n| n.feature).collect(),
+            thresholds: Codes::new(precision, tree.nodes.iter().map(|n| n.threshold as u32)),
+            left_leaves_mask: tree.nodes.iter().map(|n| n.left_is_leaf).collect(),
+            right_leaves_mask: tree.nodes.iter().map(|n| n.right_is_leaf).collect(),
+        }
+    }
+}
+
+// Malformed packed trees are rejected instead of panicking, since they come from untrusted data.
+impl TryFrom<PackedTree> for QuantizedTree {
+    type Error = String;
+
+    fn try_from(packed: PackedTree) -> Result<Self, Self::Error> {
+        let num_nodes = packed.features.len();
+        if num_nodes == 0
+            || packed.thresholds.len() != num_nodes
+            || packed.left_leaves_mask.len() != num_nodes
+            || packed.right_leaves_mask.len() != num_nodes
+        {
+            return Err("inconsistent packed tree sizes".to_string());
+        }
+        // Children are met in BFS order, so they get consecutive indexes. A node is met as a child
+        // before its own children, so children follow their parents and the tree is acyclic.
+        let mut next_node = 1;
+        let mut next_leaf = 0;
+        let mut nodes = Vec::with_capacity(num_nodes);
+        for i in 0..num_nodes {
+            if i >= next_node {
+                return Err(format!("node {i} has no parent"));
+            }
+            let mut child = |is_leaf: bool| -> Result<u32, String> {
+                let counter = if is_leaf {
+                    &mut next_leaf
+                } else {
+                    &mut next_node
+                };
+                *counter += 1;
+                if !is_leaf && *counter > num_nodes {
+                    return Err("packed tree has too few nodes".to_string());
+                }
+                Ok(*counter as u32 - 1)
+            };
+            let left_is_leaf = packed.left_leaves_mask[i];
+            let right_is_leaf = packed.right_leaves_mask[i];
+            nodes.push(Node {
+                left: child(left_is_leaf)?,
+                right: child(right_is_leaf)?,
+                feature: packed.features[i],
+                threshold: packed.thresholds.get(i) as u16,
+                left_is_leaf,
+                right_is_leaf,
+            });
+        }
+        Ok(Self { nodes })
+    }
+}
@@ -0,0 +1,135 @@
+use super::{feature_value, CompactTree};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+
+thread_local! {
+    // Bitvector buffer of `QuickScorer::with_bits`, reused by calls on the same thread.
+    static BITS: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+// QuickScorer representation of a forest. Leaves of each tree are numbered from left to right and
+// represented by a bitvector of one or more 64-bit words. Each split is turned into masks, that
+// clear the bits of leaves of its left subtree. The masks are grouped by feature and sorted by
+// threshold, so for a sample only the masks of splits that send the sample right are applied,
+// i.e. a prefix of each feature list. The exit leaf of a tree is the leftmost leaf whose bit
+// remains set. Negated features `num_features..2 * num_features` (see `TreeNode`) have lists of
+// their own.
+#[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QuickScorer {
+    // Masks of feature `f` are stored at `feature_offsets[f]..feature_offsets[f + 1]`.
+    feature_offsets: Vec<u32>,
+    thresholds: Vec<f32>,
+    // Index of a word in the bitvector of all trees the mask applies to.
+    words: Vec<u32>,
+    masks: Vec<u64>,
+    // Words of tree `t` are `tree_words[t]..tree_words[t + 1]`.
+    tree_words: Vec<u32>,
+    // Leaf values of tree `t` are `leaves[tree_leaves[t]..tree_leaves[t + 1]]`.
+    tree_leaves: Vec<u32>,
+    leaves: Vec<u32>,
+}
+
+impl QuickScorer {
+    // Creates a scorer for `trees`, leaf value `v` of tree `t` is stored as `leaf_value(t, v)`.
+    pub fn new<'a>(
+        trees: impl Iterator<Item = &'a CompactTree>,
+        num_features: usize,
+        mut leaf_value: impl FnMut(usize, u32) -> u32,
+    ) -> Self {
+        let mut features: Vec<Vec<(f32, u32, u64)>> = vec![Vec::new(); 2 * num_features];
+        let mut scorer = Self {
+            tree_words: vec![0],
+            tree_leaves: vec![0],
+            ..Default::default()
+        };
+
+        for (t, tree) in trees.enumerate() {
+            let (leaves, splits) = tree.leaves_and_splits();
+            let first_word = *scorer.tree_words.last().unwrap();
+            for (feature, threshold, range) in splits {
+                for w in range.start / 64..range.end.div_ceil(64) {
+                    let lo = range.start.max(w * 64) - w * 64;
+                    let hi = range.end.min(w * 64 + 64) - w * 64;
+                    let bits = (u64::MAX >> (64 - (hi - lo))) << lo;
+                    features[feature as usize].push((threshold, first_word + w as u32, !bits));
+                }
+            }
+            scorer
+                .leaves
+                .extend(leaves.iter().map(|&v| leaf_value(t, v)));
+            scorer.tree_leaves.push(scorer.leaves.len() as u32);
+            scorer
+                .tree_words
+                .push(first_word + leaves.len().div_ceil(64) as u32);
+        }
+
+        scorer.feature_offsets.push(0);
+        for mut masks in features {
+            masks.sort_by(|a, b| a.0.total_cmp(&b.0));
+            for (threshold, word, mask) in masks {
+                scorer.thresholds.push(threshold);
+                scorer.words.push(word);
+                scorer.masks.push(mask);
+            }
+            scorer.feature_offsets.push(scorer.thresholds.len() as u32);
+        }
+
+        scorer
+    }
+
+    #[inline(always)]
+    pub fn num_features(&self) -> usize {
+        self.feature_offsets.len() / 2
+    }
+
+    #[inline(always)]
+    pub fn num_trees(&self) -> usize {
+        self.tree_leaves.len() - 1
+    }
+
+    // Returns the length of a bitvector buffer used by `score`.
+    #[inline(always)]
+    pub fn num_words(&self) -> usize {
+        *self.tree_words.last().unwrap() as usize
+    }
+
+    // Calls `f` with a bitvector buffer of length num_words() for `score`. The buffer is kept by the
+    // thread, so it is allocated only when a scorer needs more words than earlier ones.
+    pub fn with_bits<R>(&self, f: impl FnOnce(&mut [u64]) -> R) -> R {
+        BITS.with_borrow_mut(|bits| {
+            if bits.len() < self.num_words() {
+                bits.resize(self.num_words(), 0);
+            }
+            f(&mut bits[..self.num_words()])
+        })
+    }
+
+    // Calls `f(t, leaf)` for each tree `t`, where `leaf` is the value of the exit leaf of the
+    // sample. `bits` is a buffer of length num_words().
+    pub fn score(&self, sample: &[f32], bits: &mut [u64], mut f: impl FnMut(usize, u32)) {
+        assert!(sample.len() == self.num_features());
+        assert!(bits.len() == self.num_words());
+        bits.fill(u64::MAX);
+        for (feature, range) in self.feature_offsets.windows(2).enumerate() {
+            let x = feature_value(sample, feature);
+            for k in range[0] as usize..range[1] as usize {
+                if x <= self.thresholds[k] {
+                    break;
+                }
+                bits[self.words[k] as usize] &= self.masks[k];
+            }
+        }
+
+        for t in 0..self.num_trees() {
+            let first_word = self.tree_words[t] as usize;
+            let words = &bits[first_word..self.tree_words[t + 1] as usize];
+            let (w, word) = words
+                .iter()
+                .enumerate()
+                .find(|(_, w)| **w != 0)
+                .expect("Exit leaf is always set.");
+            let leaf = w * 64 + word.trailing_zeros() as usize;
+            f(t, self.leaves[self.tree_leaves[t] as usize + leaf]);
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use super::{splitter::MseSplitter, trainer, Predictor, TrainConfig};
+use super::{
+    build, resolve_each, splitter::MseSplitter, trainer, Export, InvalidModel, Predictor, Resolve,
+    TrainConfig, Validate,
+};
 
 use crate::{FloatTarget, SampleWeight, Trainset};
 
@@ -10,16 +13,71 @@ pub struct RegressorModel<P: Predictor> {
     num_features: usize,
 }
 
+// Regressor model with borrowed tree, e.g. a tree stored in an archive.
+#[derive(Clone, Copy, Debug)]
+pub struct RegressorView<V> {
+    pub tree: V,
+    pub num_features: usize,
+}
+
+impl<V: Resolve> RegressorView<V> {
+    pub fn predict_into(&self, dataset: &[f32], out: &mut [f32]) {
+        assert!(out.len() == dataset.len() / self.num_features);
+        resolve_each(&self.tree, dataset, self.num_features, |i, leaf| {
+            out[i] = f32::from_bits(leaf);
+        });
+    }
+
+    // Adds predictions for each sample to `out`.
+    pub fn accumulate(&self, dataset: &[f32], out: &mut [f32]) {
+        assert!(out.len() == dataset.len() / self.num_features);
+        resolve_each(&self.tree, dataset, self.num_features, |i, leaf| {
+            out[i] += f32::from_bits(leaf);
+        });
+    }
+
+    #[inline(always)]
+    pub fn predict_one(&self, sample: &[f32]) -> f32 {
+        f32::from_bits(self.tree.resolve(sample))
+    }
+}
+
+impl<V: Validate> RegressorView<V> {
+    // Checks that the tree predicts finite values for samples of `num_features` features.
+    pub fn validate(&self) -> Result<(), InvalidModel> {
+        if self.num_features == 0 {
+            return Err(InvalidModel("model has no features".to_string()));
+        }
+        self.tree
+            .validate(self.num_features, &|v| f32::from_bits(v).is_finite())
+    }
+}
+
 #[derive(Default)]
 struct Aggregator {}
 
 impl<P: Predictor> RegressorModel<P> {
+    pub fn from_parts(predictor: P, num_features: usize) -> Self {
+        Self {
+            predictor,
+            num_features,
+        }
+    }
+
     pub fn predict(&self, dataset: &[f32]) -> Vec<f32> {
-        assert!(dataset.len() % self.num_features == 0);
-        dataset
-            .chunks_exact(self.num_features)
-            .map(|s| self.predict_one(s))
-            .collect()
+        assert!(dataset.len().is_multiple_of(self.num_features));
+        let mut result = vec![0.; dataset.len() / self.num_features];
+        self.predict_into(dataset, &mut result);
+        result
+    }
+
+    pub fn predict_into(&self, dataset: &[f32], out: &mut [f32]) {
+        self.view().predict_into(dataset, out);
+    }
+
+    // Adds predictions for each sample to `out`.
+    pub fn accumulate(&self, dataset: &[f32], out: &mut [f32]) {
+        self.view().accumulate(dataset, out);
     }
 
     #[inline(always)]
@@ -27,9 +85,39 @@ impl<P: Predictor> RegressorModel<P> {
         self.num_features
     }
 
+    // Rebuilds the tree without splits whose subtrees give the same leaf value.
+    pub fn simplify(&mut self)
+    where
+        P: Export,
+    {
+        self.predictor = build(&self.predictor.export());
+    }
+
+    // Checks that the tree predicts finite values for samples of `num_features` features.
+    pub fn validate(&self) -> Result<(), InvalidModel>
+    where
+        P: Validate,
+    {
+        self.view().validate()
+    }
+
+    // Returns the model with borrowed tree.
+    #[inline(always)]
+    pub fn view(&self) -> RegressorView<&P> {
+        RegressorView {
+            tree: &self.predictor,
+            num_features: self.num_features,
+        }
+    }
+
+    // Returns the tree, leaf values of the tree are bits of `f32` predictions.
+    pub fn predictor(&self) -> &P {
+        &self.predictor
+    }
+
     #[inline(always)]
     pub fn predict_one(&self, sample: &[f32]) -> f32 {
-        f32::from_bits(self.predictor.resolve(sample))
+        self.view().predict_one(sample)
     }
 
     pub fn train(ts: &Trainset<FloatTarget>, config: &TrainConfig) -> RegressorModel<P> {
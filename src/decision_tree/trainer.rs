@@ -1,6 +1,5 @@
 use super::{splitter::Splitter, Trainable};
 use crate::{IndexRange, SampleWeight, Trainset};
-use radsort;
 use rand::{rngs::SmallRng, seq::SliceRandom, SeedableRng};
 
 #[derive(Default)]
@@ -11,7 +10,7 @@ struct Split {
 }
 
 /// Defines the limiting strategy for a number of features that are selected at each split.
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, Debug)]
 pub enum MaxFeaturesPolicy {
     /// Takes `sqrt(total_features)`.
     SQRT,
@@ -19,6 +18,39 @@ pub enum MaxFeaturesPolicy {
     LOG,
     /// Sets the exact number.
     NUMBER(usize),
+    /// Takes a fraction of `total_features`, at least one feature.
+    FRACTION(f32),
+}
+
+// Fractions are compared by their bits, so the equality is reflexive even for NaN.
+impl PartialEq for MaxFeaturesPolicy {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (MaxFeaturesPolicy::SQRT, MaxFeaturesPolicy::SQRT) => true,
+            (MaxFeaturesPolicy::LOG, MaxFeaturesPolicy::LOG) => true,
+            (MaxFeaturesPolicy::NUMBER(a), MaxFeaturesPolicy::NUMBER(b)) => a == b,
+            (MaxFeaturesPolicy::FRACTION(a), MaxFeaturesPolicy::FRACTION(b)) => {
+                a.to_bits() == b.to_bits()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for MaxFeaturesPolicy {}
+
+impl MaxFeaturesPolicy {
+    // Returns the number of features to take out of `num_features`.
+    pub(crate) fn resolve(&self, num_features: usize) -> usize {
+        match self {
+            MaxFeaturesPolicy::SQRT => (num_features as f32).sqrt() as usize,
+            MaxFeaturesPolicy::LOG => (num_features as f32).log2() as usize,
+            MaxFeaturesPolicy::NUMBER(n) => (*n).min(num_features),
+            MaxFeaturesPolicy::FRACTION(f) => ((f * num_features as f32) as usize)
+                .max(1)
+                .min(num_features),
+        }
+    }
 }
 
 /// Configuration for training a decision tree.
@@ -37,9 +69,13 @@ pub struct TrainConfig {
     /// least `num_features`.
     ///
     /// **Note**. If trainer is unable find a splitting value in `num_features` features, it will
-    /// consider additional features.
+    /// consider additional features unless `strict_max_features` is set.
     pub max_features: MaxFeaturesPolicy,
 
+    /// If set, exactly `max_features` features are considered in each split, and the node becomes
+    /// a leaf if no split found among them.
+    pub strict_max_features: bool,
+
     /// Minimal number of samples in the node that can be splitted.
     pub min_samples_split: usize,
 
@@ -47,6 +83,9 @@ pub struct TrainConfig {
     pub min_samples_leaf: usize,
 
     pub weights: Vec<SampleWeight>,
+
+    // Columns of the dataset used for training, all columns if empty.
+    pub(crate) columns: Vec<usize>,
 }
 
 impl Default for TrainConfig {
@@ -54,10 +93,12 @@ impl Default for TrainConfig {
         Self {
             max_depth: usize::MAX,
             max_features: MaxFeaturesPolicy::NUMBER(usize::MAX),
+            strict_max_features: false,
             seed: 42,
             min_samples_leaf: 1,
             min_samples_split: 2,
             weights: Vec::new(),
+            columns: Vec::new(),
         }
     }
 }
@@ -82,9 +123,10 @@ struct FeaturePermutation {
 
 pub struct TrainSpace<'a, T> {
     data: &'a [f32],
+    // Feature indexes of the columns used for training.
+    columns: Vec<usize>,
     samples: Vec<u32>,
     targets: Vec<(T, SampleWeight)>,
-    num_features: usize,
     dataset_size: usize,
 }
 
@@ -126,14 +168,10 @@ pub fn train<Tgt: Copy, Tr: Trainable>(
     splitter: impl Splitter<Tgt>,
     aggregator: &mut impl Aggregator<Tgt>,
 ) -> Tr {
-    let space = TrainSpace::new(ts, &config.weights);
+    let space = TrainSpace::new(ts, &config.weights, &config.columns);
     let num_features = space.num_features();
 
-    let max_features = match config.max_features {
-        MaxFeaturesPolicy::SQRT => (num_features as f32).sqrt() as usize,
-        MaxFeaturesPolicy::LOG => (num_features as f32).log2() as usize,
-        MaxFeaturesPolicy::NUMBER(n) => n.min(num_features),
-    };
+    let max_features = config.max_features.resolve(num_features);
 
     let rng = (max_features < num_features).then_some(SmallRng::seed_from_u64(config.seed));
 
@@ -175,7 +213,8 @@ where
             if let Some(s) = split {
                 self.space.split(&range, s.feature, s.threshold);
                 let (left_range, right_range) = (range.start..s.pivot, s.pivot..range.end);
-                let (left_node, right_node) = trainee.split(&node, s.feature as u16, s.threshold);
+                let feature = self.space.feature_index(s.feature) as u16;
+                let (left_node, right_node) = trainee.split(&node, feature, s.threshold);
 
                 stack.push((left_node, left_range, depth + 1));
                 stack.push((right_node, right_range, depth + 1));
@@ -188,8 +227,8 @@ where
     }
 
     fn find_best_split(&mut self, range: &IndexRange) -> Option<Split> {
-        let targets = self.space.targets(&range);
-        let samples = self.space.samples(&range);
+        let targets = self.space.targets(range);
+        let samples = self.space.samples(range);
 
         // Splitter returns false if the range is pure.
         if !self.splitter.prepare(targets) {
@@ -205,8 +244,9 @@ where
         self.features_perm.shake();
         for (i, &feature) in self.features_perm.iter().enumerate() {
             let mut ordered_samples = proto.clone();
-            for ((x, _, _), y) in ordered_samples.iter_mut().zip(samples.iter()) {
-                *x = self.space.feature_val(*y, feature);
+            let values = self.space.column(feature);
+            for ((x, _, _), &y) in ordered_samples.iter_mut().zip(samples.iter()) {
+                *x = values[y as usize];
             }
 
             radsort::sort_by_key(&mut ordered_samples, |k| k.0);
@@ -219,7 +259,10 @@ where
                 best_impurity = p.impurity;
             }
 
-            if best_impurity == 0. || (i + 1 >= self.max_features && split.pivot > range.start) {
+            if best_impurity == 0.
+                || (i + 1 >= self.max_features
+                    && (self.config.strict_max_features || split.pivot > range.start))
+            {
                 break;
             }
         }
@@ -228,7 +271,11 @@ where
 }
 
 impl<'a, T: Copy> TrainSpace<'a, T> {
-    pub fn new(ts: &'a Trainset<'a, T>, weights: &[SampleWeight]) -> TrainSpace<'a, T> {
+    pub fn new(
+        ts: &'a Trainset<'a, T>,
+        weights: &[SampleWeight],
+        columns: &[usize],
+    ) -> TrainSpace<'a, T> {
         let mut samples: Vec<u32> = Vec::new();
         let mut weighted_targets: Vec<(T, SampleWeight)> = Vec::new();
 
@@ -244,23 +291,34 @@ impl<'a, T: Copy> TrainSpace<'a, T> {
             }
         }
 
+        let columns = if columns.is_empty() {
+            (0..ts.num_features).collect()
+        } else {
+            columns.to_vec()
+        };
         TrainSpace {
             data: &ts.data,
+            columns,
             samples,
             targets: weighted_targets,
-            num_features: ts.data.len() / ts.targets.len(),
             dataset_size: ts.targets.len(),
         }
     }
 
     #[inline(always)]
     pub fn num_features(&self) -> usize {
-        self.num_features
+        self.columns.len()
+    }
+
+    // Returns a feature index of a sample for given column of dataset.
+    #[inline(always)]
+    pub fn feature_index(&self, column: usize) -> usize {
+        self.columns[column]
     }
 
     #[inline(always)]
     pub fn targets(&self, range: &IndexRange) -> &[(T, SampleWeight)] {
-        &&self.targets[range.clone()]
+        &self.targets[range.clone()]
     }
 
     #[inline(always)]
@@ -269,10 +327,11 @@ impl<'a, T: Copy> TrainSpace<'a, T> {
     }
 
     fn split(&mut self, range: &IndexRange, feature: usize, threshold: f32) {
+        let values = self.column(feature);
         let mut i = range.start;
         let mut j = range.end;
         while i < j {
-            if self.feature_val(self.samples[i], feature) <= threshold {
+            if values[self.samples[i] as usize] <= threshold {
                 i += 1;
             } else {
                 j -= 1;
@@ -282,10 +341,11 @@ impl<'a, T: Copy> TrainSpace<'a, T> {
         }
     }
 
+    // Returns values of all samples for given column.
     #[inline(always)]
-    fn feature_val(&self, id: u32, feature: usize) -> f32 {
-        //self.dataview.feature_val(id as usize, feature)
-        self.data[self.dataset_size * feature + id as usize]
+    fn column(&self, column: usize) -> &'a [f32] {
+        let start = self.dataset_size * self.columns[column];
+        &self.data[start..start + self.dataset_size]
     }
 
     #[inline(always)]
@@ -1,12 +1,23 @@
 use crate::{
-    classify, decision_tree,
-    decision_tree::{BlockTree, ClassifierModel, Predictor},
-    ensemble_predictor,
-    ensemble_trainer::{self, EnsembleConfig},
+    classify,
+    codegen::{self, Language, SourceLiteral},
+    decision_tree,
+    decision_tree::{BlockTree, ClassifierModel, ClassifierView, CompactTree, Predictor, Validate},
+    ensemble_predictor::{self, Parallelism},
+    ensemble_trainer::{self, EnsembleConfig, WarmStartError},
+    executor::{Executor, ScopedThreads},
+    quantized::{Quantization, QuantizedClassifier},
+    quick_scorer::QuickClassifier,
     trainer_builders::*,
-    BatchPredictor, ClassDecode, ClassTarget, ClassesMapping, MaxFeaturesPolicy, Trainset,
+    tree_render, BatchPredictor, ClassDecode, ClassTarget, ClassesMapping, Export, InvalidModel,
+    Label, MaxFeaturesPolicy, Resolve, SamplingPolicy, Trainset, UnknownLabel,
 };
+use argminmax::ArgMinMax;
 use serde::{Deserialize, Serialize};
+
+// Number of classes whose probabilities are summed at once in single sample prediction.
+const CLASSES_BLOCK: usize = 32;
+
 /// A random forest classifier.
 /// # Training
 /// The [Trainer] implements [CommonTrainerBuilder] and [EnsembleTrainerBuilder]. Default training
@@ -14,13 +25,18 @@ use serde::{Deserialize, Serialize};
 /// ```text
 /// max_depth: usize::MAX,
 /// max_features: NumFeatures::SQRT,
+/// strict_max_features: false,
 /// seed: 42,
 /// min_samples_leaf: 1,
 /// min_samples_split: 2,
 /// sample_weights: empty (1.0 for each sample)
 /// num_trees: 100,
 /// num_threads: 1,
+/// max_features_per_tree: MaxFeaturesPolicy::NUMBER(usize::MAX),
+/// sampling: SamplingPolicy::BOOTSTRAP,
 ///```
+/// For imbalanced classes, [Trainer::with_sampling] allows stratified bootstrap or Balanced Random
+/// Forest sampling.
 /// # Example
 /// ```
 /// use rafor::rf;
@@ -32,25 +48,42 @@ use serde::{Deserialize, Serialize};
 /// ```
 ///
 #[derive(Default, Clone, PartialEq, Debug, Serialize, Deserialize)]
-pub struct Classifier<P: Predictor = BlockTree> {
+pub struct Classifier<P: Predictor = BlockTree, L = i64> {
     ensemble: Vec<ClassifierModel<P>>,
-    classes_map: ClassesMapping,
+    classes_map: ClassesMapping<L>,
+    #[serde(skip)]
+    parallelism: Parallelism,
+}
+
+/// Defines how the probability distributions of trees are combined in prediction.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Voting {
+    /// Averages probability distributions of trees.
+    SOFT,
+    /// Each tree votes for its most probable class. The probability of a class is the fraction of
+    /// votes for it.
+    HARD,
+    /// Weighted average of probability distributions of trees, the vector holds a weight for each
+    /// tree.
+    WEIGHTED(Vec<f32>),
 }
 
 /// Trainer for ensemble classifier.
 #[derive(Clone, PartialEq, Debug)]
-pub struct Trainer<P: Predictor> {
+pub struct Trainer<P: Predictor, L = i64> {
     pub config: EnsembleConfig,
+    classes: Vec<L>,
     _marker: std::marker::PhantomData<P>,
 }
 
-impl<P: Predictor> Default for Trainer<P> {
+impl<P: Predictor, L> Default for Trainer<P, L> {
     fn default() -> Self {
         let mut config = EnsembleConfig::default();
         config.tree_config_proto.max_features = MaxFeaturesPolicy::SQRT;
         Self {
             config,
-            _marker: std::marker::PhantomData::default(),
+            classes: Vec::new(),
+            _marker: std::marker::PhantomData,
         }
     }
 }
@@ -71,13 +104,117 @@ impl<P: Predictor> BatchPredictor for ClassifierModel<P> {
     fn predict(&self, dataset: &[f32]) -> Vec<f32> {
         Self::predict(self, dataset)
     }
+
+    fn accumulate(&self, dataset: &[f32], out: &mut [f32]) {
+        Self::accumulate(self, dataset, out);
+    }
+
+    fn num_features(&self) -> usize {
+        Self::num_features(self)
+    }
+
+    fn num_outputs(&self) -> usize {
+        self.num_classes()
+    }
+}
+
+impl<V: Resolve> BatchPredictor for ClassifierView<'_, V> {
+    fn predict(&self, dataset: &[f32]) -> Vec<f32> {
+        let mut result = vec![0.; dataset.len() / self.num_features * self.num_classes];
+        self.predict_into(dataset, &mut result);
+        result
+    }
+
+    fn accumulate(&self, dataset: &[f32], out: &mut [f32]) {
+        Self::accumulate(self, dataset, out);
+    }
+
+    fn num_features(&self) -> usize {
+        self.num_features
+    }
+
+    fn num_outputs(&self) -> usize {
+        self.num_classes
+    }
+}
+
+impl<P: Predictor, L> Trainer<P, L> {
+    /// Sets the strategy of drawing samples for each tree (`sampling`).
+    pub fn with_sampling(&mut self, policy: SamplingPolicy) -> &mut Self {
+        self.config.sampling = policy;
+        self
+    }
 }
 
-impl<P: Predictor + Default + Clone + Sync + Send> Trainer<P> {
+impl<P: Predictor + Default + Clone + Sync + Send, L: Label> Trainer<P, L> {
+    /// Declares all classes of a classifier. The classes which are absent in training labels get
+    /// zero probabilities, so `proba` has the same columns for all trained models. By default
+    /// the classes are taken from training labels.
+    pub fn with_classes(&mut self, classes: &[L]) -> &mut Self {
+        self.classes = classes.to_vec();
+        self
+    }
+
     /// Trains a classifier random forest with dataset given by a slice of length divisible by
-    /// targets.len().
-    pub fn train(&self, data: &[f32], labels: &[i64]) -> Classifier<P> {
-        let (classes_map, labels_enc) = ClassesMapping::with_encode(labels);
+    /// targets.len(). Panics if classes are declared and a label is not one of them, see
+    /// `try_train`.
+    pub fn train(&self, data: &[f32], labels: &[L]) -> Classifier<P, L> {
+        self.train_in(data, labels, &ScopedThreads(self.config.num_threads))
+    }
+
+    /// Same as `train`, but returns an error if classes are declared and a label is not one of
+    /// them.
+    pub fn try_train(
+        &self,
+        data: &[f32],
+        labels: &[L],
+    ) -> Result<Classifier<P, L>, UnknownLabel<L>> {
+        self.fit(None, data, labels, &ScopedThreads(self.config.num_threads))
+    }
+
+    /// Same as `train`, but the trees are trained by given `executor` instead of `num_threads`
+    /// new threads.
+    pub fn train_in(
+        &self,
+        data: &[f32],
+        labels: &[L],
+        executor: &dyn Executor,
+    ) -> Classifier<P, L> {
+        self.fit(None, data, labels, executor)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Continues training of a `model` with the parameters of the trainer, see [WarmStart].
+    pub fn warm_start<'a>(&self, model: &'a Classifier<P, L>) -> WarmStart<'a, P, L> {
+        WarmStart {
+            trainer: self.clone(),
+            model,
+        }
+    }
+
+    // Trains new trees, which are appended to the trees of `model` if it is given. The classes of
+    // `model` are kept.
+    fn fit(
+        &self,
+        model: Option<&Classifier<P, L>>,
+        data: &[f32],
+        labels: &[L],
+        executor: &dyn Executor,
+    ) -> Result<Classifier<P, L>, UnknownLabel<L>> {
+        let classes = if self.classes.is_empty() {
+            labels
+        } else {
+            &self.classes
+        };
+        let (classes_map, mut ensemble) = match model {
+            Some(model) => (model.classes_map.clone(), model.ensemble.clone()),
+            None => (ClassesMapping::with_classes(classes), Vec::new()),
+        };
+        let labels_enc: Vec<ClassTarget> = classes_map
+            .encode(labels)?
+            .into_iter()
+            .map(|c| c as ClassTarget)
+            .collect();
 
         let proto = Trainee {
             tree: ClassifierModel::default(),
@@ -85,31 +222,217 @@ impl<P: Predictor + Default + Clone + Sync + Send> Trainer<P> {
         };
         let trainset = Trainset::with_transposed(data, &labels_enc);
 
-        let ens = ensemble_trainer::fit(proto, &trainset, &self.config);
+        let ens = ensemble_trainer::fit(proto, &trainset, &self.config, ensemble.len(), executor);
+        ensemble.extend(ens.into_iter().map(|t| t.tree));
 
-        Classifier {
-            ensemble: ens.into_iter().map(|t| t.tree).collect(),
+        Ok(Classifier {
+            ensemble,
             classes_map,
+            parallelism: Parallelism::default(),
+        })
+    }
+}
+
+/// Continuation of training of a forest, created by [Trainer::warm_start]. Appends `num_trees` new
+/// trees to the trees of the model. The seeds of new trees continue the sequence of seeds of the
+/// model trees, so adding trees to a forest gives the same forest as training all trees at once
+/// with the same parameters.
+///
+/// Implements [CommonTrainerBuilder] and [EnsembleTrainerBuilder], the parameters are initially
+/// those of the trainer.
+/// ```
+/// use rafor::{prelude::*, rf};
+/// let dataset = [0.7, 0.0, 0.8, 1.0, 0.7, 0.0];
+/// let targets = [1, 5, 1];
+/// let trainer = rf::Classifier::trainer().with_trees(10).clone();
+/// let model: rf::Classifier = trainer.train(&dataset, &targets);
+/// let grown = trainer.warm_start(&model).with_trees(5).train(&dataset, &targets).unwrap();
+/// assert_eq!(grown.num_trees(), 15);
+/// ```
+#[derive(Clone, Debug)]
+pub struct WarmStart<'a, P: Predictor, L = i64> {
+    trainer: Trainer<P, L>,
+    model: &'a Classifier<P, L>,
+}
+
+impl<P: Predictor + Default + Clone + Sync + Send, L: Label> WarmStart<'_, P, L> {
+    /// Trains new trees on a dataset given by a slice of length divisible by labels.len(). Returns
+    /// an error if samples have a different number of features than the model, if classes are
+    /// declared and differ from the classes of the model, or if a label is not a class of the
+    /// model.
+    pub fn train(&self, data: &[f32], labels: &[L]) -> Result<Classifier<P, L>, WarmStartError<L>> {
+        let executor = ScopedThreads(self.trainer.config.num_threads);
+        self.train_in(data, labels, &executor)
+    }
+
+    /// Same as `train`, but the trees are trained by given `executor` instead of `num_threads`
+    /// new threads.
+    pub fn train_in(
+        &self,
+        data: &[f32],
+        labels: &[L],
+        executor: &dyn Executor,
+    ) -> Result<Classifier<P, L>, WarmStartError<L>> {
+        ensemble_trainer::check_warm_start(self.model.num_features(), data, labels.len())?;
+        let classes = self.model.get_decode_table();
+        if !self.trainer.classes.is_empty()
+            && ClassesMapping::with_classes(&self.trainer.classes).get_decode_table() != classes
+        {
+            return Err(WarmStartError::CLASSES {
+                expected: classes.to_vec(),
+                found: self.trainer.classes.clone(),
+            });
         }
+        self.trainer
+            .fit(Some(self.model), data, labels, executor)
+            .map_err(|e| WarmStartError::LABEL(e.0))
+    }
+}
+
+impl<P: Predictor, L> TrainConfigProvider for WarmStart<'_, P, L> {
+    fn train_config(&mut self) -> &mut decision_tree::TrainConfig {
+        self.trainer.train_config()
     }
 }
 
-impl<P: Predictor + Send + Sync> Classifier<P> {
+impl<P: Predictor, L> EnsembleConfigProvider for WarmStart<'_, P, L> {
+    fn ensemble_config(&mut self) -> &mut EnsembleConfig {
+        self.trainer.ensemble_config()
+    }
+}
+
+impl<P: Predictor, L> CommonTrainerBuilder for WarmStart<'_, P, L> {}
+impl<P: Predictor, L> EnsembleTrainerBuilder for WarmStart<'_, P, L> {}
+
+impl<P: Predictor + Send + Sync, L: Label> Classifier<P, L> {
+    pub(crate) fn from_parts(
+        ensemble: Vec<ClassifierModel<P>>,
+        classes_map: ClassesMapping<L>,
+    ) -> Self {
+        Self {
+            ensemble,
+            classes_map,
+            parallelism: Parallelism::default(),
+        }
+    }
+
     /// Predicts classes for a set of samples using `num_threads` threads.
     /// Dataset is a vector of floats with length multiple of num_features().
-    pub fn predict_batch(&self, dataset: &[f32], num_threads: usize) -> Vec<i64> {
+    pub fn predict_batch(&self, dataset: &[f32], num_threads: usize) -> Vec<L> {
         classify(&self.proba(dataset, num_threads), &self.classes_map)
     }
 
     /// Predicts class for a single sample given by a slice of length num_features().
-    pub fn predict_one(&self, sample: &[f32]) -> i64 {
-        classify(&self.proba(sample, 1), &self.classes_map)[0]
+    /// Allocates memory only to clone a label which owns it, e.g. `String`.
+    pub fn predict_one(&self, sample: &[f32]) -> L {
+        assert!(sample.len() == self.num_features());
+        self.classes_map.decode(self.predict_class(sample))
+    }
+
+    /// Writes predicted classes for a set of samples to `out` of length number_of_samples. The
+    /// prediction runs on the calling thread and allocates memory only to clone labels which own
+    /// it, e.g. `String`.
+    pub fn predict_into(&self, dataset: &[f32], out: &mut [L]) {
+        let num_features = self.num_features();
+        assert!(dataset.len() == out.len() * num_features);
+        for (r, sample) in out.iter_mut().zip(dataset.chunks_exact(num_features)) {
+            *r = self.classes_map.decode(self.predict_class(sample));
+        }
+    }
+
+    /// Writes classes probabilities for each sample to `out` of length
+    /// number_of_samples * num_classes(). The prediction runs on the calling thread and does not
+    /// allocate memory.
+    pub fn proba_into(&self, dataset: &[f32], out: &mut [f32]) {
+        ensemble_predictor::predict_into(&self.ensemble, dataset, out, &ScopedThreads(1));
+    }
+
+    // Returns index of the class with the highest mean probability. Probabilities are summed
+    // in blocks of classes on stack, so a sample is resolved by each tree once per block.
+    fn predict_class(&self, sample: &[f32]) -> usize {
+        let num_classes = self.num_classes();
+        let mut best = (f32::MIN, 0);
+        for first in (0..num_classes).step_by(CLASSES_BLOCK) {
+            let n = CLASSES_BLOCK.min(num_classes - first);
+            let mut sums = [0_f32; CLASSES_BLOCK];
+            for tree in self.ensemble.iter() {
+                let proba = &tree.predict_one(sample)[first..first + n];
+                for (s, p) in sums.iter_mut().zip(proba.iter()) {
+                    *s += p;
+                }
+            }
+            for (i, &s) in sums[..n].iter().enumerate() {
+                if s > best.0 {
+                    best = (s, first + i);
+                }
+            }
+        }
+        best.1
     }
 
     /// Predicts classes probabilities for each sample using `num_threads` threads. The length of
     /// result vector is number_of_samples * num_classes().
     pub fn proba(&self, dataset: &[f32], num_threads: usize) -> Vec<f32> {
-        ensemble_predictor::predict(&self.ensemble, dataset, num_threads)
+        self.proba_in(dataset, &ScopedThreads(num_threads))
+    }
+
+    /// Predicts classes for a set of samples using given `executor`.
+    pub fn predict_batch_in(&self, dataset: &[f32], executor: &dyn Executor) -> Vec<L> {
+        classify(&self.proba_in(dataset, executor), &self.classes_map)
+    }
+
+    /// Predicts classes probabilities for each sample using given `executor`.
+    pub fn proba_in(&self, dataset: &[f32], executor: &dyn Executor) -> Vec<f32> {
+        ensemble_predictor::predict(&self.ensemble, dataset, executor, self.parallelism)
+    }
+
+    /// Predicts classes for a set of samples using `num_threads` threads, tree predictions are
+    /// combined with given `voting`.
+    pub fn predict_batch_with(
+        &self,
+        dataset: &[f32],
+        num_threads: usize,
+        voting: &Voting,
+    ) -> Vec<L> {
+        classify(
+            &self.proba_with(dataset, num_threads, voting),
+            &self.classes_map,
+        )
+    }
+
+    /// Predicts classes probabilities for each sample using `num_threads` threads, tree
+    /// predictions are combined with given `voting`.
+    pub fn proba_with(&self, dataset: &[f32], num_threads: usize, voting: &Voting) -> Vec<f32> {
+        let executor = &ScopedThreads(num_threads);
+        match voting {
+            Voting::SOFT => self.proba_in(dataset, executor),
+            Voting::WEIGHTED(weights) => {
+                ensemble_predictor::predict_weighted(&self.ensemble, weights, dataset, executor)
+            }
+            Voting::HARD => {
+                let num_classes = self.num_classes();
+                let ensemble = &self.ensemble;
+                let mut votes = ensemble_predictor::sum(ensemble.len(), executor, |i| {
+                    let mut proba = ensemble[i].predict(dataset);
+                    for p in proba.chunks_exact_mut(num_classes) {
+                        let c = (&*p).argmax();
+                        p.fill(0.);
+                        p[c] = 1.;
+                    }
+                    proba
+                });
+                for x in votes.iter_mut() {
+                    *x /= self.ensemble.len() as f32;
+                }
+                votes
+            }
+        }
+    }
+
+    /// Sets how prediction is split between threads, `Parallelism::AUTO` by default. The option is
+    /// not serialized.
+    pub fn set_parallelism(&mut self, parallelism: Parallelism) {
+        self.parallelism = parallelism;
     }
 
     /// Returns a number of features for a trained tree.
@@ -118,28 +441,177 @@ impl<P: Predictor + Send + Sync> Classifier<P> {
     }
 
     /// Provides trainer for training a random forest classifier.
-    pub fn trainer() -> Trainer<P> {
+    pub fn trainer() -> Trainer<P, L> {
         Trainer::default()
     }
+
+    /// Returns a number of trees in forest.
+    pub fn num_trees(&self) -> usize {
+        self.ensemble.len()
+    }
+
+    pub(crate) fn trees(&self) -> &[ClassifierModel<P>] {
+        &self.ensemble
+    }
+
+    pub(crate) fn classes_map(&self) -> &ClassesMapping<L> {
+        &self.classes_map
+    }
+
+    /// Appends trees of `other` forest trained on the same feature space. The classes of both
+    /// forests are united, leaves of trees get zero probabilities for classes they were not
+    /// trained on.
+    pub fn merge(&mut self, other: Classifier<P, L>) {
+        assert!(self.num_features() == other.num_features());
+        let classes_map = self.classes_map.union(&other.classes_map);
+        if classes_map != self.classes_map {
+            let classes = classes_map.encode_targets(self.classes_map.get_decode_table());
+            for tree in self.ensemble.iter_mut() {
+                tree.remap_classes(&classes, classes_map.num_classes());
+            }
+        }
+
+        let classes = classes_map.encode_targets(other.classes_map.get_decode_table());
+        for mut tree in other.ensemble.into_iter() {
+            if classes_map != other.classes_map {
+                tree.remap_classes(&classes, classes_map.num_classes());
+            }
+            self.ensemble.push(tree);
+        }
+        self.classes_map = classes_map;
+    }
+
+    /// Keeps only the first `num_trees` trees. A forest keeps at least one tree, so `num_trees`
+    /// must be positive.
+    pub fn truncate(&mut self, num_trees: usize) {
+        assert!(num_trees > 0, "forest must keep at least one tree");
+        self.ensemble.truncate(num_trees);
+    }
+}
+
+impl<P: Predictor + Validate, L: Label> Classifier<P, L> {
+    /// Checks structural invariants of the model, e.g. after deserialization from untrusted
+    /// source: bounds and acyclicity of trees, leaf values and probability vectors. A valid model
+    /// predicts samples of num_features() features without panics.
+    pub fn validate(&self) -> Result<(), InvalidModel> {
+        let Some(first) = self.ensemble.first() else {
+            return Err(InvalidModel("forest has no trees".to_string()));
+        };
+        for (i, tree) in self.ensemble.iter().enumerate() {
+            let error = |e: String| InvalidModel(format!("tree {i}: {e}"));
+            tree.validate().map_err(|e| error(e.0))?;
+            if tree.num_features() != first.num_features()
+                || tree.num_classes() != first.num_classes()
+            {
+                return Err(error("trees have different shapes".to_string()));
+            }
+        }
+        self.classes_map.validate(first.num_classes())
+    }
+}
+
+impl<P: Predictor + Export + Send + Sync, L: Label> Classifier<P, L> {
+    /// Returns tree `index` of the forest as a Graphviz DOT graph, see
+    /// [dt::Classifier::to_dot](crate::dt::Classifier::to_dot).
+    pub fn tree_to_dot(&self, index: usize, feature_names: Option<&[&str]>) -> String {
+        tree_render::check_names(feature_names, self.num_features());
+        let tree = &self.ensemble[index];
+        let leaf = tree_render::class_leaf(tree, &self.classes_map);
+        tree_render::dot(
+            &tree.predictor().export(),
+            self.num_features(),
+            feature_names,
+            &leaf,
+        )
+    }
+
+    /// Returns tree `index` of the forest as indented text rules, see
+    /// [dt::Classifier::to_text_rules](crate::dt::Classifier::to_text_rules).
+    pub fn tree_to_text_rules(&self, index: usize, feature_names: Option<&[&str]>) -> String {
+        tree_render::check_names(feature_names, self.num_features());
+        let tree = &self.ensemble[index];
+        let leaf = tree_render::class_leaf(tree, &self.classes_map);
+        tree_render::text_rules(
+            &tree.predictor().export(),
+            self.num_features(),
+            feature_names,
+            &leaf,
+        )
+    }
 }
 
-impl<P: Predictor> ClassDecode for Classifier<P> {
-    fn get_decode_table(&self) -> &[i64] {
+impl<P: Predictor + Export, L: Label> Classifier<P, L> {
+    /// Removes splits whose subtrees predict the same value for any sample. Predictions do not
+    /// change.
+    pub fn simplify(&mut self) {
+        for tree in self.ensemble.iter_mut() {
+            tree.simplify();
+        }
+    }
+
+    /// Quantizes the forest for compact storage, see [quantized](crate::quantized).
+    pub fn quantize(&self, quantization: &Quantization) -> QuantizedClassifier<L> {
+        QuantizedClassifier::new(&self.ensemble, self.classes_map.clone(), quantization)
+    }
+}
+
+impl<P: Predictor + Export, L: Label + SourceLiteral> Classifier<P, L> {
+    /// Generates standalone Rust source of the model with names starting with `prefix`, see
+    /// [codegen].
+    pub fn to_rust_source(&self, prefix: &str) -> String {
+        codegen::classifier(
+            &self.ensemble,
+            self.get_decode_table(),
+            Language::Rust,
+            prefix,
+        )
+    }
+
+    /// Generates standalone C source of the model with names starting with `prefix`, see
+    /// [codegen].
+    pub fn to_c_source(&self, prefix: &str) -> String {
+        codegen::classifier(&self.ensemble, self.get_decode_table(), Language::C, prefix)
+    }
+}
+
+impl<L: Label> Classifier<CompactTree, L> {
+    /// Compiles the forest into [QuickClassifier], an alternative inference backend.
+    pub fn quick_scorer(&self) -> QuickClassifier<L> {
+        QuickClassifier::new(&self.ensemble, self.classes_map.clone())
+    }
+}
+
+impl<P: Predictor + Clone, L: Clone> Classifier<P, L> {
+    /// Returns a forest consisting of trees with given indexes. `indexes` must not be empty.
+    pub fn select_trees(&self, indexes: &[usize]) -> Self {
+        assert!(!indexes.is_empty(), "forest must keep at least one tree");
+        Self {
+            ensemble: indexes.iter().map(|&i| self.ensemble[i].clone()).collect(),
+            classes_map: self.classes_map.clone(),
+            parallelism: self.parallelism,
+        }
+    }
+}
+
+impl<P: Predictor, L: Label> ClassDecode for Classifier<P, L> {
+    type Label = L;
+
+    fn get_decode_table(&self) -> &[L] {
         self.classes_map.get_decode_table()
     }
 }
 
-impl<P: Predictor> TrainConfigProvider for Trainer<P> {
+impl<P: Predictor, L> TrainConfigProvider for Trainer<P, L> {
     fn train_config(&mut self) -> &mut decision_tree::TrainConfig {
         &mut self.config.tree_config_proto
     }
 }
 
-impl<P: Predictor> EnsembleConfigProvider for Trainer<P> {
+impl<P: Predictor, L> EnsembleConfigProvider for Trainer<P, L> {
     fn ensemble_config(&mut self) -> &mut EnsembleConfig {
         &mut self.config
     }
 }
 
-impl<P: Predictor> CommonTrainerBuilder for Trainer<P> {}
-impl<P: Predictor> EnsembleTrainerBuilder for Trainer<P> {}
+impl<P: Predictor, L> CommonTrainerBuilder for Trainer<P, L> {}
+impl<P: Predictor, L> EnsembleTrainerBuilder for Trainer<P, L> {}
@@ -1,46 +1,53 @@
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
-    Arc,
+    Mutex,
 };
-use std::thread;
 
 use super::BatchPredictor;
+use crate::executor::Executor;
+
+// Number of samples in a block processed by all trees in sample-parallel prediction.
+const SAMPLES_BLOCK: usize = 64;
+
+/// Defines how prediction of random forest is split between threads.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Parallelism {
+    /// Selects `SAMPLES` if there are enough samples for each thread, otherwise `TREES`.
+    #[default]
+    AUTO,
+    /// Each thread predicts a whole dataset with a subset of trees. Each thread holds its own
+    /// prediction for the whole dataset.
+    TREES,
+    /// Each thread predicts blocks of samples with all trees. Predictions are written directly to
+    /// the result.
+    SAMPLES,
+}
 
 pub fn predict<P: BatchPredictor + Sync + Send>(
-    predictors: &Vec<P>,
+    predictors: &[P],
     dataset: &[f32],
-    num_threads: usize,
+    executor: &dyn Executor,
+    parallelism: Parallelism,
 ) -> Vec<f32> {
-    let mut result: Vec<f32> = Vec::new();
-    if num_threads == 1 {
-        for p in predictors.iter() {
-            result.aggregate(&p.predict(&dataset));
+    let by_samples = match parallelism {
+        Parallelism::AUTO => {
+            let num_samples = dataset.len() / predictors[0].num_features();
+            num_samples >= SAMPLES_BLOCK * executor.num_threads()
         }
+        Parallelism::TREES => false,
+        Parallelism::SAMPLES => true,
+    };
+
+    let mut result = if by_samples {
+        let num_samples = dataset.len() / predictors[0].num_features();
+        let mut result = vec![0.; num_samples * predictors[0].num_outputs()];
+        accumulate_by_samples(predictors, dataset, &mut result, executor);
+        result
     } else {
-        let task_id = Arc::new(AtomicUsize::new(0));
-        thread::scope(|s| {
-            let mut handles = Vec::new();
-            for _ in 0..num_threads {
-                let handle = s.spawn(|| {
-                    let mut thread_result: Vec<f32> = Vec::new();
-                    loop {
-                        let id = task_id.fetch_add(1, Ordering::Relaxed);
-                        if id < predictors.len() {
-                            thread_result.aggregate(&predictors[id].predict(&dataset));
-                        } else {
-                            break;
-                        }
-                    }
-                    thread_result
-                });
-
-                handles.push(handle);
-            }
-            for handle in handles {
-                result.aggregate(&handle.join().unwrap());
-            }
-        });
-    }
+        sum(predictors.len(), executor, |i| {
+            predictors[i].predict(dataset)
+        })
+    };
 
     for x in result.iter_mut() {
         *x /= predictors.len() as f32;
@@ -49,14 +56,123 @@ pub fn predict<P: BatchPredictor + Sync + Send>(
     result
 }
 
+// Writes mean of predictors outputs to `out` without allocating memory.
+pub fn predict_into<P: BatchPredictor + Sync + Send>(
+    predictors: &[P],
+    dataset: &[f32],
+    out: &mut [f32],
+    executor: &dyn Executor,
+) {
+    out.fill(0.);
+    accumulate_by_samples(predictors, dataset, out, executor);
+    for x in out.iter_mut() {
+        *x /= predictors.len() as f32;
+    }
+}
+
+// Adds predictions of all predictors to `out`. Samples are split into blocks, each block is
+// processed by all predictors before moving to next block.
+fn accumulate_by_samples<P: BatchPredictor + Sync + Send>(
+    predictors: &[P],
+    dataset: &[f32],
+    out: &mut [f32],
+    executor: &dyn Executor,
+) {
+    let num_features = predictors[0].num_features();
+    let num_outputs = predictors[0].num_outputs();
+    assert!(dataset.len().is_multiple_of(num_features));
+    assert!(out.len() == dataset.len() / num_features * num_outputs);
+
+    let blocks = Mutex::new(
+        out.chunks_mut(SAMPLES_BLOCK * num_outputs)
+            .zip(dataset.chunks(SAMPLES_BLOCK * num_features)),
+    );
+    executor.execute(&|| loop {
+        let Some((out, samples)) = blocks.lock().unwrap().next() else {
+            break;
+        };
+        for p in predictors.iter() {
+            p.accumulate(samples, out);
+        }
+    });
+}
+
+// Computes weighted average of predictors outputs.
+pub fn predict_weighted<P: BatchPredictor + Sync + Send>(
+    predictors: &[P],
+    weights: &[f32],
+    dataset: &[f32],
+    executor: &dyn Executor,
+) -> Vec<f32> {
+    assert!(weights.len() == predictors.len());
+    let mut result = sum(predictors.len(), executor, |i| {
+        let mut p = predictors[i].predict(dataset);
+        for x in p.iter_mut() {
+            *x *= weights[i];
+        }
+        p
+    });
+
+    let total_weight: f32 = weights.iter().sum();
+    for x in result.iter_mut() {
+        *x /= total_weight;
+    }
+
+    result
+}
+
+// Returns outputs of each predictor.
+pub fn predict_all<P: BatchPredictor + Sync + Send>(
+    predictors: &[P],
+    dataset: &[f32],
+    executor: &dyn Executor,
+) -> Vec<Vec<f32>> {
+    let result: Mutex<Vec<Vec<f32>>> = Mutex::new(vec![Vec::new(); predictors.len()]);
+    let task_id = AtomicUsize::new(0);
+    executor.execute(&|| loop {
+        let id = task_id.fetch_add(1, Ordering::Relaxed);
+        if id < predictors.len() {
+            let r = predictors[id].predict(dataset);
+            result.lock().unwrap()[id] = r;
+        } else {
+            break;
+        }
+    });
+
+    result.into_inner().unwrap()
+}
+
+// Sums outputs `tree_predict(i)` for `i` in `0..num_trees` using given executor.
+pub fn sum<F>(num_trees: usize, executor: &dyn Executor, tree_predict: F) -> Vec<f32>
+where
+    F: Fn(usize) -> Vec<f32> + Sync,
+{
+    let result: Mutex<Vec<f32>> = Mutex::new(Vec::new());
+    let task_id = AtomicUsize::new(0);
+    executor.execute(&|| {
+        let mut thread_result: Vec<f32> = Vec::new();
+        loop {
+            let id = task_id.fetch_add(1, Ordering::Relaxed);
+            if id < num_trees {
+                thread_result.aggregate(&tree_predict(id));
+            } else {
+                break;
+            }
+        }
+        result.lock().unwrap().aggregate(&thread_result);
+    });
+
+    result.into_inner().unwrap()
+}
+
 pub trait Aggregate {
-    fn aggregate(&mut self, other: &Vec<f32>);
+    fn aggregate(&mut self, other: &[f32]);
 }
 
 impl Aggregate for Vec<f32> {
-    fn aggregate(&mut self, other: &Vec<f32>) {
+    fn aggregate(&mut self, other: &[f32]) {
         if self.is_empty() {
-            *self = other.clone();
+            *self = other.to_vec();
         } else {
             assert!(other.is_empty() || self.len() == other.len());
             for (s, x) in self.iter_mut().zip(other.iter()) {
@@ -1,9 +1,15 @@
 use crate::{
-    decision_tree::{self, BlockTree, Predictor, RegressorModel},
-    ensemble_predictor,
-    ensemble_trainer::{self, EnsembleConfig},
+    codegen::{self, Language},
+    decision_tree::{
+        self, BlockTree, CompactTree, Predictor, RegressorModel, RegressorView, Validate,
+    },
+    ensemble_predictor::{self, Parallelism},
+    ensemble_trainer::{self, EnsembleConfig, WarmStartError},
+    executor::{Executor, ScopedThreads},
+    quantized::{Quantization, QuantizedRegressor},
+    quick_scorer::QuickRegressor,
     trainer_builders::*,
-    BatchPredictor, FloatTarget, Trainset,
+    tree_render, BatchPredictor, Export, FloatTarget, InvalidModel, Resolve, Trainset,
 };
 use serde::{Deserialize, Serialize};
 
@@ -14,12 +20,14 @@ use serde::{Deserialize, Serialize};
 /// ```text
 /// max_depth: usize::MAX,
 /// max_features: NumFeatures::NUMBER(usize::MAX),
+/// strict_max_features: false,
 /// seed: 42,
 /// min_samples_leaf: 1,
 /// min_samples_split: 2,
 /// sample_weights: empty (1.0 for each sample)
 /// num_trees: 100,
 /// num_threads: 1,
+/// max_features_per_tree: MaxFeaturesPolicy::NUMBER(usize::MAX),
 ///```
 /// # Example
 /// ```
@@ -33,6 +41,22 @@ use serde::{Deserialize, Serialize};
 #[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Regressor<P: Predictor = BlockTree> {
     ensemble: Vec<RegressorModel<P>>,
+    #[serde(skip)]
+    parallelism: Parallelism,
+}
+
+/// Defines how the predictions of trees are combined.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Averaging {
+    /// Mean of tree predictions.
+    MEAN,
+    /// Weighted mean of tree predictions, the vector holds a weight for each tree.
+    WEIGHTED(Vec<f32>),
+    /// Median of tree predictions.
+    MEDIAN,
+    /// Mean of tree predictions after dropping the given fraction (in `[0, 0.5)`) of the lowest and
+    /// the highest predictions.
+    TRIMMED(f32),
 }
 
 /// Trainer for ensemble regressor.
@@ -46,7 +70,7 @@ impl<P: Predictor> Default for Trainer<P> {
     fn default() -> Self {
         Self {
             config: EnsembleConfig::default(),
-            _marker: std::marker::PhantomData::default(),
+            _marker: std::marker::PhantomData,
         }
     }
 }
@@ -65,7 +89,39 @@ impl<P: Predictor> ensemble_trainer::Trainable<FloatTarget> for Trainee<P> {
 impl<P: Predictor> BatchPredictor for RegressorModel<P> {
     fn predict(&self, dataset: &[f32]) -> Vec<f32> {
         //self.predict(dataset)
-        Self::predict(&self, dataset)
+        Self::predict(self, dataset)
+    }
+
+    fn accumulate(&self, dataset: &[f32], out: &mut [f32]) {
+        Self::accumulate(self, dataset, out);
+    }
+
+    fn num_features(&self) -> usize {
+        Self::num_features(self)
+    }
+
+    fn num_outputs(&self) -> usize {
+        1
+    }
+}
+
+impl<V: Resolve> BatchPredictor for RegressorView<V> {
+    fn predict(&self, dataset: &[f32]) -> Vec<f32> {
+        let mut result = vec![0.; dataset.len() / self.num_features];
+        self.predict_into(dataset, &mut result);
+        result
+    }
+
+    fn accumulate(&self, dataset: &[f32], out: &mut [f32]) {
+        Self::accumulate(self, dataset, out);
+    }
+
+    fn num_features(&self) -> usize {
+        self.num_features
+    }
+
+    fn num_outputs(&self) -> usize {
+        1
     }
 }
 
@@ -73,31 +129,306 @@ impl<P: Predictor + Default + Sync + Send + Clone> Trainer<P> {
     /// Trains a random forest regressor with dataset given by a slice of length divisible by
     /// targets.len().
     pub fn train(&self, data: &[f32], targets: &[FloatTarget]) -> Regressor<P> {
+        self.train_in(data, targets, &ScopedThreads(self.config.num_threads))
+    }
+
+    /// Same as `train`, but the trees are trained by given `executor` instead of `num_threads`
+    /// new threads.
+    pub fn train_in(
+        &self,
+        data: &[f32],
+        targets: &[FloatTarget],
+        executor: &dyn Executor,
+    ) -> Regressor<P> {
+        self.fit(Vec::new(), data, targets, executor)
+    }
+
+    /// Continues training of a `model` with the parameters of the trainer, see [WarmStart].
+    pub fn warm_start<'a>(&self, model: &'a Regressor<P>) -> WarmStart<'a, P> {
+        WarmStart {
+            trainer: self.clone(),
+            model,
+        }
+    }
+
+    // Trains new trees appended to `ensemble`.
+    fn fit(
+        &self,
+        mut ensemble: Vec<RegressorModel<P>>,
+        data: &[f32],
+        targets: &[FloatTarget],
+        executor: &dyn Executor,
+    ) -> Regressor<P> {
         let trainset = Trainset::with_transposed(data, targets);
         let trainee: Trainee<P> = Trainee::default();
-        let ens = ensemble_trainer::fit(trainee, &trainset, &self.config);
+        let ens = ensemble_trainer::fit(trainee, &trainset, &self.config, ensemble.len(), executor);
+        ensemble.extend(ens.into_iter().map(|t| t.tree));
 
         Regressor {
-            ensemble: ens.into_iter().map(|t| t.tree).collect(),
+            ensemble,
+            parallelism: Parallelism::default(),
         }
     }
 }
 
+/// Continuation of training of a forest, created by [Trainer::warm_start]. Appends `num_trees` new
+/// trees to the trees of the model. The seeds of new trees continue the sequence of seeds of the
+/// model trees, so adding trees to a forest gives the same forest as training all trees at once
+/// with the same parameters.
+///
+/// Implements [CommonTrainerBuilder] and [EnsembleTrainerBuilder], the parameters are initially
+/// those of the trainer.
+#[derive(Clone, Debug)]
+pub struct WarmStart<'a, P: Predictor> {
+    trainer: Trainer<P>,
+    model: &'a Regressor<P>,
+}
+
+impl<P: Predictor + Default + Sync + Send + Clone> WarmStart<'_, P> {
+    /// Trains new trees on a dataset given by a slice of length divisible by targets.len().
+    /// Returns an error if samples have a different number of features than the model.
+    pub fn train(
+        &self,
+        data: &[f32],
+        targets: &[FloatTarget],
+    ) -> Result<Regressor<P>, WarmStartError> {
+        let executor = ScopedThreads(self.trainer.config.num_threads);
+        self.train_in(data, targets, &executor)
+    }
+
+    /// Same as `train`, but the trees are trained by given `executor` instead of `num_threads`
+    /// new threads.
+    pub fn train_in(
+        &self,
+        data: &[f32],
+        targets: &[FloatTarget],
+        executor: &dyn Executor,
+    ) -> Result<Regressor<P>, WarmStartError> {
+        ensemble_trainer::check_warm_start(self.model.num_features(), data, targets.len())?;
+        let ensemble = self.model.ensemble.clone();
+        Ok(self.trainer.fit(ensemble, data, targets, executor))
+    }
+}
+
+impl<P: Predictor> TrainConfigProvider for WarmStart<'_, P> {
+    fn train_config(&mut self) -> &mut decision_tree::TrainConfig {
+        self.trainer.train_config()
+    }
+}
+
+impl<P: Predictor> EnsembleConfigProvider for WarmStart<'_, P> {
+    fn ensemble_config(&mut self) -> &mut EnsembleConfig {
+        self.trainer.ensemble_config()
+    }
+}
+
+impl<P: Predictor> CommonTrainerBuilder for WarmStart<'_, P> {}
+impl<P: Predictor> EnsembleTrainerBuilder for WarmStart<'_, P> {}
+
 impl<P: Predictor + Sync + Send> Regressor<P> {
+    pub(crate) fn from_parts(ensemble: Vec<RegressorModel<P>>) -> Self {
+        Self {
+            ensemble,
+            parallelism: Parallelism::default(),
+        }
+    }
+
     /// Predicts regression values for a set of samples using `num_threads` threads.
     pub fn predict_batch(&self, dataset: &[f32], num_threads: usize) -> Vec<FloatTarget> {
-        ensemble_predictor::predict(&self.ensemble, dataset, num_threads)
+        self.predict_batch_in(dataset, &ScopedThreads(num_threads))
+    }
+
+    /// Predicts regression values for a set of samples using given `executor`.
+    pub fn predict_batch_in(&self, dataset: &[f32], executor: &dyn Executor) -> Vec<FloatTarget> {
+        ensemble_predictor::predict(&self.ensemble, dataset, executor, self.parallelism)
+    }
+
+    /// Writes regression values for a set of samples to `out` of length number_of_samples. The
+    /// prediction runs on the calling thread and does not allocate memory.
+    pub fn predict_into(&self, dataset: &[f32], out: &mut [FloatTarget]) {
+        ensemble_predictor::predict_into(&self.ensemble, dataset, out, &ScopedThreads(1));
+    }
+
+    /// Predicts regression values for a set of samples using `num_threads` threads, tree
+    /// predictions are combined with given `averaging`.
+    pub fn predict_batch_with(
+        &self,
+        dataset: &[f32],
+        num_threads: usize,
+        averaging: &Averaging,
+    ) -> Vec<FloatTarget> {
+        let executor = &ScopedThreads(num_threads);
+        let num_trees = self.ensemble.len();
+        let num_drop = match averaging {
+            Averaging::MEAN => return self.predict_batch_in(dataset, executor),
+            Averaging::WEIGHTED(weights) => {
+                return ensemble_predictor::predict_weighted(
+                    &self.ensemble,
+                    weights,
+                    dataset,
+                    executor,
+                )
+            }
+            Averaging::MEDIAN => (num_trees - 1) / 2,
+            Averaging::TRIMMED(fraction) => {
+                assert!((0.0..0.5).contains(fraction));
+                (fraction * num_trees as f32) as usize
+            }
+        };
+
+        let predictions = ensemble_predictor::predict_all(&self.ensemble, dataset, executor);
+        let mut values: Vec<f32> = vec![0.; num_trees];
+        (0..predictions[0].len())
+            .map(|i| {
+                for (v, p) in values.iter_mut().zip(predictions.iter()) {
+                    *v = p[i];
+                }
+                values.sort_by(f32::total_cmp);
+                let kept = &values[num_drop..num_trees - num_drop];
+                kept.iter().sum::<f32>() / kept.len() as f32
+            })
+            .collect()
     }
 
     /// Predicts regression value for a single sample given by a slice of length num_features().
+    /// Does not allocate memory.
     pub fn predict_one(&self, sample: &[f32]) -> FloatTarget {
-        ensemble_predictor::predict(&self.ensemble, sample, 1)[0]
+        assert!(sample.len() == self.num_features());
+        let sum: f32 = self.ensemble.iter().map(|t| t.predict_one(sample)).sum();
+        sum / self.ensemble.len() as f32
+    }
+
+    /// Sets how prediction is split between threads, `Parallelism::AUTO` by default. The option is
+    /// not serialized.
+    pub fn set_parallelism(&mut self, parallelism: Parallelism) {
+        self.parallelism = parallelism;
+    }
+
+    /// Returns a number of features for a trained forest.
+    pub fn num_features(&self) -> usize {
+        self.ensemble[0].num_features()
     }
 
     /// Provides trainer for training a random forest regressor.
     pub fn trainer() -> Trainer<P> {
         Trainer::default()
     }
+
+    /// Returns a number of trees in forest.
+    pub fn num_trees(&self) -> usize {
+        self.ensemble.len()
+    }
+
+    pub(crate) fn trees(&self) -> &[RegressorModel<P>] {
+        &self.ensemble
+    }
+
+    /// Appends trees of `other` forest trained on the same feature space.
+    pub fn merge(&mut self, other: Regressor<P>) {
+        assert!(self.num_features() == other.num_features());
+        self.ensemble.extend(other.ensemble);
+    }
+
+    /// Keeps only the first `num_trees` trees. A forest keeps at least one tree, so `num_trees`
+    /// must be positive.
+    pub fn truncate(&mut self, num_trees: usize) {
+        assert!(num_trees > 0, "forest must keep at least one tree");
+        self.ensemble.truncate(num_trees);
+    }
+}
+
+impl<P: Predictor + Validate> Regressor<P> {
+    /// Checks structural invariants of the model, e.g. after deserialization from untrusted
+    /// source: bounds and acyclicity of trees, leaf values. A valid model
+    /// predicts samples of num_features() features without panics.
+    pub fn validate(&self) -> Result<(), InvalidModel> {
+        let Some(first) = self.ensemble.first() else {
+            return Err(InvalidModel("forest has no trees".to_string()));
+        };
+        for (i, tree) in self.ensemble.iter().enumerate() {
+            let error = |e: String| InvalidModel(format!("tree {i}: {e}"));
+            tree.validate().map_err(|e| error(e.0))?;
+            if tree.num_features() != first.num_features() {
+                return Err(error(
+                    "trees have different numbers of features".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<P: Predictor + Export + Send + Sync> Regressor<P> {
+    /// Returns tree `index` of the forest as a Graphviz DOT graph, see
+    /// [dt::Regressor::to_dot](crate::dt::Regressor::to_dot).
+    pub fn tree_to_dot(&self, index: usize, feature_names: Option<&[&str]>) -> String {
+        tree_render::check_names(feature_names, self.num_features());
+        let root = self.ensemble[index].predictor().export();
+        tree_render::dot(
+            &root,
+            self.num_features(),
+            feature_names,
+            &tree_render::value_leaf,
+        )
+    }
+
+    /// Returns tree `index` of the forest as indented text rules, see
+    /// [dt::Regressor::to_text_rules](crate::dt::Regressor::to_text_rules).
+    pub fn tree_to_text_rules(&self, index: usize, feature_names: Option<&[&str]>) -> String {
+        tree_render::check_names(feature_names, self.num_features());
+        let root = self.ensemble[index].predictor().export();
+        tree_render::text_rules(
+            &root,
+            self.num_features(),
+            feature_names,
+            &tree_render::value_leaf,
+        )
+    }
+}
+
+impl<P: Predictor + Export> Regressor<P> {
+    /// Removes splits whose subtrees predict the same value for any sample. Predictions do not
+    /// change.
+    pub fn simplify(&mut self) {
+        for tree in self.ensemble.iter_mut() {
+            tree.simplify();
+        }
+    }
+
+    /// Generates standalone Rust source of the model with names starting with `prefix`, see
+    /// [codegen].
+    pub fn to_rust_source(&self, prefix: &str) -> String {
+        codegen::regressor(&self.ensemble, Language::Rust, prefix)
+    }
+
+    /// Generates standalone C source of the model with names starting with `prefix`, see
+    /// [codegen].
+    pub fn to_c_source(&self, prefix: &str) -> String {
+        codegen::regressor(&self.ensemble, Language::C, prefix)
+    }
+
+    /// Quantizes the forest for compact storage, see [quantized](crate::quantized).
+    pub fn quantize(&self, quantization: &Quantization) -> QuantizedRegressor {
+        QuantizedRegressor::new(&self.ensemble, quantization)
+    }
+}
+
+impl Regressor<CompactTree> {
+    /// Compiles the forest into [QuickRegressor], an alternative inference backend.
+    pub fn quick_scorer(&self) -> QuickRegressor {
+        QuickRegressor::new(&self.ensemble)
+    }
+}
+
+impl<P: Predictor + Clone> Regressor<P> {
+    /// Returns a forest consisting of trees with given indexes. `indexes` must not be empty.
+    pub fn select_trees(&self, indexes: &[usize]) -> Self {
+        assert!(!indexes.is_empty(), "forest must keep at least one tree");
+        Self {
+            ensemble: indexes.iter().map(|&i| self.ensemble[i].clone()).collect(),
+            parallelism: self.parallelism,
+        }
+    }
 }
 
 impl<P: Predictor> TrainConfigProvider for Trainer<P> {
@@ -1,15 +1,77 @@
 use crate::{
     decision_tree,
+    executor::Executor,
     trainer_builders::{CommonTrainerBuilder, TrainConfigProvider},
-    SampleWeight, Trainset,
+    ClassTarget, FloatTarget, MaxFeaturesPolicy, SampleWeight, Trainset,
 };
-use rand::{rngs::SmallRng, Rng, SeedableRng};
+use rand::{rngs::SmallRng, seq::index, Rng, SeedableRng};
 use std::{
-    sync::atomic::{AtomicUsize, Ordering},
-    sync::Arc,
-    thread,
+    fmt::{Debug, Display},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
 };
 
+/// Defines how the training samples are drawn for each tree of ensemble.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SamplingPolicy {
+    /// Draws `num_samples` samples uniformly with replacement.
+    BOOTSTRAP,
+    /// Draws samples with replacement within each class, so each class keeps its size and class
+    /// proportions are preserved.
+    STRATIFIED,
+    /// Balanced Random Forest: draws with replacement from each class the number of samples equal
+    /// to the size of the smallest class.
+    BALANCED,
+}
+
+/// An error returned when training of a forest can not continue from a model.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum WarmStartError<L = i64> {
+    /// Samples have `found` features, while the model is trained on `expected` features.
+    FEATURES { expected: usize, found: usize },
+    /// A training label is not one of the classes of a classifier. Returned by classifiers only.
+    LABEL(L),
+    /// Classes `found` are declared for a classifier of classes `expected`. Returned by
+    /// classifiers only.
+    CLASSES { expected: Vec<L>, found: Vec<L> },
+}
+
+impl<L: Debug> Display for WarmStartError<L> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WarmStartError::FEATURES { expected, found } => write!(
+                f,
+                "Samples have {found} features, the model is trained on {expected} features."
+            ),
+            WarmStartError::LABEL(label) => write!(f, "Unknown class label {label:?}."),
+            WarmStartError::CLASSES { expected, found } => write!(
+                f,
+                "Classes {found:?} are declared, the model has classes {expected:?}."
+            ),
+        }
+    }
+}
+
+impl<L: Debug> std::error::Error for WarmStartError<L> {}
+
+// Checks that a model of `num_features` features can be trained further on `data` of
+// `num_samples` samples.
+pub fn check_warm_start<L>(
+    num_features: usize,
+    data: &[f32],
+    num_samples: usize,
+) -> Result<(), WarmStartError<L>> {
+    if data.len() != num_samples * num_features {
+        return Err(WarmStartError::FEATURES {
+            expected: num_features,
+            found: data.len().checked_div(num_samples).unwrap_or(0),
+        });
+    }
+    Ok(())
+}
+
 // Configuration for training the ensembles of trees.
 #[derive(Clone, PartialEq, Debug)]
 pub struct EnsembleConfig {
@@ -21,6 +83,13 @@ pub struct EnsembleConfig {
     /// Number of threads to use. Please note that there is no specific value for "use all cores".
     /// Maximun number of theads can be obtained useing, for instance, crate `num_cpus`.
     pub num_threads: usize,
+
+    /// Strategy of drawing samples for each tree.
+    pub sampling: SamplingPolicy,
+
+    /// Number of features in a random subset drawn for each tree. Each tree is trained on its own
+    /// subset of dataset columns.
+    pub max_features_per_tree: MaxFeaturesPolicy,
 }
 
 impl Default for EnsembleConfig {
@@ -29,6 +98,8 @@ impl Default for EnsembleConfig {
             tree_config_proto: decision_tree::TrainConfig::default(),
             num_trees: 100,
             num_threads: 1,
+            sampling: SamplingPolicy::BOOTSTRAP,
+            max_features_per_tree: MaxFeaturesPolicy::NUMBER(usize::MAX),
         }
     }
 }
@@ -45,54 +116,127 @@ pub trait Trainable<T: Copy> {
     fn fit(&mut self, ts: &Trainset<T>, config: decision_tree::TrainConfig);
 }
 
+// Provides the class of a target for stratified sampling. Targets without classes form a single
+// stratum.
+pub trait Stratum {
+    fn stratum(&self) -> Option<usize>;
+}
+
+impl Stratum for ClassTarget {
+    #[inline(always)]
+    fn stratum(&self) -> Option<usize> {
+        Some(*self as usize)
+    }
+}
+
+impl Stratum for FloatTarget {
+    #[inline(always)]
+    fn stratum(&self) -> Option<usize> {
+        None
+    }
+}
+
+// Trains `config.num_trees` trees. Trees are seeded as if they follow `first_tree` trees trained
+// with the same config, this allows adding trees to existing ensemble.
 pub fn fit<Target, Trainee>(
     proto: Trainee,
     trainset: &Trainset<Target>,
     config: &EnsembleConfig,
+    first_tree: usize,
+    executor: &dyn Executor,
 ) -> Vec<Trainee>
 where
-    Target: Copy + Sync + Send,
+    Target: Copy + Sync + Send + Stratum,
     Trainee: Trainable<Target> + Clone + Send + Sync,
 {
-    assert!(config.num_threads > 0);
+    assert!(executor.num_threads() > 0);
     let seed = config.tree_config_proto.seed;
     let mut rng = SmallRng::seed_from_u64(seed);
-    let seeds: Vec<u64> = (0..config.num_trees).map(|_| rng.random()).collect();
+    let seeds: Vec<u64> = (0..first_tree + config.num_trees)
+        .map(|_| rng.random())
+        .skip(first_tree)
+        .collect();
+    let strata = strata(trainset.targets);
+    let num_features = trainset.num_features;
+    let tree_features = config.max_features_per_tree.resolve(num_features).max(1);
 
     let num_trees = config.num_trees;
-    let tree_idx = Arc::new(AtomicUsize::new(0));
-    let mut ensemble: Vec<Trainee> = Vec::new();
-    thread::scope(|s| {
-        let mut handles = Vec::new();
-        for _ in 0..config.num_threads {
-            let handle = s.spawn(|| {
-                let mut trainees: Vec<Trainee> = Vec::new();
-                let mut id = 0;
-                while id < num_trees {
-                    id = tree_idx.fetch_add(1, Ordering::Relaxed);
-                    if id < num_trees {
-                        let mut rng = SmallRng::seed_from_u64(seeds[id]);
-                        let scalars = bootstrap(trainset.size(), &mut rng);
-                        let mut trainee = proto.clone();
-                        let mut train_config = config.tree_config_proto.clone();
-                        train_config.scale_weights(&scalars);
-                        train_config.seed = rng.random();
-                        trainee.fit(trainset, train_config);
-                        trainees.push(trainee);
-                    }
-                }
-                trainees
-            });
-
-            handles.push(handle);
+    let tree_idx = AtomicUsize::new(0);
+    let ensemble: Mutex<Vec<(usize, Trainee)>> = Mutex::new(Vec::new());
+    executor.execute(&|| loop {
+        let id = tree_idx.fetch_add(1, Ordering::Relaxed);
+        if id >= num_trees {
+            break;
         }
 
-        for handle in handles {
-            ensemble.extend(handle.join().unwrap());
+        let mut rng = SmallRng::seed_from_u64(seeds[id]);
+        let scalars = sample(&strata, trainset.size(), &config.sampling, &mut rng);
+        let mut trainee = proto.clone();
+        let mut train_config = config.tree_config_proto.clone();
+        train_config.scale_weights(&scalars);
+        train_config.seed = rng.random();
+        if tree_features < num_features {
+            let mut columns = index::sample(&mut rng, num_features, tree_features).into_vec();
+            columns.sort();
+            train_config.columns = columns;
         }
+        trainee.fit(trainset, train_config);
+        ensemble.lock().unwrap().push((id, trainee));
     });
 
-    ensemble
+    // Trees are ordered by their seeds regardless of the threads that trained them.
+    let mut ensemble = ensemble.into_inner().unwrap();
+    ensemble.sort_by_key(|(id, _)| *id);
+    ensemble.into_iter().map(|(_, t)| t).collect()
+}
+
+// Groups sample indexes by their classes. Empty classes are skipped.
+pub fn strata<T: Stratum>(targets: &[T]) -> Vec<Vec<u32>> {
+    let mut strata: Vec<Vec<u32>> = Vec::new();
+    for (i, t) in targets.iter().enumerate() {
+        let s = t.stratum().unwrap_or(0);
+        if s >= strata.len() {
+            strata.resize(s + 1, Vec::new());
+        }
+        strata[s].push(i as u32);
+    }
+    strata.retain(|s| !s.is_empty());
+    strata
+}
+
+// Returns a multiplicity of each sample drawn according to sampling policy.
+pub fn sample(
+    strata: &[Vec<u32>],
+    num_samples: usize,
+    policy: &SamplingPolicy,
+    rng: &mut SmallRng,
+) -> Vec<SampleWeight> {
+    match policy {
+        SamplingPolicy::BOOTSTRAP => bootstrap(num_samples, rng),
+        SamplingPolicy::STRATIFIED => {
+            let mut weights: Vec<SampleWeight> = vec![0.; num_samples];
+            for s in strata.iter() {
+                draw(s, s.len(), &mut weights, rng);
+            }
+            weights
+        }
+        SamplingPolicy::BALANCED => {
+            let mut weights: Vec<SampleWeight> = vec![0.; num_samples];
+            let minority = strata.iter().map(|s| s.len()).min().unwrap_or(0);
+            for s in strata.iter() {
+                draw(s, minority, &mut weights, rng);
+            }
+            weights
+        }
+    }
+}
+
+// Draws `n` samples with replacement from `samples`.
+fn draw(samples: &[u32], n: usize, weights: &mut [SampleWeight], rng: &mut SmallRng) {
+    for _ in 0..n {
+        let i = samples[rng.random_range(0..samples.len())];
+        weights[i as usize] += 1.;
+    }
 }
 
 fn bootstrap(num_samples: usize, rng: &mut SmallRng) -> Vec<SampleWeight> {
@@ -0,0 +1,143 @@
+//! Executors of parallel tasks for ensemble training and prediction.
+//!
+//! Methods of random forests taking `num_threads` spawn new threads on every call. For frequent
+//! calls on small batches the cost of spawning threads may exceed the cost of inference. In this
+//! case create an executor once, e.g. a [ThreadPool], and pass it to `*_in` methods:
+//! ```
+//! use rafor::{executor::ThreadPool, rf};
+//! let dataset = [0.7, 0.0, 0.8, 1.0, 0.7, 0.0];
+//! let targets = [1, 5, 1];
+//! let pool = ThreadPool::new(2);
+//! let predictor: rf::Classifier = rf::Classifier::trainer().train_in(&dataset, &targets, &pool);
+//! for _ in 0..10 {
+//!     let predictions = predictor.predict_batch_in(&dataset, &pool);
+//!     assert_eq!(&predictions, &[1, 5, 1]);
+//! }
+//! ```
+//! With `rayon` feature enabled, `rayon::ThreadPool` implements [Executor] as well.
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::mpsc::{self, Sender},
+    thread::{self, JoinHandle},
+};
+
+/// Runs a task on a number of threads in parallel.
+pub trait Executor: Sync {
+    /// Returns the number of threads the task runs on.
+    fn num_threads(&self) -> usize;
+
+    /// Runs `task` once on each of `num_threads()` threads and waits until all of them finish.
+    fn execute(&self, task: &(dyn Fn() + Sync));
+}
+
+/// Executor that spawns `n` scoped threads on each call. With a single thread the task runs on
+/// the calling thread.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ScopedThreads(pub usize);
+
+impl Executor for ScopedThreads {
+    fn num_threads(&self) -> usize {
+        self.0
+    }
+
+    fn execute(&self, task: &(dyn Fn() + Sync)) {
+        assert!(self.0 > 0);
+        if self.0 == 1 {
+            task();
+        } else {
+            thread::scope(|s| {
+                for _ in 0..self.0 {
+                    s.spawn(task);
+                }
+            });
+        }
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A pool of persistent threads. The threads are created once and are reused by all calls.
+///
+/// **Note**. Tasks must not call [Executor::execute] of the same pool, it would deadlock.
+#[derive(Debug)]
+pub struct ThreadPool {
+    workers: Vec<(Sender<Job>, JoinHandle<()>)>,
+}
+
+impl ThreadPool {
+    /// Creates a pool of `num_threads` threads. Panics if zero is specified.
+    pub fn new(num_threads: usize) -> Self {
+        assert!(num_threads > 0);
+        let workers = (0..num_threads)
+            .map(|_| {
+                let (sender, receiver) = mpsc::channel::<Job>();
+                let handle = thread::spawn(move || {
+                    for job in receiver {
+                        job();
+                    }
+                });
+                (sender, handle)
+            })
+            .collect();
+        Self { workers }
+    }
+}
+
+impl Executor for ThreadPool {
+    fn num_threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    fn execute(&self, task: &(dyn Fn() + Sync)) {
+        // SAFETY: the task reference is used only by jobs sent below, and this function does not
+        // return until every sent job reports its completion, so the reference outlives its uses.
+        let task: &'static (dyn Fn() + Sync) = unsafe { std::mem::transmute(task) };
+        let (done_sender, done) = mpsc::channel();
+        let mut num_jobs = 0;
+        for (sender, _) in self.workers.iter() {
+            let done_sender = done_sender.clone();
+            let job: Job = Box::new(move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(task));
+                let _ = done_sender.send(result);
+            });
+            if sender.send(job).is_ok() {
+                num_jobs += 1;
+            }
+        }
+
+        let mut panic_payload = None;
+        for _ in 0..num_jobs {
+            if let Err(payload) = done.recv().unwrap() {
+                panic_payload = Some(payload);
+            }
+        }
+
+        if let Some(payload) = panic_payload {
+            panic::resume_unwind(payload);
+        }
+        assert!(
+            num_jobs == self.workers.len(),
+            "Thread pool worker is dead."
+        );
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        for (sender, handle) in std::mem::take(&mut self.workers) {
+            drop(sender);
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl Executor for rayon::ThreadPool {
+    fn num_threads(&self) -> usize {
+        self.current_num_threads()
+    }
+
+    fn execute(&self, task: &(dyn Fn() + Sync)) {
+        self.broadcast(|_| task());
+    }
+}
@@ -0,0 +1,441 @@
+//! Versioned model files.
+//!
+//! [save] writes a model with a header describing it and a checksum of the serialized model,
+//! [load] checks them before returning the model:
+//! ```text
+//! magic:        8 bytes "RAFORMDL"
+//! version:      u32, FORMAT_VERSION
+//! kind:         u8, ModelKind
+//! task:         u8, Task
+//! layout:       u8, Layout
+//! reserved:     u8
+//! num_features: u64
+//! size:         u64, the size of the serialized model
+//! checksum:     u32, CRC-32 of the serialized model
+//! model:        the model serialized by bincode with standard config
+//! ```
+//! All numbers are little-endian. Files written by bincode with standard config before v0.4.0
+//! (without a header) are loaded as version 0 and migrated to the current model structures.
+//! Loaded models are checked with `validate()`, so corrupted or crafted files are rejected instead of
+//! panicking during inference.
+//! ```
+//! use rafor::{io, rf};
+//! let dataset = [0.7, 0.0, 0.8, 1.0, 0.7, 0.0];
+//! let targets = [1, 5, 1];
+//! let predictor: rf::Classifier = rf::Classifier::trainer().train(&dataset, &targets);
+//! let path = std::env::temp_dir().join("rafor_io_example.bin");
+//! io::save(&predictor, &path).unwrap();
+//! assert_eq!(io::read_header(&path).unwrap().num_features, 2);
+//! let loaded: rf::Classifier = io::load(&path).unwrap();
+//! assert_eq!(loaded.predict_batch(&dataset, 1), [1, 5, 1]);
+//! ```
+use crate::{
+    decision_tree::ClassifierModel, dt, rf, BlockTree, ClassesMapping, CompactTree, InvalidModel,
+    Label, Predictor, Validate,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+/// The version of written files.
+pub const FORMAT_VERSION: u32 = 1;
+
+const MAGIC: &[u8; 8] = b"RAFORMDL";
+const HEADER_SIZE: usize = 36;
+
+/// Kind of a model.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelKind {
+    /// A decision tree.
+    TREE,
+    /// A random forest.
+    FOREST,
+}
+
+/// Task of a model.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Task {
+    CLASSIFIER,
+    REGRESSOR,
+}
+
+/// Layout of trees of a model.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Layout {
+    /// [BlockTree].
+    BLOCK,
+    /// [CompactTree].
+    COMPACT,
+}
+
+impl ModelKind {
+    fn from_byte(b: u8) -> Option<Self> {
+        [Self::TREE, Self::FOREST]
+            .into_iter()
+            .find(|&k| k as u8 == b)
+    }
+}
+
+impl Task {
+    pub(crate) fn from_byte(b: u8) -> Option<Self> {
+        [Self::CLASSIFIER, Self::REGRESSOR]
+            .into_iter()
+            .find(|&t| t as u8 == b)
+    }
+}
+
+impl Layout {
+    pub(crate) fn from_byte(b: u8) -> Option<Self> {
+        [Self::BLOCK, Self::COMPACT]
+            .into_iter()
+            .find(|&l| l as u8 == b)
+    }
+}
+
+/// Header of a model file.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Header {
+    pub version: u32,
+    pub kind: ModelKind,
+    pub task: Task,
+    pub layout: Layout,
+    pub num_features: usize,
+}
+
+/// An error of reading or writing a model file.
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    /// The file is written by a newer version of the library.
+    UnsupportedVersion(u32),
+    /// The header describes a model of another type.
+    Mismatch {
+        expected: Header,
+        found: Header,
+    },
+    /// The checksum of the serialized model does not match the header.
+    Checksum,
+    /// The model can not be serialized or deserialized.
+    Codec(String),
+    /// The deserialized model does not pass `validate()`.
+    Invalid(InvalidModel),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {e}."),
+            Error::UnsupportedVersion(v) => write!(f, "Unsupported format version {v}."),
+            Error::Mismatch { expected, found } => {
+                write!(f, "Expected model {expected:?}, found {found:?}.")
+            }
+            Error::Checksum => write!(f, "Checksum mismatch."),
+            Error::Codec(e) => write!(f, "Malformed model: {e}."),
+            Error::Invalid(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// Tree types that can be stored in model files.
+pub trait TreeLayout: Validate {
+    const LAYOUT: Layout;
+}
+
+impl TreeLayout for BlockTree {
+    const LAYOUT: Layout = Layout::BLOCK;
+}
+
+impl TreeLayout for CompactTree {
+    const LAYOUT: Layout = Layout::COMPACT;
+}
+
+mod sealed {
+    pub trait Legacy: Sized {
+        // Decodes a model written by bincode before v0.4.0.
+        fn decode_legacy(bytes: &[u8]) -> Result<Self, super::Error>;
+    }
+}
+
+/// A model that can be stored in model files: trees and random forests with [TreeLayout] trees.
+pub trait Model: sealed::Legacy + Serialize + DeserializeOwned {
+    const KIND: ModelKind;
+    const TASK: Task;
+    const LAYOUT: Layout;
+
+    /// Returns a number of features.
+    fn num_features(&self) -> usize;
+
+    /// Checks structural invariants of the model.
+    fn validate(&self) -> Result<(), InvalidModel>;
+}
+
+/// Writes `model` to a file at `path`.
+pub fn save<M: Model>(model: &M, path: impl AsRef<Path>) -> Result<(), Error> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write(model, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads a model from a file at `path`.
+pub fn load<M: Model>(path: impl AsRef<Path>) -> Result<M, Error> {
+    read(&mut BufReader::new(File::open(path)?))
+}
+
+/// Reads the header of a file at `path` without reading the model.
+pub fn read_header(path: impl AsRef<Path>) -> Result<Header, Error> {
+    let mut bytes = Vec::with_capacity(HEADER_SIZE);
+    File::open(path)?
+        .take(HEADER_SIZE as u64)
+        .read_to_end(&mut bytes)?;
+    decode_header(&bytes).map(|(header, _, _)| header)
+}
+
+/// Writes `model` with a header to `writer`.
+pub fn write<M: Model>(model: &M, writer: &mut impl Write) -> Result<(), Error> {
+    let payload = bincode::serde::encode_to_vec(model, bincode::config::standard())
+        .map_err(|e| Error::Codec(e.to_string()))?;
+    writer.write_all(MAGIC)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    writer.write_all(&[M::KIND as u8, M::TASK as u8, M::LAYOUT as u8, 0])?;
+    writer.write_all(&(model.num_features() as u64).to_le_bytes())?;
+    writer.write_all(&(payload.len() as u64).to_le_bytes())?;
+    writer.write_all(&crc32(&payload).to_le_bytes())?;
+    writer.write_all(&payload)?;
+    Ok(())
+}
+
+/// Reads a model written by [write()] or a model written by bincode before v0.4.0 from `reader`.
+pub fn read<M: Model>(reader: &mut impl Read) -> Result<M, Error> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    if !bytes.starts_with(MAGIC) {
+        return migrate(0, &bytes);
+    }
+
+    let (header, size, checksum) = decode_header(&bytes)?;
+    let expected = Header {
+        version: header.version,
+        kind: M::KIND,
+        task: M::TASK,
+        layout: M::LAYOUT,
+        num_features: header.num_features,
+    };
+    if header != expected {
+        return Err(Error::Mismatch {
+            expected,
+            found: header,
+        });
+    }
+
+    let payload = &bytes[HEADER_SIZE..];
+    if payload.len() as u64 != size || crc32(payload) != checksum {
+        return Err(Error::Checksum);
+    }
+    let model: M = migrate(header.version, payload)?;
+    if model.num_features() != header.num_features {
+        return Err(Error::Codec("number of features mismatch".to_string()));
+    }
+    Ok(model)
+}
+
+// Decodes and validates a model serialized in given format version.
+fn migrate<M: Model>(version: u32, payload: &[u8]) -> Result<M, Error> {
+    let model: M = match version {
+        0 => M::decode_legacy(payload),
+        1 => decode(payload),
+        _ => Err(Error::UnsupportedVersion(version)),
+    }?;
+    model.validate().map_err(Error::Invalid)?;
+    Ok(model)
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Error> {
+    let (value, size) = bincode::serde::decode_from_slice(bytes, bincode::config::standard())
+        .map_err(|e| Error::Codec(e.to_string()))?;
+    if size != bytes.len() {
+        return Err(Error::Codec("trailing bytes".to_string()));
+    }
+    Ok(value)
+}
+
+// Returns the header, the size and the checksum of the model.
+fn decode_header(bytes: &[u8]) -> Result<(Header, u64, u32), Error> {
+    if bytes.len() < HEADER_SIZE || !bytes.starts_with(MAGIC) {
+        return Err(Error::Codec("not a model file".to_string()));
+    }
+    let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+    let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+    let version = u32_at(8);
+    if version > FORMAT_VERSION {
+        return Err(Error::UnsupportedVersion(version));
+    }
+    let invalid = |field: &str| Error::Codec(format!("invalid {field}"));
+    let header = Header {
+        version,
+        kind: ModelKind::from_byte(bytes[12]).ok_or_else(|| invalid("model kind"))?,
+        task: Task::from_byte(bytes[13]).ok_or_else(|| invalid("task"))?,
+        layout: Layout::from_byte(bytes[14]).ok_or_else(|| invalid("layout"))?,
+        num_features: u64_at(16) as usize,
+    };
+    Ok((header, u64_at(24), u32_at(32)))
+}
+
+// CRC-32 (IEEE 802.3).
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+// Classifier model before v0.4.0, it has no shared one-hot leaves.
+#[derive(Deserialize)]
+struct LegacyClassifierModel<P> {
+    proba: Vec<f32>,
+    num_classes: usize,
+    predictor: P,
+    num_features: usize,
+}
+
+impl<P: Predictor> From<LegacyClassifierModel<P>> for ClassifierModel<P> {
+    fn from(m: LegacyClassifierModel<P>) -> Self {
+        ClassifierModel::from_parts(m.proba, m.num_classes, m.predictor, m.num_features)
+    }
+}
+
+#[derive(Deserialize)]
+struct LegacyTreeClassifier<P, L> {
+    classifier: LegacyClassifierModel<P>,
+    classes_map: ClassesMapping<L>,
+}
+
+#[derive(Deserialize)]
+struct LegacyForestClassifier<P, L> {
+    ensemble: Vec<LegacyClassifierModel<P>>,
+    classes_map: ClassesMapping<L>,
+}
+
+impl<P, L> sealed::Legacy for dt::Classifier<P, L>
+where
+    P: Predictor + TreeLayout + DeserializeOwned,
+    L: Label + DeserializeOwned,
+{
+    fn decode_legacy(bytes: &[u8]) -> Result<Self, Error> {
+        let m: LegacyTreeClassifier<P, L> = decode(bytes)?;
+        Ok(Self::from_parts(m.classifier.into(), m.classes_map))
+    }
+}
+
+impl<P, L> Model for dt::Classifier<P, L>
+where
+    P: Predictor + TreeLayout + Serialize + DeserializeOwned,
+    L: Label + Serialize + DeserializeOwned,
+{
+    const KIND: ModelKind = ModelKind::TREE;
+    const TASK: Task = Task::CLASSIFIER;
+    const LAYOUT: Layout = P::LAYOUT;
+
+    fn num_features(&self) -> usize {
+        self.num_features()
+    }
+
+    fn validate(&self) -> Result<(), InvalidModel> {
+        self.validate()
+    }
+}
+
+impl<P, L> sealed::Legacy for rf::Classifier<P, L>
+where
+    P: Predictor + TreeLayout + Send + Sync + DeserializeOwned,
+    L: Label + DeserializeOwned,
+{
+    fn decode_legacy(bytes: &[u8]) -> Result<Self, Error> {
+        let m: LegacyForestClassifier<P, L> = decode(bytes)?;
+        let ensemble = m.ensemble.into_iter().map(|t| t.into()).collect();
+        Ok(Self::from_parts(ensemble, m.classes_map))
+    }
+}
+
+impl<P, L> Model for rf::Classifier<P, L>
+where
+    P: Predictor + TreeLayout + Send + Sync + Serialize + DeserializeOwned,
+    L: Label + Serialize + DeserializeOwned,
+{
+    const KIND: ModelKind = ModelKind::FOREST;
+    const TASK: Task = Task::CLASSIFIER;
+    const LAYOUT: Layout = P::LAYOUT;
+
+    fn num_features(&self) -> usize {
+        self.num_features()
+    }
+
+    fn validate(&self) -> Result<(), InvalidModel> {
+        self.validate()
+    }
+}
+
+// Regressors have not changed since v0.3.0.
+impl<P: Predictor + TreeLayout + DeserializeOwned> sealed::Legacy for dt::Regressor<P> {
+    fn decode_legacy(bytes: &[u8]) -> Result<Self, Error> {
+        decode(bytes)
+    }
+}
+
+impl<P: Predictor + TreeLayout + Serialize + DeserializeOwned> Model for dt::Regressor<P> {
+    const KIND: ModelKind = ModelKind::TREE;
+    const TASK: Task = Task::REGRESSOR;
+    const LAYOUT: Layout = P::LAYOUT;
+
+    fn num_features(&self) -> usize {
+        self.num_features()
+    }
+
+    fn validate(&self) -> Result<(), InvalidModel> {
+        self.validate()
+    }
+}
+
+impl<P> sealed::Legacy for rf::Regressor<P>
+where
+    P: Predictor + TreeLayout + Send + Sync + DeserializeOwned,
+{
+    fn decode_legacy(bytes: &[u8]) -> Result<Self, Error> {
+        decode(bytes)
+    }
+}
+
+impl<P> Model for rf::Regressor<P>
+where
+    P: Predictor + TreeLayout + Send + Sync + Serialize + DeserializeOwned,
+{
+    const KIND: ModelKind = ModelKind::FOREST;
+    const TASK: Task = Task::REGRESSOR;
+    const LAYOUT: Layout = P::LAYOUT;
+
+    fn num_features(&self) -> usize {
+        self.num_features()
+    }
+
+    fn validate(&self) -> Result<(), InvalidModel> {
+        self.validate()
+    }
+}
@@ -0,0 +1,461 @@
+//! Human-readable JSON representation of models.
+//!
+//! Every model type is represented by a [Document] with an explicit list of nodes for each tree.
+//! Documents can be imported back into models with any tree type, e.g. a forest of `CompactTree`s
+//! exported to JSON can be imported as a forest of `BlockTree`s.
+//! ```json
+//! {
+//!   "format": "rafor",
+//!   "version": 1,
+//!   "kind": "forest",
+//!   "task": "classifier",
+//!   "num_features": 2,
+//!   "classes": [1, 5],
+//!   "trees": [
+//!     {
+//!       "nodes": [
+//!         { "id": 0, "feature": 1, "threshold": 0.5, "left": 1, "right": 2 },
+//!         { "id": 1, "proba": [1.0, 0.0] },
+//!         { "id": 2, "proba": [0.25, 0.75], "samples": 4 }
+//!       ]
+//!     }
+//!   ]
+//! }
+//! ```
+//! * `kind` is `"tree"` or `"forest"`, a tree is stored as a single element of `trees`.
+//! * `task` is `"classifier"` or `"regressor"`.
+//! * `classes` holds class labels of classifiers in the order of probabilities in `proba`.
+//! * The first node of a tree is its root. A split node has `feature`, `threshold`, and ids of
+//!   `left` and `right` children; samples with `sample[feature] <= threshold` go to the left child.
+//!   Missing values (NaNs) go to the right child, or to the left child if the optional
+//!   `missing_left` is `true`.
+//! * A leaf node has class probabilities `proba` for classifiers or a predicted `value` for
+//!   regressors.
+//! * `samples` is an optional number of training samples of a node. Models do not keep it, so it is
+//!   not exported and it is ignored on import.
+//! * Trees deeper than 1024 splits are rejected on import.
+//!
+//! ```
+//! use rafor::{json, rf, CompactTree};
+//! let dataset = [0.7, 0.0, 0.8, 1.0, 0.7, 0.0];
+//! let targets = [1, 5, 1];
+//! let predictor: rf::Classifier = rf::Classifier::trainer().train(&dataset, &targets);
+//! let text = json::to_string_pretty(&predictor);
+//! let imported: rf::Classifier<CompactTree> = json::from_str(&text).unwrap();
+//! assert_eq!(imported.predict_batch(&dataset, 1), [1, 5, 1]);
+//! ```
+use crate::{
+    decision_tree::{
+        build, decode, split, ClassifierModel, DecodedNode, RegressorModel, Split, MAX_IMPORT_DEPTH,
+    },
+    dt,
+    io::{Error, ModelKind, Task},
+    rf, ClassDecode, ClassesMapping, Export, InvalidModel, Label, Predictor, TreeNode, Validate,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// The version of written documents.
+pub const JSON_VERSION: u32 = 1;
+
+const FORMAT: &str = "rafor";
+
+/// JSON document of a model.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Document {
+    pub format: String,
+    pub version: u32,
+    pub kind: ModelKind,
+    pub task: Task,
+    pub num_features: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classes: Option<Vec<serde_json::Value>>,
+    pub trees: Vec<Tree>,
+}
+
+/// Nodes of a tree, the first node is the root.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Tree {
+    pub nodes: Vec<Node>,
+}
+
+/// A split node or a leaf of a tree.
+#[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+    pub id: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feature: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub left: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub right: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub missing_left: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proba: Option<Vec<f32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub samples: Option<u64>,
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A model with JSON representation: trees and random forests.
+pub trait JsonModel: sealed::Sealed + Sized {
+    /// Returns the document of the model.
+    fn to_document(&self) -> Document;
+
+    /// Builds a model from `document` and checks it with `validate()`.
+    fn from_document(document: &Document) -> Result<Self, Error>;
+}
+
+/// Returns compact JSON of `model`.
+pub fn to_string(model: &impl JsonModel) -> String {
+    serde_json::to_string(&model.to_document()).unwrap()
+}
+
+/// Returns indented JSON of `model`.
+pub fn to_string_pretty(model: &impl JsonModel) -> String {
+    serde_json::to_string_pretty(&model.to_document()).unwrap()
+}
+
+/// Reads a model from JSON.
+pub fn from_str<M: JsonModel>(s: &str) -> Result<M, Error> {
+    let document: Document = serde_json::from_str(s).map_err(|e| Error::Codec(e.to_string()))?;
+    M::from_document(&document)
+}
+
+pub(crate) fn document(
+    kind: ModelKind,
+    task: Task,
+    num_features: usize,
+    trees: Vec<Tree>,
+) -> Document {
+    Document {
+        format: FORMAT.to_string(),
+        version: JSON_VERSION,
+        kind,
+        task,
+        num_features,
+        classes: None,
+        trees,
+    }
+}
+
+// Returns trees of `document` after checking its header.
+fn trees(document: &Document, kind: ModelKind, task: Task) -> Result<&[Tree], Error> {
+    if document.format != FORMAT {
+        return Err(Error::Codec(format!("unknown format {}", document.format)));
+    }
+    if document.version > JSON_VERSION {
+        return Err(Error::UnsupportedVersion(document.version));
+    }
+    if document.kind != kind || document.task != task {
+        return Err(Error::Codec(format!(
+            "expected {kind:?} {task:?}, found {:?} {:?}",
+            document.kind, document.task
+        )));
+    }
+    if kind == ModelKind::TREE && document.trees.len() != 1 {
+        return Err(Error::Codec(
+            "a tree document must hold one tree".to_string(),
+        ));
+    }
+    Ok(&document.trees)
+}
+
+// Returns nodes of a tree of `num_features` features in preorder, `leaf` fills leaf nodes by leaf
+// values.
+fn export_tree(root: &TreeNode, num_features: usize, leaf: &dyn Fn(u32, &mut Node)) -> Tree {
+    let mut nodes = Vec::new();
+    export_node(root, num_features, leaf, &mut nodes);
+    Tree { nodes }
+}
+
+fn export_node(
+    node: &TreeNode,
+    num_features: usize,
+    leaf: &dyn Fn(u32, &mut Node),
+    nodes: &mut Vec<Node>,
+) -> usize {
+    let id = nodes.len();
+    nodes.push(Node {
+        id,
+        ..Node::default()
+    });
+    match decode(node, num_features) {
+        DecodedNode::Leaf(value) => leaf(value, &mut nodes[id]),
+        DecodedNode::Split(s, left, right) => {
+            let left = export_node(left, num_features, leaf, nodes);
+            let right = export_node(right, num_features, leaf, nodes);
+            let node = &mut nodes[id];
+            node.feature = Some(s.feature);
+            node.threshold = Some(s.threshold);
+            node.left = Some(left);
+            node.right = Some(right);
+            node.missing_left = s.missing_left.then_some(true);
+        }
+    }
+    id
+}
+
+// Builds the structure of a tree of `num_features` features, `leaf` returns leaf values for leaf
+// nodes.
+fn import_tree(
+    tree: &Tree,
+    num_features: usize,
+    leaf: &mut dyn FnMut(&Node) -> Result<u32, String>,
+) -> Result<TreeNode, Error> {
+    let mut nodes = HashMap::with_capacity(tree.nodes.len());
+    for node in tree.nodes.iter() {
+        if nodes.insert(node.id, node).is_some() {
+            return Err(Error::Codec(format!("duplicate node id {}", node.id)));
+        }
+        if node.feature.is_some_and(|f| f as usize >= num_features) {
+            return Err(Error::Invalid(InvalidModel(format!(
+                "node {} has invalid feature",
+                node.id
+            ))));
+        }
+    }
+    let root = tree
+        .nodes
+        .first()
+        .ok_or_else(|| Error::Codec("tree has no nodes".to_string()))?;
+    let mut visited = HashSet::with_capacity(tree.nodes.len());
+    let context = Context {
+        nodes: &nodes,
+        num_features,
+    };
+    let root = import_node(root, 0, &context, &mut visited, leaf).map_err(Error::Codec)?;
+    if visited.len() != tree.nodes.len() {
+        return Err(Error::Codec("tree has unreachable nodes".to_string()));
+    }
+    Ok(root)
+}
+
+// Nodes of an imported tree by their ids and the number of features of its samples.
+struct Context<'a> {
+    nodes: &'a HashMap<usize, &'a Node>,
+    num_features: usize,
+}
+
+// Imports a node at `depth` splits from the root.
+fn import_node(
+    node: &Node,
+    depth: usize,
+    context: &Context,
+    visited: &mut HashSet<usize>,
+    leaf: &mut dyn FnMut(&Node) -> Result<u32, String>,
+) -> Result<TreeNode, String> {
+    if !visited.insert(node.id) {
+        return Err(format!("node {} has more than one parent", node.id));
+    }
+    if depth > MAX_IMPORT_DEPTH {
+        return Err(format!("tree is deeper than {MAX_IMPORT_DEPTH} splits"));
+    }
+    let child = |id: usize| {
+        context
+            .nodes
+            .get(&id)
+            .copied()
+            .ok_or_else(|| format!("node {} refers to unknown node {id}", node.id))
+    };
+    match (node.feature, node.threshold, node.left, node.right) {
+        (Some(feature), Some(threshold), Some(left), Some(right)) => {
+            let s = Split {
+                feature,
+                threshold,
+                missing_left: node.missing_left.unwrap_or(false),
+            };
+            let left = import_node(child(left)?, depth + 1, context, visited, leaf)?;
+            let right = import_node(child(right)?, depth + 1, context, visited, leaf)?;
+            split(s, left, right, context.num_features)
+        }
+        (None, None, None, None) => Ok(TreeNode::Leaf(
+            leaf(node).map_err(|e| format!("node {}: {e}", node.id))?,
+        )),
+        _ => Err(format!("node {} is neither a split nor a leaf", node.id)),
+    }
+}
+
+fn export_classifier<P: Predictor + Export, L: Label + Serialize>(
+    kind: ModelKind,
+    trees: &[ClassifierModel<P>],
+    classes_map: &ClassesMapping<L>,
+) -> Document {
+    let json_trees = trees
+        .iter()
+        .map(|tree| {
+            export_tree(
+                &tree.predictor().export(),
+                tree.num_features(),
+                &|v, node| node.proba = Some(tree.leaf(v).to_vec()),
+            )
+        })
+        .collect();
+    let mut document = document(kind, Task::CLASSIFIER, trees[0].num_features(), json_trees);
+    let labels = classes_map.get_decode_table().iter();
+    document.classes = Some(labels.map(|l| serde_json::to_value(l).unwrap()).collect());
+    document
+}
+
+// Imports classifier trees with probability rows deduplicated per tree.
+fn import_classifier<P: Predictor, L: Label + DeserializeOwned>(
+    document: &Document,
+    kind: ModelKind,
+) -> Result<(Vec<ClassifierModel<P>>, ClassesMapping<L>), Error> {
+    let trees = trees(document, kind, Task::CLASSIFIER)?;
+    let labels = document
+        .classes
+        .as_ref()
+        .ok_or_else(|| Error::Codec("classifier has no classes".to_string()))?
+        .iter()
+        .map(|label| serde_json::from_value(label.clone()))
+        .collect::<Result<Vec<L>, _>>()
+        .map_err(|e| Error::Codec(e.to_string()))?;
+    let classes_map = ClassesMapping::with_classes(&labels);
+    if classes_map.get_decode_table() != labels {
+        return Err(Error::Codec(
+            "classes are not distinct and ordered".to_string(),
+        ));
+    }
+    let num_classes = labels.len();
+    let models = trees
+        .iter()
+        .map(|tree| {
+            let mut proba = Vec::new();
+            let mut rows: HashMap<Vec<u32>, u32> = HashMap::new();
+            let root = import_tree(tree, document.num_features, &mut |node| {
+                let row = node.proba.as_ref().ok_or("leaf has no proba")?;
+                if row.len() != num_classes {
+                    return Err(format!("proba of {} classes", row.len()));
+                }
+                let key = row.iter().map(|p| p.to_bits()).collect();
+                let next = rows.len() as u32;
+                Ok(*rows.entry(key).or_insert_with(|| {
+                    proba.extend_from_slice(row);
+                    next
+                }))
+            })?;
+            let predictor = build(&root);
+            Ok(ClassifierModel::from_parts(
+                proba,
+                num_classes,
+                predictor,
+                document.num_features,
+            ))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok((models, classes_map))
+}
+
+fn export_regressor<P: Predictor + Export>(
+    kind: ModelKind,
+    trees: &[RegressorModel<P>],
+) -> Document {
+    let json_trees = trees
+        .iter()
+        .map(|tree| {
+            export_tree(
+                &tree.predictor().export(),
+                tree.num_features(),
+                &|v, node| node.value = Some(f32::from_bits(v)),
+            )
+        })
+        .collect();
+    document(kind, Task::REGRESSOR, trees[0].num_features(), json_trees)
+}
+
+fn import_regressor<P: Predictor>(
+    document: &Document,
+    kind: ModelKind,
+) -> Result<Vec<RegressorModel<P>>, Error> {
+    trees(document, kind, Task::REGRESSOR)?
+        .iter()
+        .map(|tree| {
+            let root = import_tree(tree, document.num_features, &mut |node| {
+                node.value
+                    .map(f32::to_bits)
+                    .ok_or("leaf has no value".to_string())
+            })?;
+            Ok(RegressorModel::from_parts(
+                build(&root),
+                document.num_features,
+            ))
+        })
+        .collect()
+}
+
+impl<P: Predictor, L> sealed::Sealed for dt::Classifier<P, L> {}
+
+impl<P, L> JsonModel for dt::Classifier<P, L>
+where
+    P: Predictor + Export + Validate,
+    L: Label + Serialize + DeserializeOwned,
+{
+    fn to_document(&self) -> Document {
+        let trees = std::slice::from_ref(self.model());
+        export_classifier(ModelKind::TREE, trees, self.classes_map())
+    }
+
+    fn from_document(document: &Document) -> Result<Self, Error> {
+        let (mut trees, classes_map) = import_classifier(document, ModelKind::TREE)?;
+        let model = Self::from_parts(trees.remove(0), classes_map);
+        model.validate().map_err(Error::Invalid)?;
+        Ok(model)
+    }
+}
+
+impl<P: Predictor, L> sealed::Sealed for rf::Classifier<P, L> {}
+
+impl<P, L> JsonModel for rf::Classifier<P, L>
+where
+    P: Predictor + Export + Validate + Send + Sync,
+    L: Label + Serialize + DeserializeOwned,
+{
+    fn to_document(&self) -> Document {
+        export_classifier(ModelKind::FOREST, self.trees(), self.classes_map())
+    }
+
+    fn from_document(document: &Document) -> Result<Self, Error> {
+        let (trees, classes_map) = import_classifier(document, ModelKind::FOREST)?;
+        let model = Self::from_parts(trees, classes_map);
+        model.validate().map_err(Error::Invalid)?;
+        Ok(model)
+    }
+}
+
+impl<P: Predictor> sealed::Sealed for dt::Regressor<P> {}
+
+impl<P: Predictor + Export + Validate> JsonModel for dt::Regressor<P> {
+    fn to_document(&self) -> Document {
+        export_regressor(ModelKind::TREE, std::slice::from_ref(self.model()))
+    }
+
+    fn from_document(document: &Document) -> Result<Self, Error> {
+        let mut trees = import_regressor(document, ModelKind::TREE)?;
+        let model = Self::from_parts(trees.remove(0));
+        model.validate().map_err(Error::Invalid)?;
+        Ok(model)
+    }
+}
+
+impl<P: Predictor> sealed::Sealed for rf::Regressor<P> {}
+
+impl<P: Predictor + Export + Validate + Send + Sync> JsonModel for rf::Regressor<P> {
+    fn to_document(&self) -> Document {
+        export_regressor(ModelKind::FOREST, self.trees())
+    }
+
+    fn from_document(document: &Document) -> Result<Self, Error> {
+        let model = Self::from_parts(import_regressor(document, ModelKind::FOREST)?);
+        model.validate().map_err(Error::Invalid)?;
+        Ok(model)
+    }
+}
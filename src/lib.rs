@@ -2,11 +2,13 @@
 //!
 //! # Classification
 //! Rafor provide a decision tree (DT) classifier [`dt::Classifier`] and a random forest (RF) classifier
-//! [`rf::Classifier`]. The class label is `i64` value. Classifiers use Gini index for
-//! evaluating the split impurity.
+//! [`rf::Classifier`]. The class label is `i64` value by default, but any type implementing
+//! [Label] (`Ord + Clone + Debug`), like `String`, `bool` or enum, can be used instead, e.g.
+//! `rf::Classifier<BlockTree, String>`. The labels are stored in the model and serialized with it.
+//! Classifiers use Gini index for evaluating the split impurity.
 //!
-//! Classifiers provide method `predict` for predicting a batch of samples, it returns `Vec<i64>`
-//! with predicted class labels. Method `predict_one` returns `i64` -- a predicted class for a
+//! Classifiers provide method `predict` for predicting a batch of samples, it returns `Vec<L>`
+//! with predicted class labels. Method `predict_one` returns `L` -- a predicted class for a
 //! single sample.
 //!
 //! To get probabilities distribution, there is a method `proba` which returns a `Vec<f32>` of
@@ -40,6 +42,10 @@
 //! best split value for decision tree node. Note that if no split value found, additional features
 //! will be considered until split is found or all features used.
 //!
+//! `strict_max_features: bool`, if set, exactly `max_features` features are considered for each
+//! node (as in scikit-learn), and the node becomes a leaf if none of them gives a split.
+//! Default: `false`.
+//!
 //! `seed: u64`, defines the seed for random number generator. For trees the random numbers are
 //! used for generating the feature sequence when finding split when `max_features` is less than the
 //! number of all features of training dataset. In RF, the datasets are generated using random sampling,
@@ -57,12 +63,42 @@
 //! ## Ensemble parameters
 //! `num_trees: usize` defines the number of individual trees in ensemble.
 //!
-//! `num_threads: usize` defines the number of CPU threads to use for training.
+//! `num_threads: usize` defines the number of CPU threads to use for training. Alternatively,
+//! trainers and forests provide `*_in` methods that run on a reusable [executor::Executor],
+//! e.g. a thread pool.
+//!
+//! `max_features_per_tree`: [MaxFeaturesPolicy], the number of features in a random subset that is
+//! drawn for each tree (random subspace method). Per split features are sampled from this subset
+//! according to `max_features`. By default all features are used.
+//!
+//! `sampling`: [SamplingPolicy], the strategy of drawing training samples for each tree. Stratified
+//! and balanced sampling are available for [`rf::Classifier`] only.
 //!
 //! # Model serialization and deserialization
 //! All models support [serde](https://docs.rs/serde/latest/serde/), so any lib that supports `serde`
 //! can be used for serialization and deserialization.
 //!
+//! Module [io] stores models in versioned files with a header describing the model (kind,
+//! tree layout, number of features) and a checksum. Files written by bincode before v0.4.0 are
+//! loaded as well. Models from untrusted sources can be checked with `validate()` before inference.
+//!
+//! Random forests can also be stored in [archive]s, which are used for prediction directly from a
+//! memory mapped file without deserialization.
+//!
+//! Module [json] exports models to documented human-readable JSON with explicit node lists and
+//! imports them back.
+//!
+//! Trees can be inspected as Graphviz DOT graphs with [dt::Classifier::to_dot] and as text rules with
+//! `to_text_rules()`; forests render individual trees with `tree_to_dot()` and `tree_to_text_rules()`.
+//!
+//! Existing scikit-learn trees and random forests are imported with [sklearn] from a JSON dump of
+//! their `tree_` arrays, so inference can be switched to rafor without retraining.
+//!
+//! Gradient boosted models trained by XGBoost or LightGBM are loaded into [boosting::Booster]
+//! and predicted with the same tree types.
+//!
+//! Trees and random forests are exported to ONNX with [onnx] for serving by ONNX runtimes.
+//!
 //! # Example
 //! ```
 //! use rafor::prelude::*; // Required for .with_option builders and .num_classes().
@@ -107,8 +143,8 @@
 //! 1. split threshold is `f32`;
 //! 2. feature index is `u16`, up to 2^16 = 65,536 features allowed;
 //! 3. in regression tasks, the target type is `f32`;
-//! 4. in classification tasks, the class is represented by `u32` (the input `i64` labels are mapped
-//! into `u32` internally, and restored during prediction);
+//! 4. in classification tasks, the class is represented by `u32` (the input labels are mapped
+//!    into `u32` internally, and restored during prediction);
 //! 5. child node index is `u32`, up to 2^32 = 4,294,967,296 nodes allowed.
 //!
 //! # Tree types
@@ -117,7 +153,7 @@
 //! the box: `BlockTree` and `CompactTree`.
 //!
 //! ## BlockTree
-//! This is an exceptionally fast predictor, but it requires about 4.5 times more RAM than 
+//! This is an exceptionally fast predictor, but it requires about 4.5 times more RAM than
 //! CompactTree in worst scenario. The tree is stored as an array of 64-byte blocks where each
 //! block holds a balanced tree of depth 2. This is a cache-friendly structure because prediction
 //! requires 3 times less jumps to traverse to leaf node. `BlockTree` is a default predictor.
@@ -128,18 +164,38 @@
 //! internal (parent) nodes. The leaf value (`f32` for regression trees, `u32` index pointing to
 //! the class probabilities for classification trees) is bit-packed into parent's `u32` child node
 //! index.
+//!
+//! ## QuickScorer
+//! Random forests of `CompactTree`s can be compiled with `quick_scorer()` into
+//! [`rf::QuickClassifier`] and [`rf::QuickRegressor`], see [quick_scorer] module.
+pub mod archive;
+pub mod boosting;
 mod classes_mapping;
+pub mod codegen;
 mod decision_tree;
 pub mod ensemble_classifier;
 mod ensemble_predictor;
 pub mod ensemble_regressor;
 mod ensemble_trainer;
+pub mod executor;
+pub mod io;
+pub mod json;
+pub mod onnx;
+pub mod quantized;
+pub mod quick_scorer;
+pub mod sklearn;
 pub mod trainer_builders;
 pub mod tree_classifier;
 pub mod tree_regressor;
+mod tree_render;
 use argminmax::ArgMinMax;
 use classes_mapping::{ClassDecode, ClassesMapping};
-pub use decision_tree::{BlockTree, CompactTree, Predictor, Trainable, Resolve, MaxFeaturesPolicy};
+pub use classes_mapping::{Label, UnknownLabel};
+pub use decision_tree::{
+    BlockTree, BlockTreeView, CompactTree, CompactTreeView, Export, InvalidModel,
+    MaxFeaturesPolicy, Predictor, Resolve, Trainable, TreeNode, Validate,
+};
+pub use ensemble_trainer::SamplingPolicy;
 
 type ClassTarget = u32;
 type FloatTarget = f32;
@@ -148,9 +204,10 @@ type SampleWeight = f32;
 type IndexRange = std::ops::Range<usize>;
 
 pub mod prelude {
-    pub use crate::classes_mapping::ClassDecode;
+    pub use crate::classes_mapping::{ClassDecode, Label};
     pub use crate::trainer_builders::{CommonTrainerBuilder, EnsembleTrainerBuilder};
     pub use crate::MaxFeaturesPolicy;
+    pub use crate::SamplingPolicy;
 }
 
 pub mod dt {
@@ -161,12 +218,16 @@ pub mod dt {
 
 pub mod rf {
     //! Random Forest implementation.
-    pub use crate::ensemble_classifier::Classifier;
-    pub use crate::ensemble_regressor::Regressor;
+    pub use crate::ensemble_classifier::{Classifier, Voting};
+    pub use crate::ensemble_predictor::Parallelism;
+    pub use crate::ensemble_regressor::{Averaging, Regressor};
+    pub use crate::ensemble_trainer::WarmStartError;
+    pub use crate::quantized::{QuantizedClassifier, QuantizedRegressor};
+    pub use crate::quick_scorer::{QuickClassifier, QuickRegressor};
 }
 
-fn classify(proba: &[f32], mapping: &ClassesMapping) -> Vec<i64> {
-    assert!(proba.len() % mapping.num_classes() == 0);
+fn classify<L: Label>(proba: &[f32], mapping: &ClassesMapping<L>) -> Vec<L> {
+    assert!(proba.len().is_multiple_of(mapping.num_classes()));
     proba
         .chunks(mapping.num_classes())
         .map(|c| mapping.decode(c.argmax()))
@@ -175,14 +236,16 @@ fn classify(proba: &[f32], mapping: &ClassesMapping) -> Vec<i64> {
 
 #[derive(Clone, PartialEq, Debug)]
 struct Trainset<'a, T> {
+    // Columns of the dataset, each column holds values of a single feature for all samples.
     pub data: Vec<f32>,
     pub targets: &'a [T],
+    // Number of features of a sample.
     pub num_features: usize,
 }
 
 impl<'a, T> Trainset<'a, T> {
     pub fn with_transposed(data: &[f32], targets: &'a [T]) -> Self {
-        assert!(data.len() % targets.len() == 0);
+        assert!(data.len().is_multiple_of(targets.len()));
         let num_features = data.len() / targets.len();
 
         let mut res: Vec<f32> = Vec::with_capacity(data.len());
@@ -204,6 +267,15 @@ impl<'a, T> Trainset<'a, T> {
 
 pub trait BatchPredictor {
     fn predict(&self, dataset: &[f32]) -> Vec<f32>;
+
+    /// Adds the predictions for a set of samples to `out` of length
+    /// number_of_samples * num_outputs().
+    fn accumulate(&self, dataset: &[f32], out: &mut [f32]);
+
+    fn num_features(&self) -> usize;
+
+    /// Returns the length of the prediction for a single sample.
+    fn num_outputs(&self) -> usize;
 }
 
 #[cfg(test)]
@@ -0,0 +1,433 @@
+//! Export of trees and random forests to ONNX models.
+//!
+//! A model is exported as a graph of a single `TreeEnsembleClassifier` or `TreeEnsembleRegressor`
+//! node of the `ai.onnx.ml` domain. The graph takes an input `X` of shape `[N, num_features]` of
+//! `f32` samples.
+//! * Classifiers output `label` of shape `[N]` with class labels, which are `int64` if all labels
+//!   are integers and strings otherwise, and `probabilities` of shape `[N, num_classes]`.
+//!   Probabilities of forests are averaged over trees like with [Voting::SOFT](crate::rf::Voting).
+//! * Regressors output `variable` of shape `[N, 1]`, predictions of forests are averaged over
+//!   trees like with [Averaging::MEAN](crate::rf::Averaging).
+//!
+//! Samples with `sample[feature] <= threshold` go to the true branch of a node, and missing values
+//! go to the false branch unless `nodes_missing_value_tracks_true` of the node is `1`.
+//! ```
+//! use rafor::{onnx::OnnxModel, rf};
+//! let dataset = [0.7, 0.0, 0.8, 1.0, 0.7, 0.0];
+//! let targets = [1, 5, 1];
+//! let predictor: rf::Classifier = rf::Classifier::trainer().train(&dataset, &targets);
+//! let bytes = predictor.to_onnx();
+//! // Write bytes to "model.onnx" to serve the model with an ONNX runtime.
+//! assert!(!bytes.is_empty());
+//! ```
+use crate::{
+    decision_tree::{decode, ClassifierModel, DecodedNode, RegressorModel},
+    dt, rf, ClassDecode, ClassesMapping, Export, Label, Predictor, TreeNode,
+};
+use serde::Serialize;
+
+const IR_VERSION: i64 = 8;
+const OPSET: i64 = 17;
+const ML_DOMAIN: &str = "ai.onnx.ml";
+const ML_OPSET: i64 = 3;
+
+// Wire types of protobuf fields.
+const VARINT: u64 = 0;
+const LENGTH_DELIMITED: u64 = 2;
+
+// Types of attributes, `AttributeProto.AttributeType`.
+const ATTRIBUTE_INT: i64 = 2;
+const ATTRIBUTE_STRING: i64 = 3;
+const ATTRIBUTE_FLOATS: i64 = 6;
+const ATTRIBUTE_INTS: i64 = 7;
+const ATTRIBUTE_STRINGS: i64 = 8;
+
+// Types of tensor elements, `TensorProto.DataType`.
+const TENSOR_FLOAT: i64 = 1;
+const TENSOR_INT64: i64 = 7;
+const TENSOR_STRING: i64 = 8;
+
+// Encoder of a protobuf message.
+#[derive(Default)]
+struct Message(Vec<u8>);
+
+impl Message {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    fn key(&mut self, field: u64, wire_type: u64) {
+        self.varint(field << 3 | wire_type);
+    }
+
+    fn int(&mut self, field: u64, value: i64) -> &mut Self {
+        self.key(field, VARINT);
+        self.varint(value as u64);
+        self
+    }
+
+    fn bytes(&mut self, field: u64, value: &[u8]) -> &mut Self {
+        self.key(field, LENGTH_DELIMITED);
+        self.varint(value.len() as u64);
+        self.0.extend_from_slice(value);
+        self
+    }
+
+    fn string(&mut self, field: u64, value: &str) -> &mut Self {
+        self.bytes(field, value.as_bytes())
+    }
+
+    fn message(&mut self, field: u64, value: &Message) -> &mut Self {
+        self.bytes(field, &value.0)
+    }
+
+    fn packed_ints(&mut self, field: u64, values: &[i64]) -> &mut Self {
+        let mut packed = Message::default();
+        values.iter().for_each(|&v| packed.varint(v as u64));
+        self.message(field, &packed)
+    }
+
+    fn packed_floats(&mut self, field: u64, values: &[f32]) -> &mut Self {
+        let packed: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        self.bytes(field, &packed)
+    }
+}
+
+// Values of an attribute of a node.
+enum Attribute<'a> {
+    Int(i64),
+    String(&'a str),
+    Floats(&'a [f32]),
+    Ints(&'a [i64]),
+    Strings(&'a [String]),
+}
+
+// Returns `AttributeProto` of an attribute.
+fn attribute(name: &str, value: Attribute) -> Message {
+    let mut m = Message::default();
+    m.string(1, name);
+    match value {
+        Attribute::Int(v) => m.int(3, v).int(20, ATTRIBUTE_INT),
+        Attribute::String(v) => m.string(4, v).int(20, ATTRIBUTE_STRING),
+        Attribute::Floats(v) => m.packed_floats(7, v).int(20, ATTRIBUTE_FLOATS),
+        Attribute::Ints(v) => m.packed_ints(8, v).int(20, ATTRIBUTE_INTS),
+        Attribute::Strings(v) => {
+            v.iter().for_each(|s| {
+                m.string(9, s);
+            });
+            m.int(20, ATTRIBUTE_STRINGS)
+        }
+    };
+    m
+}
+
+// Returns `ValueInfoProto` of a tensor, `None` dimensions are the number of samples `N`.
+fn tensor(name: &str, elem_type: i64, dims: &[Option<i64>]) -> Message {
+    let mut shape = Message::default();
+    for dim in dims {
+        let mut d = Message::default();
+        match dim {
+            Some(size) => d.int(1, *size),
+            None => d.string(2, "N"),
+        };
+        shape.message(1, &d);
+    }
+    let mut tensor_type = Message::default();
+    tensor_type.int(1, elem_type).message(2, &shape);
+    let mut type_proto = Message::default();
+    type_proto.message(1, &tensor_type);
+    let mut m = Message::default();
+    m.string(1, name).message(2, &type_proto);
+    m
+}
+
+// Nodes of trees of `num_features` features in the layout of `nodes_*` attributes.
+#[derive(Default)]
+struct Nodes {
+    num_features: usize,
+    tree_ids: Vec<i64>,
+    node_ids: Vec<i64>,
+    feature_ids: Vec<i64>,
+    values: Vec<f32>,
+    modes: Vec<String>,
+    true_ids: Vec<i64>,
+    false_ids: Vec<i64>,
+    missing_true: Vec<i64>,
+}
+
+impl Nodes {
+    // Appends nodes of a tree in preorder, `leaf(node_id, value)` is called for each leaf.
+    fn add_tree(&mut self, tree_id: i64, root: &TreeNode, leaf: &mut dyn FnMut(i64, u32)) {
+        let first = self.node_ids.len();
+        self.add_node(tree_id, root, first, leaf);
+    }
+
+    fn add_node(
+        &mut self,
+        tree_id: i64,
+        node: &TreeNode,
+        first: usize,
+        leaf: &mut dyn FnMut(i64, u32),
+    ) -> i64 {
+        let index = self.node_ids.len();
+        let id = (index - first) as i64;
+        self.tree_ids.push(tree_id);
+        self.node_ids.push(id);
+        self.feature_ids.push(0);
+        self.values.push(0.);
+        self.modes.push("LEAF".to_string());
+        self.true_ids.push(0);
+        self.false_ids.push(0);
+        self.missing_true.push(0);
+        match decode(node, self.num_features) {
+            DecodedNode::Leaf(value) => leaf(id, value),
+            DecodedNode::Split(split, left, right) => {
+                self.feature_ids[index] = split.feature as i64;
+                self.values[index] = split.threshold;
+                self.missing_true[index] = split.missing_left as i64;
+                self.modes[index] = "BRANCH_LEQ".to_string();
+                self.true_ids[index] = self.add_node(tree_id, left, first, leaf);
+                self.false_ids[index] = self.add_node(tree_id, right, first, leaf);
+            }
+        }
+        id
+    }
+
+    fn attributes(&self) -> Vec<Message> {
+        vec![
+            attribute("nodes_treeids", Attribute::Ints(&self.tree_ids)),
+            attribute("nodes_nodeids", Attribute::Ints(&self.node_ids)),
+            attribute("nodes_featureids", Attribute::Ints(&self.feature_ids)),
+            attribute("nodes_values", Attribute::Floats(&self.values)),
+            attribute("nodes_modes", Attribute::Strings(&self.modes)),
+            attribute("nodes_truenodeids", Attribute::Ints(&self.true_ids)),
+            attribute("nodes_falsenodeids", Attribute::Ints(&self.false_ids)),
+            attribute(
+                "nodes_missing_value_tracks_true",
+                Attribute::Ints(&self.missing_true),
+            ),
+            attribute("post_transform", Attribute::String("NONE")),
+        ]
+    }
+}
+
+// Returns `ModelProto` of a graph with a single node, `outputs` are names and types of outputs.
+fn model(
+    op_type: &str,
+    attributes: Vec<Message>,
+    num_features: usize,
+    outputs: &[(&str, Message)],
+) -> Vec<u8> {
+    let mut node = Message::default();
+    node.string(1, "X");
+    for (name, _) in outputs.iter() {
+        node.string(2, name);
+    }
+    node.string(3, op_type)
+        .string(4, op_type)
+        .string(7, ML_DOMAIN);
+    attributes.iter().for_each(|a| {
+        node.message(5, a);
+    });
+
+    let mut graph = Message::default();
+    graph.message(1, &node).string(2, "rafor");
+    graph.message(
+        11,
+        &tensor("X", TENSOR_FLOAT, &[None, Some(num_features as i64)]),
+    );
+    outputs.iter().for_each(|(_, o)| {
+        graph.message(12, o);
+    });
+
+    let mut model = Message::default();
+    model
+        .int(1, IR_VERSION)
+        .string(2, "rafor")
+        .string(3, env!("CARGO_PKG_VERSION"));
+    for (domain, version) in [("", OPSET), (ML_DOMAIN, ML_OPSET)] {
+        let mut opset = Message::default();
+        opset.string(1, domain).int(2, version);
+        model.message(8, &opset);
+    }
+    model.message(7, &graph);
+    model.0
+}
+
+fn classifier<P: Predictor + Export, L: Label + Serialize>(
+    trees: &[ClassifierModel<P>],
+    classes_map: &ClassesMapping<L>,
+) -> Vec<u8> {
+    let num_classes = classes_map.num_classes();
+    let scale = 1. / trees.len() as f32;
+    let mut nodes = Nodes {
+        num_features: trees[0].num_features(),
+        ..Nodes::default()
+    };
+    let (mut class_tree_ids, mut class_node_ids) = (Vec::new(), Vec::new());
+    let (mut class_ids, mut class_weights) = (Vec::new(), Vec::new());
+    for (tree_id, tree) in trees.iter().enumerate() {
+        nodes.add_tree(
+            tree_id as i64,
+            &tree.predictor().export(),
+            &mut |node_id, value| {
+                for (class, p) in tree.leaf(value).iter().enumerate() {
+                    class_tree_ids.push(tree_id as i64);
+                    class_node_ids.push(node_id);
+                    class_ids.push(class as i64);
+                    class_weights.push(p * scale);
+                }
+            },
+        );
+    }
+    let mut attributes = nodes.attributes();
+    attributes.push(attribute("class_treeids", Attribute::Ints(&class_tree_ids)));
+    attributes.push(attribute("class_nodeids", Attribute::Ints(&class_node_ids)));
+    attributes.push(attribute("class_ids", Attribute::Ints(&class_ids)));
+    attributes.push(attribute(
+        "class_weights",
+        Attribute::Floats(&class_weights),
+    ));
+
+    let labels: Vec<serde_json::Value> = classes_map
+        .get_decode_table()
+        .iter()
+        .map(|l| serde_json::to_value(l).unwrap())
+        .collect();
+    let ints: Option<Vec<i64>> = labels.iter().map(serde_json::Value::as_i64).collect();
+    let label_type = match ints {
+        Some(ints) => {
+            attributes.push(attribute("classlabels_int64s", Attribute::Ints(&ints)));
+            TENSOR_INT64
+        }
+        None => {
+            let strings: Vec<String> = labels
+                .iter()
+                .map(|l| match l {
+                    serde_json::Value::String(s) => s.clone(),
+                    l => l.to_string(),
+                })
+                .collect();
+            attributes.push(attribute(
+                "classlabels_strings",
+                Attribute::Strings(&strings),
+            ));
+            TENSOR_STRING
+        }
+    };
+    let probabilities = &[None, Some(num_classes as i64)];
+    let outputs = [
+        ("label", tensor("label", label_type, &[None])),
+        (
+            "probabilities",
+            tensor("probabilities", TENSOR_FLOAT, probabilities),
+        ),
+    ];
+    model(
+        "TreeEnsembleClassifier",
+        attributes,
+        trees[0].num_features(),
+        &outputs,
+    )
+}
+
+fn regressor<P: Predictor + Export>(trees: &[RegressorModel<P>]) -> Vec<u8> {
+    let mut nodes = Nodes {
+        num_features: trees[0].num_features(),
+        ..Nodes::default()
+    };
+    let (mut target_tree_ids, mut target_node_ids) = (Vec::new(), Vec::new());
+    let mut target_weights = Vec::new();
+    for (tree_id, tree) in trees.iter().enumerate() {
+        nodes.add_tree(
+            tree_id as i64,
+            &tree.predictor().export(),
+            &mut |node_id, value| {
+                target_tree_ids.push(tree_id as i64);
+                target_node_ids.push(node_id);
+                target_weights.push(f32::from_bits(value));
+            },
+        );
+    }
+    let target_ids = vec![0; target_weights.len()];
+    let mut attributes = nodes.attributes();
+    attributes.push(attribute("n_targets", Attribute::Int(1)));
+    attributes.push(attribute(
+        "aggregate_function",
+        Attribute::String("AVERAGE"),
+    ));
+    attributes.push(attribute("base_values", Attribute::Floats(&[0.])));
+    attributes.push(attribute(
+        "target_treeids",
+        Attribute::Ints(&target_tree_ids),
+    ));
+    attributes.push(attribute(
+        "target_nodeids",
+        Attribute::Ints(&target_node_ids),
+    ));
+    attributes.push(attribute("target_ids", Attribute::Ints(&target_ids)));
+    attributes.push(attribute(
+        "target_weights",
+        Attribute::Floats(&target_weights),
+    ));
+    let outputs = [(
+        "variable",
+        tensor("variable", TENSOR_FLOAT, &[None, Some(1)]),
+    )];
+    model(
+        "TreeEnsembleRegressor",
+        attributes,
+        trees[0].num_features(),
+        &outputs,
+    )
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A model that can be exported to ONNX: trees and random forests.
+pub trait OnnxModel: sealed::Sealed {
+    /// Returns the model encoded as an ONNX `ModelProto`.
+    fn to_onnx(&self) -> Vec<u8>;
+}
+
+impl<P: Predictor, L> sealed::Sealed for dt::Classifier<P, L> {}
+
+impl<P: Predictor + Export, L: Label + Serialize> OnnxModel for dt::Classifier<P, L> {
+    fn to_onnx(&self) -> Vec<u8> {
+        classifier(std::slice::from_ref(self.model()), self.classes_map())
+    }
+}
+
+impl<P: Predictor, L> sealed::Sealed for rf::Classifier<P, L> {}
+
+impl<P, L> OnnxModel for rf::Classifier<P, L>
+where
+    P: Predictor + Export + Send + Sync,
+    L: Label + Serialize,
+{
+    fn to_onnx(&self) -> Vec<u8> {
+        classifier(self.trees(), self.classes_map())
+    }
+}
+
+impl<P: Predictor> sealed::Sealed for dt::Regressor<P> {}
+
+impl<P: Predictor + Export> OnnxModel for dt::Regressor<P> {
+    fn to_onnx(&self) -> Vec<u8> {
+        regressor(std::slice::from_ref(self.model()))
+    }
+}
+
+impl<P: Predictor> sealed::Sealed for rf::Regressor<P> {}
+
+impl<P: Predictor + Export + Send + Sync> OnnxModel for rf::Regressor<P> {
+    fn to_onnx(&self) -> Vec<u8> {
+        regressor(self.trees())
+    }
+}
@@ -0,0 +1,305 @@
+//! Quantized random forests for compact model storage.
+//!
+//! A forest is quantized with [Quantization] settings:
+//! * thresholds of each feature are replaced by indexes in a per-feature codebook of at most 256
+//!   (`Precision::U8`) or 65,536 (`Precision::U16`) thresholds. Thresholds are exact if a feature
+//!   has no more distinct thresholds than the codebook size, otherwise the codebook holds their
+//!   quantiles;
+//! * class probabilities are stored as fixed point numbers in `[0, 1]`, regression values as
+//!   fixed point numbers between the minimal and the maximal leaf value.
+//!
+//! Serialized quantized trees store neither child indexes nor leaf indexes. Inference quantizes
+//! features of a sample once and compares integer codes. Deserialization rejects malformed trees,
+//! `validate` checks the rest of a deserialized model.
+//! ```
+//! use rafor::{quantized::Quantization, rf};
+//! let dataset = [0.7, 0.0, 0.8, 1.0, 0.7, 0.0];
+//! let targets = [1, 5, 1];
+//! let forest: rf::Classifier = rf::Classifier::trainer().train(&dataset, &targets);
+//! let predictor = forest.quantize(&Quantization::default());
+//! assert_eq!(&predictor.predict_batch(&dataset), &[1, 5, 1]);
+//! ```
+use crate::{
+    decision_tree::{ClassifierModel, Codebooks, Codes, Predictor, QuantizedTree, RegressorModel},
+    ClassDecode, ClassesMapping, Export, FloatTarget, InvalidModel, Label,
+};
+use argminmax::ArgMinMax;
+use serde::{Deserialize, Serialize};
+
+pub use crate::decision_tree::Precision;
+
+/// Quantization settings. Default: `U16` thresholds and `U8` leaves.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Quantization {
+    /// Precision of threshold indexes.
+    pub thresholds: Precision,
+    /// Precision of class probabilities or regression values.
+    pub leaves: Precision,
+}
+
+impl Default for Quantization {
+    fn default() -> Self {
+        Self {
+            thresholds: Precision::U16,
+            leaves: Precision::U8,
+        }
+    }
+}
+
+/// A quantized random forest classifier. Created by
+/// [`rf::Classifier::quantize`](crate::rf::Classifier::quantize).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QuantizedClassifier<L = i64> {
+    codebooks: Codebooks,
+    trees: Vec<QuantizedTree>,
+    // Probabilities of leaves of each tree, `num_classes` codes per leaf.
+    proba: Vec<Codes>,
+    // Probability of code `c` is `c / max_code`.
+    max_code: u32,
+    classes_map: ClassesMapping<L>,
+}
+
+/// A quantized random forest regressor. Created by
+/// [`rf::Regressor::quantize`](crate::rf::Regressor::quantize).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QuantizedRegressor {
+    codebooks: Codebooks,
+    trees: Vec<QuantizedTree>,
+    // Values of leaves of each tree, the value of code `c` is `min + c * step`.
+    values: Vec<Codes>,
+    min: f32,
+    step: f32,
+}
+
+impl<L: Label> QuantizedClassifier<L> {
+    pub(crate) fn new<P: Predictor + Export>(
+        ensemble: &[ClassifierModel<P>],
+        classes_map: ClassesMapping<L>,
+        quantization: &Quantization,
+    ) -> Self {
+        let nodes: Vec<_> = ensemble.iter().map(|t| t.predictor().export()).collect();
+        let codebooks = Codebooks::new(
+            nodes.iter(),
+            ensemble[0].num_features(),
+            quantization.thresholds,
+        );
+        let max_code = quantization.leaves.max_code();
+        let mut trees = Vec::with_capacity(ensemble.len());
+        let mut proba = Vec::with_capacity(ensemble.len());
+        for (node, model) in nodes.iter().zip(ensemble.iter()) {
+            let (tree, leaves) = QuantizedTree::new(node, &codebooks);
+            let rows = leaves.iter().flat_map(|&leaf| model.leaf(leaf).iter());
+            proba.push(Codes::new(
+                quantization.leaves,
+                rows.map(|p| (p * max_code as f32).round() as u32),
+            ));
+            trees.push(tree);
+        }
+        Self {
+            codebooks,
+            trees,
+            proba,
+            max_code,
+            classes_map,
+        }
+    }
+
+    /// Predicts classes for a set of samples.
+    pub fn predict_batch(&self, dataset: &[f32]) -> Vec<L> {
+        crate::classify(&self.proba(dataset), &self.classes_map)
+    }
+
+    /// Predicts class for a single sample given by a slice of length num_features().
+    pub fn predict_one(&self, sample: &[f32]) -> L {
+        self.classes_map.decode(self.proba(sample).argmax())
+    }
+
+    /// Predicts classes probabilities for each sample. The length of result vector is
+    /// number_of_samples * num_classes().
+    pub fn proba(&self, dataset: &[f32]) -> Vec<f32> {
+        let num_features = self.num_features();
+        let num_classes = self.num_classes();
+        assert!(dataset.len().is_multiple_of(num_features));
+        let mut result = vec![0.; dataset.len() / num_features * num_classes];
+        let mut quantized = vec![0; 2 * num_features];
+        let scale = (self.max_code as usize * self.trees.len()) as f32;
+        let mut sums = vec![0_u32; num_classes];
+        for (out, sample) in result
+            .chunks_exact_mut(num_classes)
+            .zip(dataset.chunks_exact(num_features))
+        {
+            self.codebooks.quantize(sample, &mut quantized);
+            sums.fill(0);
+            for (tree, proba) in self.trees.iter().zip(self.proba.iter()) {
+                let row = tree.resolve(&quantized) * num_classes;
+                for (i, s) in sums.iter_mut().enumerate() {
+                    *s += proba.get(row + i);
+                }
+            }
+            for (o, s) in out.iter_mut().zip(sums.iter()) {
+                *o = *s as f32 / scale;
+            }
+        }
+        result
+    }
+
+    /// Returns a number of features.
+    pub fn num_features(&self) -> usize {
+        self.codebooks.num_features()
+    }
+
+    /// Returns a number of trees.
+    pub fn num_trees(&self) -> usize {
+        self.trees.len()
+    }
+
+    /// Checks that a deserialized model is consistent: split features and thresholds are indexes
+    /// of codebooks, each leaf has a probability code of each class not above the maximal code,
+    /// and class labels are distinct and ordered.
+    pub fn validate(&self) -> Result<(), InvalidModel> {
+        let num_classes = self.num_classes();
+        let max_code = self.max_code;
+        validate_trees(
+            &self.codebooks,
+            &self.trees,
+            &self.proba,
+            num_classes,
+            |proba| {
+                if max_code == 0 || (0..proba.len()).any(|i| proba.get(i) > max_code) {
+                    return Err("probability codes are out of range".to_string());
+                }
+                Ok(())
+            },
+        )?;
+        self.classes_map.validate(num_classes)
+    }
+}
+
+// Checks that `trees` use `codebooks` and each tree has `codes_per_leaf` leaf codes, which are
+// checked by `check_codes`.
+fn validate_trees(
+    codebooks: &Codebooks,
+    trees: &[QuantizedTree],
+    leaves: &[Codes],
+    codes_per_leaf: usize,
+    check_codes: impl Fn(&Codes) -> Result<(), String>,
+) -> Result<(), InvalidModel> {
+    codebooks.validate().map_err(InvalidModel)?;
+    if trees.is_empty() {
+        return Err(InvalidModel("forest has no trees".to_string()));
+    }
+    if trees.len() != leaves.len() {
+        return Err(InvalidModel("leaves of trees are not given".to_string()));
+    }
+    for (i, (tree, codes)) in trees.iter().zip(leaves.iter()).enumerate() {
+        let error = |e: String| InvalidModel(format!("tree {i}: {e}"));
+        tree.validate(codebooks).map_err(error)?;
+        if codes.len() != tree.num_leaves() * codes_per_leaf {
+            return Err(error(format!(
+                "{} leaf codes for {} leaves",
+                codes.len(),
+                tree.num_leaves()
+            )));
+        }
+        check_codes(codes).map_err(error)?;
+    }
+    Ok(())
+}
+
+impl<L: Label> ClassDecode for QuantizedClassifier<L> {
+    type Label = L;
+
+    fn get_decode_table(&self) -> &[L] {
+        self.classes_map.get_decode_table()
+    }
+}
+
+impl QuantizedRegressor {
+    pub(crate) fn new<P: Predictor + Export>(
+        ensemble: &[RegressorModel<P>],
+        quantization: &Quantization,
+    ) -> Self {
+        let nodes: Vec<_> = ensemble.iter().map(|t| t.predictor().export()).collect();
+        let codebooks = Codebooks::new(
+            nodes.iter(),
+            ensemble[0].num_features(),
+            quantization.thresholds,
+        );
+        let (trees, leaves): (Vec<_>, Vec<_>) = nodes
+            .iter()
+            .map(|node| QuantizedTree::new(node, &codebooks))
+            .unzip();
+
+        let all_values = leaves.iter().flatten().map(|&v| f32::from_bits(v));
+        let min = all_values.clone().fold(f32::INFINITY, f32::min);
+        let max = all_values.fold(f32::NEG_INFINITY, f32::max);
+        let max_code = quantization.leaves.max_code();
+        let step = if max > min {
+            (max - min) / max_code as f32
+        } else {
+            1.0
+        };
+        let values = leaves
+            .iter()
+            .map(|leaves| {
+                let codes = leaves
+                    .iter()
+                    .map(|&v| ((f32::from_bits(v) - min) / step).round() as u32);
+                Codes::new(quantization.leaves, codes)
+            })
+            .collect();
+
+        Self {
+            codebooks,
+            trees,
+            values,
+            min,
+            step,
+        }
+    }
+
+    /// Predicts regression values for a set of samples.
+    pub fn predict_batch(&self, dataset: &[f32]) -> Vec<FloatTarget> {
+        let num_features = self.num_features();
+        assert!(dataset.len().is_multiple_of(num_features));
+        let mut quantized = vec![0; 2 * num_features];
+        dataset
+            .chunks_exact(num_features)
+            .map(|sample| {
+                self.codebooks.quantize(sample, &mut quantized);
+                let sum: u64 = self
+                    .trees
+                    .iter()
+                    .zip(self.values.iter())
+                    .map(|(tree, values)| values.get(tree.resolve(&quantized)) as u64)
+                    .sum();
+                self.min + sum as f32 * self.step / self.trees.len() as f32
+            })
+            .collect()
+    }
+
+    /// Predicts regression value for a single sample given by a slice of length num_features().
+    pub fn predict_one(&self, sample: &[f32]) -> FloatTarget {
+        self.predict_batch(sample)[0]
+    }
+
+    /// Returns a number of features.
+    pub fn num_features(&self) -> usize {
+        self.codebooks.num_features()
+    }
+
+    /// Returns a number of trees.
+    pub fn num_trees(&self) -> usize {
+        self.trees.len()
+    }
+
+    /// Checks that a deserialized model is consistent: split features and thresholds are indexes
+    /// of codebooks, each leaf has a value code, and the values of codes are finite.
+    pub fn validate(&self) -> Result<(), InvalidModel> {
+        validate_trees(&self.codebooks, &self.trees, &self.values, 1, |_| Ok(()))?;
+        if !self.min.is_finite() || !self.step.is_finite() {
+            return Err(InvalidModel("leaf values are not finite".to_string()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+//! QuickScorer inference backend for random forests.
+//!
+//! A forest of [CompactTree]s is compiled into per-feature lists of thresholds sorted in ascending
+//! order, each threshold holding a bitvector mask of leaves of a tree. Prediction of a sample
+//! applies masks of the thresholds below the feature value with bitwise AND, and the exit leaf of
+//! each tree is the lowest set bit of its bitvector. No trees are traversed, but the masks applied
+//! to a sample grow with the number of splits rather than the depth of trees: `cargo bench --bench
+//! resolve` measured 50 trees of depth 4 predicted 0.5 times as fast as by traversal of
+//! [CompactTree]s, and of depth 8 0.14 times as fast. Bitvectors are kept per thread, so prediction
+//! allocates memory only for larger models than earlier ones on the thread.
+//! ```
+//! use rafor::{rf, CompactTree};
+//! let dataset = [0.7, 0.0, 0.8, 1.0, 0.7, 0.0];
+//! let targets = [1, 5, 1];
+//! let forest = rf::Classifier::<CompactTree>::trainer().train(&dataset, &targets);
+//! let predictor = forest.quick_scorer();
+//! assert_eq!(&predictor.predict_batch(&dataset), &[1, 5, 1]);
+//! ```
+use crate::{
+    decision_tree::{ClassifierModel, QuickScorer, RegressorModel},
+    ClassDecode, ClassesMapping, CompactTree, FloatTarget, Label,
+};
+use argminmax::ArgMinMax;
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, collections::HashMap};
+
+thread_local! {
+    // Probabilities of a sample predicted by `QuickClassifier::predict_one` on this thread.
+    static PROBA: RefCell<Vec<f32>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A random forest classifier compiled into QuickScorer representation. Created by
+/// [`rf::Classifier::quick_scorer`](crate::rf::Classifier::quick_scorer).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QuickClassifier<L = i64> {
+    scorer: QuickScorer,
+    // Distinct probability vectors of leaves of all trees, leaf values of the scorer are rows.
+    proba: Vec<f32>,
+    classes_map: ClassesMapping<L>,
+}
+
+/// A random forest regressor compiled into QuickScorer representation. Created by
+/// [`rf::Regressor::quick_scorer`](crate::rf::Regressor::quick_scorer).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QuickRegressor {
+    scorer: QuickScorer,
+}
+
+impl<L: Label> QuickClassifier<L> {
+    pub(crate) fn new(
+        ensemble: &[ClassifierModel<CompactTree>],
+        classes_map: ClassesMapping<L>,
+    ) -> Self {
+        let num_classes = classes_map.num_classes();
+        let mut proba = Vec::new();
+        let mut rows: HashMap<Vec<u32>, u32> = HashMap::new();
+        let scorer = QuickScorer::new(
+            ensemble.iter().map(|t| t.predictor()),
+            ensemble[0].num_features(),
+            |t, v| {
+                let row = ensemble[t].leaf(v);
+                let key = row.iter().map(|p| p.to_bits()).collect();
+                *rows.entry(key).or_insert_with(|| {
+                    proba.extend_from_slice(row);
+                    (proba.len() / num_classes - 1) as u32
+                })
+            },
+        );
+        Self {
+            scorer,
+            proba,
+            classes_map,
+        }
+    }
+
+    /// Predicts classes for a set of samples.
+    pub fn predict_batch(&self, dataset: &[f32]) -> Vec<L> {
+        crate::classify(&self.proba(dataset), &self.classes_map)
+    }
+
+    /// Predicts class for a single sample given by a slice of length num_features().
+    pub fn predict_one(&self, sample: &[f32]) -> L {
+        PROBA.with_borrow_mut(|proba| {
+            proba.resize(self.num_classes(), 0.);
+            self.proba_into(sample, proba);
+            self.classes_map.decode(proba.as_slice().argmax())
+        })
+    }
+
+    /// Predicts classes probabilities for each sample. The length of result vector is
+    /// number_of_samples * num_classes().
+    pub fn proba(&self, dataset: &[f32]) -> Vec<f32> {
+        let mut result = vec![0.; dataset.len() / self.num_features() * self.num_classes()];
+        self.proba_into(dataset, &mut result);
+        result
+    }
+
+    /// Writes classes probabilities for each sample to `out` of length
+    /// number_of_samples * num_classes().
+    pub fn proba_into(&self, dataset: &[f32], out: &mut [f32]) {
+        let num_features = self.num_features();
+        let num_classes = self.num_classes();
+        assert!(dataset.len().is_multiple_of(num_features));
+        assert!(out.len() == dataset.len() / num_features * num_classes);
+        self.scorer.with_bits(|bits| {
+            for (o, sample) in out
+                .chunks_exact_mut(num_classes)
+                .zip(dataset.chunks_exact(num_features))
+            {
+                o.fill(0.);
+                self.scorer.score(sample, bits, |_, leaf| {
+                    let row = leaf as usize * num_classes;
+                    for (x, p) in o.iter_mut().zip(&self.proba[row..row + num_classes]) {
+                        *x += p;
+                    }
+                });
+                for x in o.iter_mut() {
+                    *x /= self.num_trees() as f32;
+                }
+            }
+        });
+    }
+
+    /// Returns a number of features.
+    pub fn num_features(&self) -> usize {
+        self.scorer.num_features()
+    }
+
+    /// Returns a number of trees.
+    pub fn num_trees(&self) -> usize {
+        self.scorer.num_trees()
+    }
+}
+
+impl<L: Label> ClassDecode for QuickClassifier<L> {
+    type Label = L;
+
+    fn get_decode_table(&self) -> &[L] {
+        self.classes_map.get_decode_table()
+    }
+}
+
+impl QuickRegressor {
+    pub(crate) fn new(ensemble: &[RegressorModel<CompactTree>]) -> Self {
+        Self {
+            scorer: QuickScorer::new(
+                ensemble.iter().map(|t| t.predictor()),
+                ensemble[0].num_features(),
+                |_, v| v,
+            ),
+        }
+    }
+
+    /// Predicts regression values for a set of samples.
+    pub fn predict_batch(&self, dataset: &[f32]) -> Vec<FloatTarget> {
+        let mut result = vec![0.; dataset.len() / self.num_features()];
+        self.predict_into(dataset, &mut result);
+        result
+    }
+
+    /// Predicts regression value for a single sample given by a slice of length num_features().
+    pub fn predict_one(&self, sample: &[f32]) -> FloatTarget {
+        let mut out = [0.];
+        self.predict_into(sample, &mut out);
+        out[0]
+    }
+
+    /// Writes regression values for a set of samples to `out` of length number_of_samples.
+    pub fn predict_into(&self, dataset: &[f32], out: &mut [FloatTarget]) {
+        let num_features = self.num_features();
+        assert!(dataset.len() == out.len() * num_features);
+        self.scorer.with_bits(|bits| {
+            for (o, sample) in out.iter_mut().zip(dataset.chunks_exact(num_features)) {
+                let mut sum = 0.;
+                self.scorer
+                    .score(sample, bits, |_, leaf| sum += f32::from_bits(leaf));
+                *o = sum / self.num_trees() as f32;
+            }
+        });
+    }
+
+    /// Returns a number of features.
+    pub fn num_features(&self) -> usize {
+        self.scorer.num_features()
+    }
+
+    /// Returns a number of trees.
+    pub fn num_trees(&self) -> usize {
+        self.scorer.num_trees()
+    }
+}
@@ -0,0 +1,277 @@
+//! Import of scikit-learn decision trees and random forests.
+//!
+//! `DecisionTreeClassifier`, `DecisionTreeRegressor`, `RandomForestClassifier` and
+//! `RandomForestRegressor` models are imported from a JSON [Dump] of their `tree_` arrays, which
+//! is written by the following Python function:
+//! ```python
+//! import json
+//!
+//! def dump(model, path):
+//!     trees = getattr(model, "estimators_", [model])
+//!     arrays = ("children_left", "children_right", "feature", "threshold", "value",
+//!               "missing_go_to_left")
+//!     document = {
+//!         "estimator": type(model).__name__,
+//!         "n_features_in": int(model.n_features_in_),
+//!         "trees": [{a: getattr(t.tree_, a).tolist() for a in arrays if hasattr(t.tree_, a)}
+//!                   for t in trees],
+//!     }
+//!     if hasattr(model, "classes_"):
+//!         document["classes"] = model.classes_.tolist()
+//!     with open(path, "w") as f:
+//!         json.dump(document, f)
+//! ```
+//! * `estimator` is the class name of the model.
+//! * `classes` holds class labels of classifiers in the order of columns of `value`. Labels are
+//!   mapped into the classes of the imported model, so any order is accepted.
+//! * A node `i` is a leaf if `children_left[i]` is `-1`. Otherwise samples with
+//!   `sample[feature[i]] <= threshold[i]` go to node `children_left[i]`, and the rest to node
+//!   `children_right[i]`.
+//! * `value[i][0]` holds class weights or fractions of a node for classifiers, which are
+//!   normalized into probabilities, and a single predicted value for regressors. Only models with
+//!   one output are supported.
+//! * `missing_go_to_left[i]`, written by scikit-learn 1.3 and later, is `1` if missing values go
+//!   to the left child of a split. Without it missing values (NaNs) go to the right child.
+//!
+//! scikit-learn compares `f32` features with `f64` thresholds, so a threshold is imported as the
+//! largest `f32` not above it, which keeps all decisions for `f32` samples.
+//! ```
+//! use rafor::{dt, sklearn, BlockTree};
+//! let dump = r#"{"estimator": "DecisionTreeClassifier", "n_features_in": 1, "classes": [0, 1],
+//!     "trees": [{"children_left": [1, -1, -1], "children_right": [2, -1, -1],
+//!     "feature": [0, -2, -2], "threshold": [0.5, -2.0, -2.0],
+//!     "value": [[[2.0, 2.0]], [[2.0, 0.0]], [[0.0, 2.0]]]}]}"#;
+//! let imported: dt::Classifier<BlockTree> = sklearn::from_str(dump).unwrap();
+//! assert_eq!(imported.predict_batch(&[0.2, 0.8]), [0, 1]);
+//! ```
+use crate::{
+    decision_tree::threshold_below,
+    dt,
+    io::{Error, ModelKind, Task},
+    json::{self, Document, JsonModel, Node, Tree},
+    rf, ClassDecode, ClassesMapping, Export, Label, Predictor, Validate,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::path::Path;
+
+/// A dump of a scikit-learn model.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Dump {
+    pub estimator: String,
+    pub n_features_in: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classes: Option<Vec<serde_json::Value>>,
+    pub trees: Vec<SklearnTree>,
+}
+
+/// Arrays of a scikit-learn `tree_` object.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SklearnTree {
+    pub children_left: Vec<i64>,
+    pub children_right: Vec<i64>,
+    pub feature: Vec<i64>,
+    pub threshold: Vec<f64>,
+    pub value: Vec<Vec<Vec<f64>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub missing_go_to_left: Option<Vec<u8>>,
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A model that can be imported from scikit-learn: trees and random forests.
+pub trait SklearnModel: sealed::Sealed + Sized {
+    /// Builds a model from `dump` and checks it with `validate()`.
+    fn from_dump(dump: &Dump) -> Result<Self, Error>;
+}
+
+/// Reads a model from a JSON dump.
+pub fn from_str<M: SklearnModel>(s: &str) -> Result<M, Error> {
+    let dump: Dump = serde_json::from_str(s).map_err(|e| Error::Codec(e.to_string()))?;
+    M::from_dump(&dump)
+}
+
+/// Reads a model from a JSON dump in a file at `path`.
+pub fn load<M: SklearnModel>(path: impl AsRef<Path>) -> Result<M, Error> {
+    from_str(&std::fs::read_to_string(path)?)
+}
+
+// Returns the kind of a model with the estimator name after checking the task.
+fn kind(dump: &Dump, task: Task) -> Result<ModelKind, Error> {
+    let (kind, found) = match dump.estimator.as_str() {
+        "DecisionTreeClassifier" => (ModelKind::TREE, Task::CLASSIFIER),
+        "DecisionTreeRegressor" => (ModelKind::TREE, Task::REGRESSOR),
+        "RandomForestClassifier" => (ModelKind::FOREST, Task::CLASSIFIER),
+        "RandomForestRegressor" => (ModelKind::FOREST, Task::REGRESSOR),
+        name => return Err(Error::Codec(format!("unsupported estimator {name}"))),
+    };
+    if found != task {
+        return Err(Error::Codec(format!(
+            "expected {task:?}, found {}",
+            dump.estimator
+        )));
+    }
+    Ok(kind)
+}
+
+// Fills a leaf node by values of a scikit-learn node.
+type FillLeaf<'a> = &'a dyn Fn(&[Vec<f64>], &mut Node) -> Result<(), String>;
+
+// Converts a tree into JSON nodes.
+fn convert_tree(tree: &SklearnTree, leaf: FillLeaf) -> Result<Tree, Error> {
+    let n = tree.children_left.len();
+    if [
+        tree.children_right.len(),
+        tree.feature.len(),
+        tree.threshold.len(),
+        tree.value.len(),
+    ] != [n; 4]
+    {
+        return Err(Error::Codec("tree arrays differ in length".to_string()));
+    }
+    let missing_go_to_left = tree.missing_go_to_left.as_deref().unwrap_or(&[]);
+    if tree.missing_go_to_left.is_some() && missing_go_to_left.len() != n {
+        return Err(Error::Codec("tree arrays differ in length".to_string()));
+    }
+    let child = |id: i64| usize::try_from(id).map_err(|_| format!("invalid child {id}"));
+    let nodes = (0..n)
+        .map(|id| {
+            let mut node = Node {
+                id,
+                ..Node::default()
+            };
+            if tree.children_left[id] == -1 {
+                leaf(&tree.value[id], &mut node)?;
+            } else {
+                let feature = tree.feature[id];
+                node.feature =
+                    Some(u16::try_from(feature).map_err(|_| format!("invalid feature {feature}"))?);
+                node.threshold = Some(threshold_below(tree.threshold[id]));
+                node.left = Some(child(tree.children_left[id])?);
+                node.right = Some(child(tree.children_right[id])?);
+                node.missing_left = missing_go_to_left.get(id).map(|&left| left != 0);
+            }
+            Ok(node)
+        })
+        .collect::<Result<Vec<_>, String>>()
+        .map_err(Error::Codec)?;
+    Ok(Tree { nodes })
+}
+
+// Returns the single output of node values.
+fn output(value: &[Vec<f64>]) -> Result<&[f64], String> {
+    match value {
+        [output] => Ok(output),
+        _ => Err(format!("{} outputs are not supported", value.len())),
+    }
+}
+
+// Converts a classifier dump with columns of `value` reordered into classes of the model.
+fn classifier_document<L: Label + Serialize + DeserializeOwned>(
+    dump: &Dump,
+) -> Result<Document, Error> {
+    let kind = kind(dump, Task::CLASSIFIER)?;
+    let labels = dump
+        .classes
+        .as_ref()
+        .ok_or_else(|| Error::Codec("classifier has no classes".to_string()))?
+        .iter()
+        .map(|label| serde_json::from_value(label.clone()))
+        .collect::<Result<Vec<L>, _>>()
+        .map_err(|e| Error::Codec(e.to_string()))?;
+    let classes_map = ClassesMapping::with_classes(&labels);
+    if classes_map.num_classes() != labels.len() {
+        return Err(Error::Codec("classes are not distinct".to_string()));
+    }
+    let columns = classes_map.encode(&labels).unwrap();
+    let trees = dump
+        .trees
+        .iter()
+        .map(|tree| {
+            convert_tree(tree, &|value, node| {
+                let weights = output(value)?;
+                if weights.len() != columns.len() {
+                    return Err(format!("values of {} classes", weights.len()));
+                }
+                let total: f64 = weights.iter().sum();
+                if total <= 0.0 {
+                    return Err(format!("node {} has no weight", node.id));
+                }
+                let mut proba = vec![0.0; columns.len()];
+                for (&column, &weight) in columns.iter().zip(weights) {
+                    proba[column] = (weight / total) as f32;
+                }
+                node.proba = Some(proba);
+                Ok(())
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let mut document = json::document(kind, Task::CLASSIFIER, dump.n_features_in, trees);
+    let labels = classes_map.get_decode_table().iter();
+    document.classes = Some(labels.map(|l| serde_json::to_value(l).unwrap()).collect());
+    Ok(document)
+}
+
+fn regressor_document(dump: &Dump) -> Result<Document, Error> {
+    let kind = kind(dump, Task::REGRESSOR)?;
+    let trees = dump
+        .trees
+        .iter()
+        .map(|tree| {
+            convert_tree(tree, &|value, node| match output(value)? {
+                [value] => {
+                    node.value = Some(*value as f32);
+                    Ok(())
+                }
+                values => Err(format!("{} values of a regressor node", values.len())),
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(json::document(
+        kind,
+        Task::REGRESSOR,
+        dump.n_features_in,
+        trees,
+    ))
+}
+
+impl<P: Predictor, L> sealed::Sealed for dt::Classifier<P, L> {}
+
+impl<P, L> SklearnModel for dt::Classifier<P, L>
+where
+    P: Predictor + Export + Validate,
+    L: Label + Serialize + DeserializeOwned,
+{
+    fn from_dump(dump: &Dump) -> Result<Self, Error> {
+        Self::from_document(&classifier_document::<L>(dump)?)
+    }
+}
+
+impl<P: Predictor, L> sealed::Sealed for rf::Classifier<P, L> {}
+
+impl<P, L> SklearnModel for rf::Classifier<P, L>
+where
+    P: Predictor + Export + Validate + Send + Sync,
+    L: Label + Serialize + DeserializeOwned,
+{
+    fn from_dump(dump: &Dump) -> Result<Self, Error> {
+        Self::from_document(&classifier_document::<L>(dump)?)
+    }
+}
+
+impl<P: Predictor> sealed::Sealed for dt::Regressor<P> {}
+
+impl<P: Predictor + Export + Validate> SklearnModel for dt::Regressor<P> {
+    fn from_dump(dump: &Dump) -> Result<Self, Error> {
+        Self::from_document(&regressor_document(dump)?)
+    }
+}
+
+impl<P: Predictor> sealed::Sealed for rf::Regressor<P> {}
+
+impl<P: Predictor + Export + Validate + Send + Sync> SklearnModel for rf::Regressor<P> {
+    fn from_dump(dump: &Dump) -> Result<Self, Error> {
+        Self::from_document(&regressor_document(dump)?)
+    }
+}
@@ -1,5 +1,21 @@
-use crate::{dt, prelude::*, rf, BlockTree, CompactTree};
-use std::{fs::read_to_string, str::FromStr};
+use crate::{
+    archive,
+    boosting::{Booster, Objective},
+    codegen::SourceLiteral,
+    decision_tree, dt, ensemble_trainer,
+    executor::ThreadPool,
+    io, json,
+    onnx::OnnxModel,
+    prelude::*,
+    quantized::{Precision, Quantization},
+    rf, sklearn, BlockTree, CompactTree, Export, TreeNode, UnknownLabel,
+};
+use rand::{rngs::SmallRng, SeedableRng};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::read_to_string,
+    str::FromStr,
+};
 
 const MAX_THREADS: usize = 8;
 
@@ -56,6 +72,35 @@ fn random_forest_classifier_depth10() {
     assert!(acc >= 0.67);
 }
 
+#[test]
+fn random_forest_classifier_strict_fraction() {
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let (x_train, y_train, x_pred, y_ref) = split_dataset(&samples, &targets);
+    let predictor = rf::Classifier::<BlockTree>::trainer()
+        .with_max_depth(10)
+        .with_max_features(MaxFeaturesPolicy::FRACTION(0.3))
+        .with_strict_max_features(true)
+        .with_threads(MAX_THREADS)
+        .train(&x_train, &y_train);
+
+    let y_pred = predictor.predict_batch(&x_pred, MAX_THREADS);
+    let acc = classifier_accuracy(&y_pred, &y_ref);
+    assert!(acc >= 0.66);
+}
+
+#[test]
+fn max_features_fraction() {
+    assert_eq!(MaxFeaturesPolicy::FRACTION(0.3).resolve(11), 3);
+    assert_eq!(MaxFeaturesPolicy::FRACTION(0.01).resolve(11), 1);
+    assert_eq!(MaxFeaturesPolicy::FRACTION(2.0).resolve(11), 11);
+    let nan = MaxFeaturesPolicy::FRACTION(f32::NAN);
+    assert_eq!(nan, nan.clone());
+    assert_ne!(
+        MaxFeaturesPolicy::FRACTION(0.3),
+        MaxFeaturesPolicy::FRACTION(0.4)
+    );
+}
+
 #[test]
 fn random_forest_classifier_depth10_self() {
     let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
@@ -85,6 +130,778 @@ fn random_forest_binary_classifier() {
     assert!(f1 >= 0.79);
 }
 
+#[test]
+fn random_forest_string_labels() {
+    let (samples, targets) = load_dataset::<String>("datasets/magic04.data", ",", false);
+    let (x_train, y_train, x_pred, y_ref) = split_dataset(&samples, &targets);
+    let predictor: rf::Classifier<BlockTree, String> = rf::Classifier::trainer()
+        .with_max_depth(10)
+        .with_trees(20)
+        .with_threads(MAX_THREADS)
+        .train(&x_train, &y_train);
+    assert_eq!(predictor.get_decode_table(), &["g", "h"]);
+
+    let config = bincode::config::standard();
+    let bytes = bincode::serde::encode_to_vec(&predictor, config).unwrap();
+    let (restored, _): (rf::Classifier<BlockTree, String>, usize) =
+        bincode::serde::decode_from_slice(&bytes, config).unwrap();
+    assert_eq!(restored, predictor);
+
+    let y_pred = restored.predict_batch(&x_pred, MAX_THREADS);
+    let acc = y_pred
+        .iter()
+        .zip(y_ref.iter())
+        .filter(|(p, t)| p == t)
+        .count();
+    assert!(acc as f64 / y_ref.len() as f64 >= 0.85);
+
+    let y_bool: Vec<bool> = y_train.iter().map(|t| t == "h").collect();
+    let predictor = dt::Classifier::<BlockTree, bool>::trainer()
+        .with_max_depth(10)
+        .train(&x_train, &y_bool);
+    let y_pred = predictor.predict_batch(&x_pred);
+    let agree = y_pred
+        .iter()
+        .zip(y_ref.iter())
+        .filter(|(p, t)| **p == (*t == "h"))
+        .count();
+    assert!(agree as f64 / y_ref.len() as f64 >= 0.8);
+}
+
+#[test]
+fn declared_classes() {
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let (x_train, y_train, x_pred, y_ref) = split_dataset(&samples, &targets);
+    let classes: Vec<i64> = (0..=10).collect();
+    let predictor = rf::Classifier::<BlockTree>::trainer()
+        .with_max_depth(10)
+        .with_trees(10)
+        .with_classes(&classes)
+        .train(&x_train, &y_train);
+    assert_eq!(predictor.get_decode_table(), &classes);
+
+    let proba = predictor.proba(&x_pred, MAX_THREADS);
+    assert_eq!(proba.len(), y_ref.len() * classes.len());
+    for p in proba.chunks(classes.len()) {
+        assert_eq!(p[0], 0.);
+        assert_eq!(p[10], 0.);
+    }
+
+    let predictor = dt::Classifier::<CompactTree>::trainer()
+        .with_classes(&classes)
+        .train(&x_train, &y_train);
+    assert_eq!(predictor.num_classes(), classes.len());
+    assert_eq!(predictor.encode(&[0, 5, 10]), Ok(vec![0, 5, 10]));
+    assert_eq!(predictor.encode(&[5, 42]), Err(UnknownLabel(42)));
+
+    // Labels out of declared classes.
+    let mut labels = y_train.clone();
+    labels[1] = 42;
+    let result = dt::Classifier::<CompactTree>::trainer()
+        .with_classes(&classes)
+        .try_train(&x_train, &labels);
+    assert_eq!(result.unwrap_err(), UnknownLabel(42));
+    let result = rf::Classifier::<BlockTree>::trainer()
+        .with_trees(2)
+        .with_classes(&classes)
+        .try_train(&x_train, &labels);
+    assert_eq!(result.unwrap_err(), UnknownLabel(42));
+}
+
+#[test]
+fn random_forest_balanced_classifier() {
+    let (samples, targets) = load_dataset::<String>("datasets/magic04.data", ",", false);
+    let targets: Vec<i64> = targets.iter().map(|t| (t == "h") as i64).collect();
+
+    let (x_train, y_train, x_pred, y_ref) = split_dataset(&samples, &targets);
+    let predictor: rf::Classifier = rf::Classifier::trainer()
+        .with_max_depth(10)
+        .with_trees(100)
+        .with_threads(MAX_THREADS)
+        .with_sampling(SamplingPolicy::BALANCED)
+        .train(&x_train, &y_train);
+
+    let y_pred = predictor.predict_batch(&x_pred, MAX_THREADS);
+    let f1 = f1score(&y_pred, &y_ref);
+    assert!(f1 >= 0.79);
+}
+
+#[test]
+fn stratified_and_balanced_sampling() {
+    // Classes of 3, 5 and 12 samples, listed out of order.
+    let targets: Vec<u32> = [2, 1, 2, 0, 2, 1, 2, 0, 2, 1, 2, 0, 2, 1, 2, 2, 2, 1, 2, 2].to_vec();
+    let strata = ensemble_trainer::strata(&targets);
+    let class_counts = |weights: &[f32]| {
+        let mut counts = [0.; 3];
+        for (&t, &w) in targets.iter().zip(weights.iter()) {
+            counts[t as usize] += w;
+        }
+        counts
+    };
+    assert_eq!(class_counts(&[1.; 20]), [3., 5., 12.]);
+
+    // Weights of each tree as drawn by the trainer from its own seed.
+    let draws = |policy: SamplingPolicy| -> Vec<Vec<f32>> {
+        (0..50)
+            .map(|seed| {
+                let mut rng = SmallRng::seed_from_u64(seed);
+                ensemble_trainer::sample(&strata, targets.len(), &policy, &mut rng)
+            })
+            .collect()
+    };
+    let stratified = draws(SamplingPolicy::STRATIFIED);
+    assert!(stratified.iter().all(|w| class_counts(w) == [3., 5., 12.]));
+    let balanced = draws(SamplingPolicy::BALANCED);
+    assert!(balanced.iter().all(|w| class_counts(w) == [3., 3., 3.]));
+    // Samples are still drawn with replacement within classes.
+    for weights in [&stratified, &balanced] {
+        assert!(weights.iter().any(|w| w.iter().any(|&x| x > 1.)));
+        assert!(weights.iter().any(|w| w != &weights[0]));
+    }
+    let bootstrap = draws(SamplingPolicy::BOOTSTRAP);
+    assert!(bootstrap.iter().all(|w| w.iter().sum::<f32>() == 20.));
+    assert!(bootstrap.iter().any(|w| class_counts(w) != [3., 5., 12.]));
+}
+
+#[test]
+fn random_forest_warm_start() {
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let full: rf::Classifier = rf::Classifier::trainer()
+        .with_max_depth(10)
+        .with_trees(20)
+        .train(&samples, &targets);
+
+    let half: rf::Classifier = rf::Classifier::trainer()
+        .with_max_depth(10)
+        .with_trees(10)
+        .train(&samples, &targets);
+    let warm = rf::Classifier::trainer()
+        .with_max_depth(10)
+        .warm_start(&half)
+        .with_trees(10)
+        .train(&samples, &targets)
+        .unwrap();
+    assert_eq!(full, warm);
+    assert_eq!(half.num_trees(), 10);
+
+    // Samples of other features.
+    let error = rf::Classifier::trainer()
+        .warm_start(&half)
+        .with_trees(1)
+        .train(&samples[..samples.len() / 2], &targets)
+        .unwrap_err();
+    let expected = half.num_features();
+    let found = expected / 2;
+    assert_eq!(error, rf::WarmStartError::FEATURES { expected, found });
+
+    // Classes of the model are not changed by warm start.
+    let classes = half.get_decode_table().to_vec();
+    let mut declared = classes.clone();
+    declared.reverse();
+    let warm = rf::Classifier::trainer()
+        .with_max_depth(10)
+        .with_classes(&declared)
+        .warm_start(&half)
+        .with_trees(10)
+        .train(&samples, &targets)
+        .unwrap();
+    assert_eq!(full, warm);
+    declared.push(100);
+    let error = rf::Classifier::trainer()
+        .with_classes(&declared)
+        .warm_start(&half)
+        .with_trees(1)
+        .train(&samples, &targets)
+        .unwrap_err();
+    let found = declared;
+    assert_eq!(
+        error,
+        rf::WarmStartError::CLASSES {
+            expected: classes,
+            found
+        }
+    );
+
+    let mut labels = targets.clone();
+    labels[0] = 100;
+    let error = rf::Classifier::trainer()
+        .warm_start(&half)
+        .with_trees(1)
+        .train(&samples, &labels)
+        .unwrap_err();
+    assert_eq!(error, rf::WarmStartError::LABEL(100));
+
+    let (samples, targets) = load_dataset::<f32>("datasets/Folds5x2_pp.csv", ",", true);
+    let full: rf::Regressor = rf::Regressor::trainer()
+        .with_max_depth(5)
+        .with_trees(6)
+        .train(&samples, &targets);
+
+    let half: rf::Regressor = rf::Regressor::trainer()
+        .with_max_depth(5)
+        .with_trees(3)
+        .train(&samples, &targets);
+    let warm = rf::Regressor::trainer()
+        .with_max_depth(5)
+        .warm_start(&half)
+        .with_trees(3)
+        .train(&samples, &targets)
+        .unwrap();
+    assert_eq!(full, warm);
+}
+
+#[test]
+fn random_forest_merge_and_select() {
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let full: rf::Classifier = rf::Classifier::trainer()
+        .with_max_depth(5)
+        .with_trees(20)
+        .train(&samples, &targets);
+
+    let mut head = full.select_trees(&(0..10).collect::<Vec<_>>());
+    let tail = full.select_trees(&(10..20).collect::<Vec<_>>());
+    let mut truncated = full.clone();
+    truncated.truncate(10);
+    assert_eq!(head, truncated);
+
+    head.merge(tail);
+    assert_eq!(head, full);
+
+    // Forests with different classes.
+    let (x_train, y_train, _, _) = split_dataset(&samples, &targets);
+    let y_train: Vec<i64> = y_train.iter().map(|&y| y.min(6)).collect();
+    let mut other: rf::Classifier = rf::Classifier::trainer()
+        .with_max_depth(5)
+        .with_trees(10)
+        .train(&x_train, &y_train);
+    let num_classes = other.num_classes();
+    other.merge(full.clone());
+    assert_eq!(other.num_classes(), full.num_classes());
+    assert!(other.num_classes() > num_classes);
+    assert_eq!(other.num_trees(), 30);
+    let proba = other.proba(&samples, MAX_THREADS);
+    let proba_full = full.proba(&samples, MAX_THREADS);
+    for (p, f) in proba
+        .chunks(other.num_classes())
+        .zip(proba_full.chunks(full.num_classes()))
+    {
+        assert!((p.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+        // Classes above 6 are predicted only by trees of full forest.
+        for c in 0..full.num_classes() {
+            if full.decode(c) > 6 {
+                assert!((p[c] * 30. - f[c] * 20.).abs() < 1e-3);
+            }
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "forest must keep at least one tree")]
+fn random_forest_truncate_to_empty() {
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let mut forest: rf::Classifier = rf::Classifier::trainer()
+        .with_max_depth(2)
+        .with_trees(2)
+        .train(&samples, &targets);
+    forest.truncate(0);
+}
+
+#[test]
+#[should_panic(expected = "forest must keep at least one tree")]
+fn random_forest_select_no_trees() {
+    let (samples, targets) = load_dataset::<f32>("datasets/Folds5x2_pp.csv", ",", true);
+    let forest: rf::Regressor = rf::Regressor::trainer()
+        .with_max_depth(2)
+        .with_trees(2)
+        .train(&samples, &targets);
+    forest.select_trees(&[]);
+}
+
+#[test]
+fn random_forest_voting() {
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let (x_train, y_train, x_pred, y_ref) = split_dataset(&samples, &targets);
+    let predictor: rf::Classifier = rf::Classifier::trainer()
+        .with_max_depth(10)
+        .with_trees(30)
+        .train(&x_train, &y_train);
+
+    let soft = predictor.proba(&x_pred, MAX_THREADS);
+    let weighted = predictor.proba_with(&x_pred, 1, &rf::Voting::WEIGHTED(vec![2.; 30]));
+    assert!(soft
+        .iter()
+        .zip(weighted.iter())
+        .all(|(x, y)| (x - y).abs() < 1e-5));
+
+    let votes = predictor.proba_with(&x_pred, MAX_THREADS, &rf::Voting::HARD);
+    for p in votes.chunks(predictor.num_classes()) {
+        assert!(p.iter().all(|&v| (v * 30.).fract().abs() < 1e-3));
+    }
+    let y_pred = predictor.predict_batch_with(&x_pred, MAX_THREADS, &rf::Voting::HARD);
+    assert!(classifier_accuracy(&y_pred, &y_ref) >= 0.64);
+}
+
+#[test]
+fn random_forest_regressor_averaging() {
+    let (samples, targets) = load_dataset::<f32>("datasets/Folds5x2_pp.csv", ",", true);
+    let (x_train, y_train, x_pred, y_ref) = split_dataset(&samples, &targets);
+    let predictor: rf::Regressor = rf::Regressor::trainer()
+        .with_max_depth(10)
+        .with_trees(20)
+        .train(&x_train, &y_train);
+
+    let mean = predictor.predict_batch(&x_pred, MAX_THREADS);
+    let trimmed = predictor.predict_batch_with(&x_pred, 1, &rf::Averaging::TRIMMED(0.0));
+    assert!(mean
+        .iter()
+        .zip(trimmed.iter())
+        .all(|(x, y)| (x - y).abs() < 1e-2));
+
+    let median = predictor.predict_batch_with(&x_pred, MAX_THREADS, &rf::Averaging::MEDIAN);
+    assert!(mean_squared_error(&median, &y_ref) < 16.0);
+    let trimmed = predictor.predict_batch_with(&x_pred, MAX_THREADS, &rf::Averaging::TRIMMED(0.2));
+    assert!(mean_squared_error(&trimmed, &y_ref) < 16.0);
+}
+
+#[test]
+fn random_forest_thread_pool() {
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let pool = ThreadPool::new(MAX_THREADS);
+    let mut trainer = rf::Classifier::<BlockTree>::trainer();
+    trainer.with_max_depth(10).with_trees(20);
+    let predictor = trainer.train_in(&samples, &targets, &pool);
+    assert_eq!(predictor, trainer.with_threads(3).train(&samples, &targets));
+
+    for _ in 0..3 {
+        assert_eq!(
+            predictor.predict_batch_in(&samples, &pool),
+            predictor.predict_batch(&samples, 1)
+        );
+    }
+
+    let (samples, targets) = load_dataset::<f32>("datasets/Folds5x2_pp.csv", ",", true);
+    let predictor: rf::Regressor = rf::Regressor::trainer()
+        .with_max_depth(5)
+        .with_trees(10)
+        .train_in(&samples, &targets, &pool);
+    let mse = mean_squared_error(&predictor.predict_batch_in(&samples, &pool), &targets);
+    assert!(mse < 20.0);
+}
+
+#[test]
+fn random_forest_sample_parallelism() {
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let mut predictor = rf::Classifier::<BlockTree>::trainer()
+        .with_max_depth(10)
+        .with_trees(20)
+        .train(&samples, &targets);
+    let by_trees = predictor.proba(&samples, MAX_THREADS);
+    predictor.set_parallelism(rf::Parallelism::SAMPLES);
+    let by_samples = predictor.proba(&samples, MAX_THREADS);
+    assert!(by_trees
+        .iter()
+        .zip(by_samples.iter())
+        .all(|(a, b)| (a - b).abs() < 1e-5));
+
+    let (samples, targets) = load_dataset::<f32>("datasets/Folds5x2_pp.csv", ",", true);
+    let mut predictor: rf::Regressor = rf::Regressor::trainer()
+        .with_max_depth(5)
+        .with_trees(10)
+        .train(&samples, &targets);
+    predictor.set_parallelism(rf::Parallelism::TREES);
+    let by_trees = predictor.predict_batch(&samples, MAX_THREADS);
+    predictor.set_parallelism(rf::Parallelism::SAMPLES);
+    let by_samples = predictor.predict_batch(&samples, MAX_THREADS);
+    assert!(by_trees
+        .iter()
+        .zip(by_samples.iter())
+        .all(|(a, b)| (a - b).abs() < 1e-2));
+}
+
+#[test]
+fn predict_into_buffers() {
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let num_samples = targets.len();
+    let tree = dt::Classifier::<BlockTree>::trainer()
+        .with_max_depth(10)
+        .train(&samples, &targets);
+    let forest = rf::Classifier::<CompactTree>::trainer()
+        .with_max_depth(10)
+        .with_trees(20)
+        .train(&samples, &targets);
+
+    let mut labels = vec![0; num_samples];
+    let mut proba = vec![0.; num_samples * forest.num_classes()];
+    tree.predict_into(&samples, &mut labels);
+    tree.proba_into(&samples, &mut proba);
+    assert_eq!(labels, tree.predict_batch(&samples));
+    assert_eq!(proba, tree.proba(&samples));
+
+    forest.predict_into(&samples, &mut labels);
+    forest.proba_into(&samples, &mut proba);
+    assert_eq!(labels, forest.predict_batch(&samples, MAX_THREADS));
+    let expected = forest.proba(&samples, MAX_THREADS);
+    assert!(proba
+        .iter()
+        .zip(expected.iter())
+        .all(|(a, b)| (a - b).abs() < 1e-5));
+    let num_features = forest.num_features();
+    for (sample, label) in samples.chunks(num_features).zip(labels.iter()).take(100) {
+        assert_eq!(forest.predict_one(sample), *label);
+    }
+
+    let (samples, targets) = load_dataset::<f32>("datasets/Folds5x2_pp.csv", ",", true);
+    let mut values = vec![0.; targets.len()];
+    let tree = dt::Regressor::<BlockTree>::trainer()
+        .with_max_depth(5)
+        .train(&samples, &targets);
+    tree.predict_into(&samples, &mut values);
+    assert_eq!(values, tree.predict_batch(&samples));
+
+    let forest: rf::Regressor = rf::Regressor::trainer()
+        .with_max_depth(5)
+        .with_trees(10)
+        .train(&samples, &targets);
+    forest.predict_into(&samples, &mut values);
+    let expected = forest.predict_batch(&samples, 1);
+    assert!(values
+        .iter()
+        .zip(expected.iter())
+        .all(|(a, b)| (a - b).abs() < 1e-2));
+    for (sample, value) in samples
+        .chunks(forest.num_features())
+        .zip(expected.iter())
+        .take(100)
+    {
+        assert!((forest.predict_one(sample) - value).abs() < 1e-2);
+    }
+}
+
+#[test]
+fn batch_resolve_matches_single_sample() {
+    let (samples, targets) = load_dataset::<f32>("datasets/Folds5x2_pp.csv", ",", true);
+    let num_features = samples.len() / targets.len();
+    // An odd number of samples leaves an incomplete batch.
+    let samples = &samples[..1001 * num_features];
+    let targets = &targets[..1001];
+
+    // Small compact trees are traversed one sample at a time.
+    for depth in [3, 12] {
+        let block = dt::Regressor::<BlockTree>::trainer()
+            .with_max_depth(depth)
+            .train(samples, targets);
+        let compact = dt::Regressor::<CompactTree>::trainer()
+            .with_max_depth(depth)
+            .train(samples, targets);
+        let expected: Vec<f32> = samples
+            .chunks(num_features)
+            .map(|s| block.predict_one(s))
+            .collect();
+        assert_eq!(block.predict_batch(samples), expected);
+        assert_eq!(compact.predict_batch(samples), expected);
+    }
+}
+
+#[test]
+fn quick_scorer() {
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let forest = rf::Classifier::<CompactTree>::trainer()
+        .with_max_depth(8)
+        .with_trees(20)
+        .train(&samples, &targets);
+    let classifier = forest.quick_scorer();
+    assert_eq!(classifier.num_classes(), forest.num_classes());
+    let expected = forest.proba(&samples, 1);
+    assert!(classifier
+        .proba(&samples)
+        .iter()
+        .zip(expected.iter())
+        .all(|(a, b)| (a - b).abs() < 1e-5));
+    let classes = forest.predict_batch(&samples, 1);
+    assert_eq!(classifier.predict_batch(&samples), classes);
+    let wine_samples = samples;
+
+    // Deep trees have more than 64 leaves, so leaves take multiple words.
+    let (samples, targets) = load_dataset::<f32>("datasets/Folds5x2_pp.csv", ",", true);
+    let forest: rf::Regressor<CompactTree> = rf::Regressor::trainer()
+        .with_max_depth(12)
+        .with_trees(5)
+        .train(&samples, &targets);
+    let predictor = forest.quick_scorer();
+    let expected = forest.predict_batch(&samples, 1);
+    assert!(predictor
+        .predict_batch(&samples)
+        .iter()
+        .zip(expected.iter())
+        .all(|(a, b)| (a - b).abs() < 1e-2));
+    assert!((predictor.predict_one(&samples[..4]) - expected[0]).abs() < 1e-2);
+    // Bitvectors of the thread were grown by the regressor and are reused by the classifier.
+    for (sample, class) in wine_samples.chunks(11).zip(classes.iter()).take(100) {
+        assert_eq!(classifier.predict_one(sample), *class);
+    }
+}
+
+#[test]
+fn source_generation() {
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let predictor = rf::Classifier::<BlockTree>::trainer()
+        .with_max_depth(4)
+        .with_trees(3)
+        .train(&samples, &targets);
+    let source = predictor.to_rust_source("");
+    assert!(source.contains("pub const NUM_FEATURES: usize = 11;"));
+    assert!(source.contains("pub const CLASSES: [i64; NUM_CLASSES] = [3, 4, 5, 6, 7, 8];"));
+    assert!(source.contains("fn tree_2(x: &[f32]) -> usize {"));
+    assert!(!source.contains("fn tree_3"));
+    let source = predictor.to_c_source("wine_");
+    assert!(source.contains(
+        "static long long const WINE_CLASSES[WINE_NUM_CLASSES] = {3LL, 4LL, 5LL, 6LL, 7LL, 8LL};"
+    ));
+    assert!(source.contains("long long wine_predict(const float *x) {"));
+    assert!(source.contains("row = wine_tree_2(x);"));
+    assert!(!source.contains(" predict(") && !source.contains(" TREE_"));
+
+    let (samples, targets) = load_dataset::<f32>("datasets/Folds5x2_pp.csv", ",", true);
+    let predictor = dt::Regressor::<CompactTree>::trainer()
+        .with_max_depth(3)
+        .train(&samples, &targets);
+    let source = predictor.to_c_source("");
+    // A tree of depth 3 has 8 leaves.
+    assert_eq!(source.matches("return ").count(), 8 + 1);
+    assert!(source.contains("float predict(const float *x) {"));
+
+    assert_eq!(i64::MIN.c_literal(), "(-9223372036854775807LL - 1)");
+    assert_eq!(i32::MIN.c_literal(), "(-2147483647 - 1)");
+    assert_eq!(u64::MAX.c_literal(), "18446744073709551615ULL");
+    assert_eq!((-1i64).c_literal(), "-1LL");
+}
+
+// Sources generated by `compiled_sources`, two models with distinct prefixes share a module.
+mod generated {
+    include!("../datasets/models/wine_classifier.rs");
+    include!("../datasets/models/power_regressor.rs");
+}
+
+#[test]
+fn compiled_sources() {
+    // The included sources are compared with regenerated ones, which keeps them up to date.
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let classifier = dt::Classifier::<BlockTree>::trainer()
+        .with_max_depth(4)
+        .train(&samples, &targets);
+    assert_eq!(
+        classifier.to_rust_source("wine_"),
+        read_to_string("datasets/models/wine_classifier.rs").unwrap()
+    );
+    for sample in samples.chunks(generated::WINE_NUM_FEATURES) {
+        assert_eq!(generated::wine_proba(sample), classifier.proba(sample)[..]);
+        assert_eq!(
+            generated::wine_predict(sample),
+            classifier.predict_one(sample)
+        );
+    }
+
+    let (samples, targets) = load_dataset::<f32>("datasets/Folds5x2_pp.csv", ",", true);
+    let regressor = dt::Regressor::<CompactTree>::trainer()
+        .with_max_depth(3)
+        .train(&samples, &targets);
+    assert_eq!(
+        regressor.to_rust_source("power_"),
+        read_to_string("datasets/models/power_regressor.rs").unwrap()
+    );
+    for sample in samples.chunks(generated::POWER_NUM_FEATURES) {
+        assert_eq!(
+            generated::power_predict(sample),
+            regressor.predict_one(sample)
+        );
+    }
+}
+
+#[test]
+fn quantized_forests() {
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let (x_train, y_train, x_pred, y_ref) = split_dataset(&samples, &targets);
+    let forest = rf::Classifier::<BlockTree>::trainer()
+        .with_trees(50)
+        .with_threads(MAX_THREADS)
+        .train(&x_train, &y_train);
+    let acc = classifier_accuracy(&forest.predict_batch(&x_pred, MAX_THREADS), &y_ref);
+    let config = bincode::config::standard();
+    let size = bincode::serde::encode_to_vec(&forest, config)
+        .unwrap()
+        .len();
+
+    let quantization = Quantization {
+        thresholds: Precision::U8,
+        leaves: Precision::U8,
+    };
+    let quantized = forest.quantize(&quantization);
+    let quantized_acc = classifier_accuracy(&quantized.predict_batch(&x_pred), &y_ref);
+    let bytes = bincode::serde::encode_to_vec(&quantized, config).unwrap();
+    let (decoded, _): (rf::QuantizedClassifier, usize) =
+        bincode::serde::decode_from_slice(&bytes, config).unwrap();
+    assert_eq!(decoded, quantized);
+    assert!((acc - quantized_acc).abs() <= 0.01);
+    assert!(bytes.len() * 2 < size);
+
+    // Labels without source literals keep the order of classes, so the trees are the same.
+    let y_train: Vec<Option<i64>> = y_train.iter().map(|&t| Some(t)).collect();
+    let forest = rf::Classifier::<BlockTree, Option<i64>>::trainer()
+        .with_trees(50)
+        .with_threads(MAX_THREADS)
+        .train(&x_train, &y_train);
+    let expected: Vec<_> = quantized
+        .predict_batch(&x_pred)
+        .into_iter()
+        .map(Some)
+        .collect();
+    assert_eq!(
+        forest.quantize(&quantization).predict_batch(&x_pred),
+        expected
+    );
+
+    let (samples, targets) = load_dataset::<f32>("datasets/Folds5x2_pp.csv", ",", true);
+    let (x_train, y_train, x_pred, y_ref) = split_dataset(&samples, &targets);
+    let forest: rf::Regressor<CompactTree> = rf::Regressor::trainer()
+        .with_max_depth(15)
+        .with_trees(20)
+        .with_threads(MAX_THREADS)
+        .train(&x_train, &y_train);
+    let mse = mean_squared_error(&forest.predict_batch(&x_pred, MAX_THREADS), &y_ref);
+    let size = bincode::serde::encode_to_vec(&forest, config)
+        .unwrap()
+        .len();
+    let quantized = forest.quantize(&Quantization::default());
+    let quantized_mse = mean_squared_error(&quantized.predict_batch(&x_pred), &y_ref);
+    let bytes = bincode::serde::encode_to_vec(&quantized, config).unwrap();
+    assert!((mse - quantized_mse).abs() < 0.5);
+    assert!(bytes.len() < size);
+    assert!(quantized.validate().is_ok());
+
+    // The root splits at 2.5 with a leaf on the right, its left child splits at 1.5.
+    let mask = |bits: u8| {
+        format!(
+            r#"{{"order": "bitvec::order::Lsb0", "head": {{"width": 64, "index": 0}}, "bits": 2,
+            "data": [{bits}]}}"#
+        )
+    };
+    let model = format!(
+        r#"{{"codebooks": {{"codebooks": [[1.5, 2.5], []]}}, "trees": [{{"features": [0, 0],
+        "thresholds": {{"U8": [1, 0]}}, "left_leaves_mask": {}, "right_leaves_mask": {}}}],
+        "values": [{{"U8": [255, 0, 127]}}], "min": 2.0, "step": 0.5}}"#,
+        mask(2),
+        mask(3)
+    );
+    let quantized: rf::QuantizedRegressor = serde_json::from_str(&model).unwrap();
+    assert!(quantized.validate().is_ok());
+    assert_eq!(
+        quantized.predict_batch(&[0.0, 2.0, 3.0]),
+        [2.0, 65.5, 129.5]
+    );
+    let decode = |model: String| serde_json::from_str::<rf::QuantizedRegressor>(&model);
+    // The root has no child nodes, or the only other node is the child of both sides of the root.
+    assert!(decode(model.replacen(&mask(2), &mask(3), 1)).is_err());
+    assert!(decode(model.replace(&mask(3), &mask(2))).is_err());
+    assert!(decode(model.replace("[0, 0]", "[0]")).is_err());
+    for invalid in [
+        model.replace("[0, 0]", "[0, 2]"),
+        model.replace("[1, 0]", "[2, 0]"),
+        model.replace("[255, 0, 127]", "[255, 0]"),
+        model.replace("[[1.5, 2.5], []]", "[[1.5, 2.5]]"),
+        model.replace(r#""step": 0.5"#, r#""step": 1e39"#),
+    ] {
+        assert!(decode(invalid).unwrap().validate().is_err());
+    }
+}
+
+#[test]
+fn shared_leaf_probabilities() {
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let predictor: rf::Classifier = rf::Classifier::trainer()
+        .with_trees(20)
+        .with_threads(MAX_THREADS)
+        .train(&samples, &targets);
+    // Pure leaves refer to shared one-hot vectors, other leaves to distinct vectors of a tree.
+    // Storing a vector per leaf takes about 345 KB.
+    let config = bincode::config::standard();
+    let bytes = bincode::serde::encode_to_vec(&predictor, config).unwrap();
+    assert!(bytes.len() < 200_000);
+
+    let proba = predictor.proba(&samples, MAX_THREADS);
+    for p in proba.chunks(predictor.num_classes()) {
+        assert!((p.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+    }
+    let (decoded, _): (rf::Classifier, usize) =
+        bincode::serde::decode_from_slice(&bytes, config).unwrap();
+    assert_eq!(decoded.proba(&samples, MAX_THREADS), proba);
+}
+
+#[test]
+fn simplify_redundant_splits() {
+    let leaf = |v: u32| Box::new(TreeNode::Leaf(v));
+    let split = |feature, left, right| {
+        Box::new(TreeNode::Split {
+            feature,
+            threshold: 0.5,
+            left,
+            right,
+        })
+    };
+    let node = split(
+        0,
+        split(1, leaf(3), leaf(4)),
+        split(1, leaf(7), split(2, leaf(7), leaf(7))),
+    );
+    let expected = *split(0, split(1, leaf(3), leaf(4)), leaf(7));
+    let compact: CompactTree = decision_tree::build(&node);
+    let block: BlockTree = decision_tree::build(&node);
+    assert_eq!(compact.export(), expected);
+    assert_eq!(block.export(), expected);
+    let block: BlockTree = decision_tree::build(&block.export());
+    assert_eq!(block.num_blocks(), 1);
+
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let mut predictor = rf::Classifier::<BlockTree>::trainer()
+        .with_max_depth(8)
+        .with_trees(10)
+        .train(&samples, &targets);
+    let proba = predictor.proba(&samples, 1);
+    predictor.simplify();
+    assert_eq!(predictor.proba(&samples, 1), proba);
+
+    // Labels without source literals.
+    #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    enum Quality {
+        Low,
+        High,
+    }
+    let labels: Vec<Quality> = targets
+        .iter()
+        .map(|&t| if t > 5 { Quality::High } else { Quality::Low })
+        .collect();
+    let mut predictor = dt::Classifier::<CompactTree, Quality>::trainer()
+        .with_max_depth(8)
+        .train(&samples, &labels);
+    let predictions = predictor.predict_batch(&samples);
+    predictor.simplify();
+    assert_eq!(predictor.predict_batch(&samples), predictions);
+    let mut predictor = rf::Classifier::<BlockTree, Quality>::trainer()
+        .with_max_depth(8)
+        .with_trees(10)
+        .train(&samples, &labels);
+    let predictions = predictor.predict_batch(&samples, 1);
+    predictor.simplify();
+    assert_eq!(predictor.predict_batch(&samples, 1), predictions);
+
+    let (samples, targets) = load_dataset::<f32>("datasets/Folds5x2_pp.csv", ",", true);
+    let mut predictor = dt::Regressor::<CompactTree>::trainer()
+        .with_max_depth(10)
+        .train(&samples, &targets);
+    let values = predictor.predict_batch(&samples);
+    predictor.simplify();
+    assert_eq!(predictor.predict_batch(&samples), values);
+}
+
 fn f1score(pred: &[i64], target: &[i64]) -> f64 {
     let tp = pred
         .iter()
@@ -103,8 +920,8 @@ fn f1score(pred: &[i64], target: &[i64]) -> f64 {
         .count();
     let precision = tp as f64 / (tp as f64 + fp as f64);
     let recall = tp as f64 / (tp as f64 + fnn as f64);
-    let f1 = 2. * precision * recall / (precision + recall);
-    f1
+
+    2. * precision * recall / (precision + recall)
 }
 
 #[test]
@@ -168,19 +985,59 @@ fn random_forest_regressor_depth10() {
     assert!(mse < 15.0);
 }
 
+#[test]
+fn random_forest_regressor_random_subspace() {
+    let (samples, targets) = load_dataset::<f32>("datasets/Folds5x2_pp.csv", ",", true);
+    let (x_train, y_train, x_pred, y_ref) = split_dataset(&samples, &targets);
+    let predictor: rf::Regressor = rf::Regressor::trainer()
+        .with_max_depth(10)
+        .with_max_features_per_tree(MaxFeaturesPolicy::NUMBER(3))
+        .with_threads(MAX_THREADS)
+        .train(&x_train, &y_train);
+
+    let y_pred = predictor.predict_batch(&x_pred, MAX_THREADS);
+    let mse = mean_squared_error(&y_pred, &y_ref);
+    assert!(mse < 16.0);
+
+    // Each tree splits only the 3 columns drawn for it, negated features count as their columns.
+    fn split_features(node: &TreeNode, num_features: usize, features: &mut BTreeSet<usize>) {
+        if let TreeNode::Split {
+            feature,
+            left,
+            right,
+            ..
+        } = node
+        {
+            features.insert(*feature as usize % num_features);
+            split_features(left, num_features, features);
+            split_features(right, num_features, features);
+        }
+    }
+    let subsets: Vec<BTreeSet<usize>> = predictor
+        .trees()
+        .iter()
+        .map(|tree| {
+            let mut features = BTreeSet::new();
+            split_features(&tree.predictor().export(), 4, &mut features);
+            features
+        })
+        .collect();
+    assert!(subsets.iter().all(|s| s.len() == 3));
+    assert!(subsets.iter().any(|s| s != &subsets[0]));
+}
+
 fn mean_squared_error(v: &[f32], u: &[f32]) -> f64 {
     assert!(v.len() == u.len());
-    let mse = v
-        .iter()
+
+    v.iter()
         .zip(u.iter())
         .map(|(&x, &y)| (x - y) as f64 * (x - y) as f64)
         .sum::<f64>()
-        / v.len() as f64;
-    mse
+        / v.len() as f64
 }
 
-fn split_dataset<T: Copy>(x: &[f32], y: &[T]) -> (Vec<f32>, Vec<T>, Vec<f32>, Vec<T>) {
-    assert!(x.len() % y.len() == 0);
+fn split_dataset<T: Clone>(x: &[f32], y: &[T]) -> (Vec<f32>, Vec<T>, Vec<f32>, Vec<T>) {
+    assert!(x.len().is_multiple_of(y.len()));
     let features = x.len() / y.len();
 
     let x_train: Vec<f32> = x
@@ -194,7 +1051,7 @@ fn split_dataset<T: Copy>(x: &[f32], y: &[T]) -> (Vec<f32>, Vec<T>, Vec<f32>, Ve
         .iter()
         .enumerate()
         .filter(|(i, _)| i % 5 > 0)
-        .map(|(_, v)| *v)
+        .map(|(_, v)| v.clone())
         .collect();
 
     let x_pred: Vec<f32> = x
@@ -208,7 +1065,7 @@ fn split_dataset<T: Copy>(x: &[f32], y: &[T]) -> (Vec<f32>, Vec<T>, Vec<f32>, Ve
         .iter()
         .enumerate()
         .filter(|(i, _)| i % 5 == 0)
-        .map(|(_, v)| *v)
+        .map(|(_, v)| v.clone())
         .collect();
 
     (x_train, y_train, x_pred, y_ref)
@@ -256,3 +1113,763 @@ where
     }
     (samples, targets)
 }
+
+#[test]
+fn model_files() {
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let predictor = rf::Classifier::<CompactTree>::trainer()
+        .with_trees(10)
+        .with_threads(MAX_THREADS)
+        .train(&samples, &targets);
+    let mut bytes = Vec::new();
+    io::write(&predictor, &mut bytes).unwrap();
+    let loaded: rf::Classifier<CompactTree> = io::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(
+        loaded.proba(&samples, MAX_THREADS),
+        predictor.proba(&samples, MAX_THREADS)
+    );
+
+    let path = std::env::temp_dir().join("rafor_model_files.bin");
+    io::save(&predictor, &path).unwrap();
+    let header = io::read_header(&path).unwrap();
+    assert_eq!(header.version, io::FORMAT_VERSION);
+    assert_eq!(header.kind, io::ModelKind::FOREST);
+    assert_eq!(header.layout, io::Layout::COMPACT);
+    assert_eq!(header.num_features, 11);
+    assert!(matches!(
+        io::load::<rf::Classifier<BlockTree>>(&path),
+        Err(io::Error::Mismatch { .. })
+    ));
+    assert!(matches!(
+        io::load::<dt::Classifier<CompactTree>>(&path),
+        Err(io::Error::Mismatch { .. })
+    ));
+    std::fs::remove_file(&path).unwrap();
+
+    let last = bytes.len() - 1;
+    bytes[last] ^= 1;
+    assert!(matches!(
+        io::read::<rf::Classifier<CompactTree>>(&mut bytes.as_slice()),
+        Err(io::Error::Checksum)
+    ));
+    bytes[8] = 99;
+    assert!(matches!(
+        io::read::<rf::Classifier<CompactTree>>(&mut bytes.as_slice()),
+        Err(io::Error::UnsupportedVersion(99))
+    ));
+}
+
+#[test]
+fn legacy_model_files() {
+    // Before v0.4.0 a tree classifier was stored by bincode as
+    // ((proba, num_classes, tree, num_features), classes) with a probability row per leaf value.
+    let node = TreeNode::Split {
+        feature: 0,
+        threshold: 0.5,
+        left: Box::new(TreeNode::Leaf(0)),
+        right: Box::new(TreeNode::Leaf(1)),
+    };
+    let tree: CompactTree = decision_tree::build(&node);
+    let legacy = (
+        (vec![1.0f32, 0.0, 0.25, 0.75], 2usize, tree, 2usize),
+        crate::ClassesMapping::with_classes(&[3i64, 8]),
+    );
+    let bytes = bincode::serde::encode_to_vec(&legacy, bincode::config::standard()).unwrap();
+    let predictor: dt::Classifier<CompactTree> = io::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(predictor.predict_batch(&[0.2, 0.0, 0.9, 0.0]), [3, 8]);
+    assert_eq!(
+        predictor.proba(&[0.2, 0.0, 0.9, 0.0]),
+        [1.0, 0.0, 0.25, 0.75]
+    );
+
+    let mut migrated = Vec::new();
+    io::write(&predictor, &mut migrated).unwrap();
+    let loaded: dt::Classifier<CompactTree> = io::read(&mut migrated.as_slice()).unwrap();
+    assert_eq!(loaded.proba(&[0.2, 0.0, 0.9, 0.0]), [1.0, 0.0, 0.25, 0.75]);
+
+    // Self-describing formats of models without one-hot leaves omit them.
+    let mut value = serde_json::to_value(&predictor).unwrap();
+    let fields = value["classifier"].as_object_mut().unwrap();
+    assert_eq!(fields.remove("one_hot"), Some(serde_json::json!([])));
+    let deserialized: dt::Classifier<CompactTree> = serde_json::from_value(value).unwrap();
+    assert_eq!(deserialized, predictor);
+}
+
+#[test]
+fn validate_models() {
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let float_targets: Vec<f32> = targets.iter().map(|&t| t as f32).collect();
+    let classifier = rf::Classifier::<BlockTree>::trainer()
+        .with_trees(5)
+        .with_threads(MAX_THREADS)
+        .train(&samples, &targets);
+    assert_eq!(classifier.validate(), Ok(()));
+    let regressor = rf::Regressor::<CompactTree>::trainer()
+        .with_trees(5)
+        .with_threads(MAX_THREADS)
+        .train(&samples, &float_targets);
+    assert_eq!(regressor.validate(), Ok(()));
+    assert_eq!(
+        rf::Regressor::<CompactTree>::from_parts(Vec::new())
+            .validate()
+            .unwrap_err()
+            .0,
+        "forest has no trees"
+    );
+
+    // A tree referring to leaf value 2 of a model with 2 probability vectors, it splits the negated
+    // feature 0.
+    let node = TreeNode::Split {
+        feature: 2,
+        threshold: 0.5,
+        left: Box::new(TreeNode::Leaf(0)),
+        right: Box::new(TreeNode::Leaf(2)),
+    };
+    let tree: CompactTree = decision_tree::build(&node);
+    let model = (
+        (vec![1.0f32, 0.0, 0.25, 0.75], 2usize, tree.clone(), 2usize),
+        crate::ClassesMapping::with_classes(&[3i64, 8]),
+    );
+    let bytes = bincode::serde::encode_to_vec(&model, bincode::config::standard()).unwrap();
+    let Err(io::Error::Invalid(e)) = io::read::<dt::Classifier<CompactTree>>(&mut bytes.as_slice())
+    else {
+        panic!("invalid leaf value is not detected");
+    };
+    assert_eq!(e.0, "node 0: invalid leaf value 0x2");
+
+    // The same tree with a single feature, which has no feature 2 even when negated.
+    let model = (
+        (vec![1.0f32, 0.0, 0.5, 0.5, 0.0, 1.0], 2usize, tree, 1usize),
+        model.1,
+    );
+    let bytes = bincode::serde::encode_to_vec(&model, bincode::config::standard()).unwrap();
+    assert!(matches!(
+        io::read::<dt::Classifier<CompactTree>>(&mut bytes.as_slice()),
+        Err(io::Error::Invalid(_))
+    ));
+
+    // A block tree of a regressor whose second block refers to itself.
+    let block = |offset: u32| ([0.5f32; 7], [0u16; 7], [offset, 0, 0, 0]);
+    let model = (((vec![block(1), block(1), block(0)],), 1usize),);
+    let bytes = bincode::serde::encode_to_vec(&model, bincode::config::standard()).unwrap();
+    let Err(io::Error::Invalid(e)) = io::read::<dt::Regressor<BlockTree>>(&mut bytes.as_slice())
+    else {
+        panic!("cycle is not detected");
+    };
+    assert_eq!(e.0, "block 1: invalid child blocks offset 1");
+}
+
+#[test]
+fn archived_forests() {
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let float_targets: Vec<f32> = targets.iter().map(|&t| t as f32).collect();
+    let classifier = rf::Classifier::<BlockTree>::trainer()
+        .with_trees(10)
+        .with_threads(MAX_THREADS)
+        .train(&samples, &targets);
+    let regressor = rf::Regressor::<CompactTree>::trainer()
+        .with_trees(10)
+        .with_threads(MAX_THREADS)
+        .train(&samples, &float_targets);
+    let map = |path: &std::path::Path| {
+        // SAFETY: files of the test are not modified while mapped.
+        unsafe { archive::Mmap::map(&std::fs::File::open(path).unwrap()).unwrap() }
+    };
+
+    let path = std::env::temp_dir().join("rafor_archived_classifier.bin");
+    archive::save(&classifier, &path).unwrap();
+    let mmap = map(&path);
+    let archived = archive::ArchivedClassifier::<BlockTree>::new(&mmap).unwrap();
+    assert_eq!(archived.num_trees(), 10);
+    assert_eq!(archived.num_classes(), classifier.num_classes());
+    assert_eq!(
+        archived.proba(&samples, MAX_THREADS),
+        classifier.proba(&samples, MAX_THREADS)
+    );
+    assert_eq!(
+        archived.predict_batch(&samples, 1),
+        classifier.predict_batch(&samples, 1)
+    );
+    assert!(matches!(
+        archive::ArchivedClassifier::<CompactTree>::new(&mmap),
+        Err(io::Error::Mismatch { .. })
+    ));
+    assert!(matches!(
+        archive::ArchivedClassifier::<BlockTree>::new(&mmap[..mmap.len() - 64]),
+        Err(io::Error::Codec(_))
+    ));
+    // SAFETY: the archive is written by `save` above.
+    let trusted = unsafe { archive::ArchivedClassifier::<BlockTree>::new_unchecked(&mmap) };
+    assert_eq!(
+        trusted.unwrap().proba(&samples, 1),
+        classifier.proba(&samples, 1)
+    );
+    std::fs::remove_file(&path).unwrap();
+
+    let path = std::env::temp_dir().join("rafor_archived_regressor.bin");
+    archive::save(&regressor, &path).unwrap();
+    let mmap = map(&path);
+    let archived = archive::ArchivedRegressor::<CompactTree>::new(&mmap).unwrap();
+    let mut predictions = vec![0.; targets.len()];
+    archived.predict_into(&samples, &mut predictions);
+    assert_eq!(predictions, regressor.predict_batch(&samples, 1));
+    // SAFETY: the archive is written by `save` above.
+    let trusted = unsafe { archive::ArchivedRegressor::<CompactTree>::new_unchecked(&mmap) };
+    assert_eq!(
+        trusted.unwrap().predict_batch(&samples, 1),
+        regressor.predict_batch(&samples, 1)
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn json_models() {
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let float_targets: Vec<f32> = targets.iter().map(|&t| t as f32).collect();
+    let forest = rf::Classifier::<CompactTree>::trainer()
+        .with_trees(5)
+        .with_threads(MAX_THREADS)
+        .train(&samples, &targets);
+    let imported: rf::Classifier<BlockTree> = json::from_str(&json::to_string(&forest)).unwrap();
+    assert_eq!(
+        imported.proba(&samples, MAX_THREADS),
+        forest.proba(&samples, MAX_THREADS)
+    );
+
+    let tree = dt::Regressor::<BlockTree>::trainer()
+        .with_max_depth(8)
+        .train(&samples, &float_targets);
+    let text = json::to_string_pretty(&tree);
+    let document: serde_json::Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(document["kind"], "tree");
+    assert_eq!(document["task"], "regressor");
+    assert_eq!(document["trees"][0]["nodes"][0]["id"], 0);
+    let imported: dt::Regressor<CompactTree> = json::from_str(&text).unwrap();
+    assert_eq!(
+        imported.predict_batch(&samples),
+        tree.predict_batch(&samples)
+    );
+    assert!(matches!(
+        json::from_str::<rf::Regressor>(&text),
+        Err(io::Error::Codec(_))
+    ));
+
+    let tree = r#"{"format": "rafor", "version": 1, "kind": "tree", "task": "classifier",
+        "num_features": 2, "classes": ["no", "yes"], "trees": [{"nodes": [
+        {"id": 0, "feature": 1, "threshold": 0.5, "left": 2, "right": 1},
+        {"id": 1, "proba": [0.25, 0.75], "samples": 4},
+        {"id": 2, "proba": [1.0, 0.0]}]}]}"#;
+    let imported: dt::Classifier<BlockTree, String> = json::from_str(tree).unwrap();
+    assert_eq!(imported.predict_batch(&[0.0, 0.0, 0.0, 1.0]), ["no", "yes"]);
+    let cyclic = tree.replace(r#""right": 1"#, r#""right": 0"#);
+    assert!(json::from_str::<dt::Classifier<BlockTree, String>>(&cyclic).is_err());
+    let bad_feature = tree.replace(r#""feature": 1"#, r#""feature": 2"#);
+    assert!(matches!(
+        json::from_str::<dt::Classifier<BlockTree, String>>(&bad_feature),
+        Err(io::Error::Invalid(_))
+    ));
+
+    // A chain of `depth` splits, where each split has a leaf on the left.
+    let chain = |depth: usize| {
+        let mut nodes: Vec<String> = (0..depth)
+            .flat_map(|i| {
+                let (split, leaf) = (2 * i, 2 * i + 1);
+                [
+                    format!(
+                        r#"{{"id": {split}, "feature": 0, "threshold": {i}, "left": {leaf},
+                        "right": {}}}"#,
+                        split + 2
+                    ),
+                    format!(r#"{{"id": {leaf}, "value": {i}}}"#),
+                ]
+            })
+            .collect();
+        nodes.push(format!(r#"{{"id": {}, "value": {depth}}}"#, 2 * depth));
+        format!(
+            r#"{{"format": "rafor", "version": 1, "kind": "tree", "task": "regressor",
+            "num_features": 1, "trees": [{{"nodes": [{}]}}]}}"#,
+            nodes.join(",")
+        )
+    };
+    let imported: dt::Regressor<CompactTree> = json::from_str(&chain(1024)).unwrap();
+    assert_eq!(
+        imported.predict_batch(&[0.5, 1023.5, 3000.]),
+        [1., 1024., 1024.]
+    );
+    assert_eq!(
+        json::from_str::<dt::Regressor<CompactTree>>(&json::to_string(&imported)).unwrap(),
+        imported
+    );
+    assert!(matches!(
+        json::from_str::<dt::Regressor<CompactTree>>(&chain(1025)),
+        Err(io::Error::Codec(_))
+    ));
+}
+
+#[test]
+fn tree_rendering() {
+    let tree = r#"{"format": "rafor", "version": 1, "kind": "tree", "task": "classifier",
+        "num_features": 2, "classes": ["no", "yes"], "trees": [{"nodes": [
+        {"id": 0, "feature": 1, "threshold": 0.5, "left": 1, "right": 2},
+        {"id": 1, "proba": [1.0, 0.0]},
+        {"id": 2, "proba": [0.25, 0.75]}]}]}"#;
+    let classifier: dt::Classifier<BlockTree, String> = json::from_str(tree).unwrap();
+    assert_eq!(
+        classifier.to_text_rules(Some(&["alcohol", "sugar"])),
+        "|--- sugar <= 0.5\n\
+         |   |--- class: \"no\", proba: [1.000, 0.000]\n\
+         |--- sugar >  0.5\n\
+         |   |--- class: \"yes\", proba: [0.250, 0.750]\n"
+    );
+    let dot = classifier.to_dot(None);
+    assert!(dot.starts_with("digraph Tree {"));
+    assert!(dot.contains("0 [label=\"x[1] <= 0.5\"] ;"));
+    assert!(dot.contains("1 [label=\"class = \\\"no\\\"\\nproba = [1.000, 0.000]\"] ;"));
+    assert!(dot.contains("0 -> 2 [labeldistance=2.5, labelangle=-45, headlabel=\"False\"] ;"));
+
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let float_targets: Vec<f32> = targets.iter().map(|&t| t as f32).collect();
+    let forest = rf::Regressor::<BlockTree>::trainer()
+        .with_trees(3)
+        .with_max_depth(3)
+        .with_threads(MAX_THREADS)
+        .train(&samples, &float_targets);
+    let rules = forest.tree_to_text_rules(2, None);
+    let leaves = rules.lines().filter(|l| l.contains("value: ")).count();
+    let splits = rules.lines().filter(|l| l.contains(" <= ")).count();
+    assert_eq!(leaves, splits + 1);
+    assert!(rules.lines().all(|l| l.matches("|   ").count() <= 3));
+    assert_eq!(
+        forest.tree_to_dot(2, None).matches(" -> ").count(),
+        2 * splits
+    );
+}
+
+#[test]
+fn sklearn_models() {
+    // Classes are given out of order, the split threshold lies between two adjacent f32 values.
+    let below = 0.1f32;
+    let above = below.next_up();
+    let midpoint = (below as f64 + above as f64) / 2.0;
+    let dump = format!(
+        r#"{{"estimator": "DecisionTreeClassifier", "n_features_in": 2, "classes": ["yes", "no"],
+        "trees": [{{"children_left": [1, -1, -1], "children_right": [2, -1, -1],
+        "feature": [1, -2, -2], "threshold": [{midpoint}, -2.0, -2.0],
+        "value": [[[3.0, 5.0]], [[1.0, 3.0]], [[2.0, 2.0]]]}}]}}"#
+    );
+    let tree: dt::Classifier<BlockTree, String> = sklearn::from_str(&dump).unwrap();
+    assert_eq!(
+        tree.proba(&[0.0, below, 0.0, above]),
+        [0.75, 0.25, 0.5, 0.5]
+    );
+    assert_eq!(tree.predict_batch(&[0.0, below]), ["no"]);
+    assert!(matches!(
+        sklearn::from_str::<rf::Classifier<BlockTree, String>>(&dump),
+        Err(io::Error::Codec(_))
+    ));
+    let bad_feature = dump.replace(r#""feature": [1,"#, r#""feature": [2,"#);
+    assert!(matches!(
+        sklearn::from_str::<dt::Classifier<CompactTree, String>>(&bad_feature),
+        Err(io::Error::Invalid(_))
+    ));
+    let multi_output = dump.replace("[[1.0, 3.0]]", "[[1.0, 3.0], [2.0, 2.0]]");
+    assert!(sklearn::from_str::<dt::Classifier<BlockTree, String>>(&multi_output).is_err());
+    let missing_right = dump.replace(
+        r#""value":"#,
+        r#""missing_go_to_left": [0, 0, 0], "value":"#,
+    );
+    let tree: dt::Classifier<BlockTree, String> = sklearn::from_str(&missing_right).unwrap();
+    assert_eq!(tree.proba(&[0.0, f32::NAN]), [0.5, 0.5]);
+    let missing_left = missing_right.replace("[0, 0, 0]", "[1, 0, 0]");
+    let tree: dt::Classifier<CompactTree, String> = sklearn::from_str(&missing_left).unwrap();
+    assert_eq!(
+        tree.proba(&[0.0, f32::NAN, 0.0, below, 0.0, above]),
+        [0.75, 0.25, 0.75, 0.25, 0.5, 0.5]
+    );
+    let exported: serde_json::Value = serde_json::from_str(&json::to_string(&tree)).unwrap();
+    assert_eq!(exported["trees"][0]["nodes"][0]["missing_left"], true);
+    assert_eq!(exported["trees"][0]["nodes"][0]["feature"], 1);
+    let reimported: dt::Classifier<BlockTree, String> =
+        json::from_str(&json::to_string(&tree)).unwrap();
+    assert_eq!(
+        reimported.proba(&[0.0, f32::NAN, 0.0, above]),
+        [0.75, 0.25, 0.5, 0.5]
+    );
+
+    let leaf = |value: f32| sklearn::SklearnTree {
+        children_left: vec![-1],
+        children_right: vec![-1],
+        feature: vec![-2],
+        threshold: vec![-2.0],
+        value: vec![vec![vec![value as f64]]],
+        missing_go_to_left: None,
+    };
+    let mut split = leaf(0.0);
+    split.children_left = vec![1, -1, -1];
+    split.children_right = vec![2, -1, -1];
+    split.feature = vec![0, -2, -2];
+    split.threshold = vec![1.5, -2.0, -2.0];
+    split.value = vec![vec![vec![2.0]], vec![vec![1.0]], vec![vec![3.0]]];
+    let dump = sklearn::Dump {
+        estimator: "RandomForestRegressor".to_string(),
+        n_features_in: 1,
+        classes: None,
+        trees: vec![split, leaf(5.0)],
+    };
+    let forest: rf::Regressor<CompactTree> = sklearn::SklearnModel::from_dump(&dump).unwrap();
+    assert_eq!(forest.predict_batch(&[1.0, 2.0], MAX_THREADS), [3.0, 4.0]);
+
+    // Some splits of the forest send missing values left, nulls of samples are missing values.
+    let dump = read_to_string("datasets/models/sklearn_forest.json").unwrap();
+    let forest: rf::Classifier<CompactTree> = sklearn::from_str(&dump).unwrap();
+    let expected: serde_json::Value =
+        serde_json::from_str(&read_to_string("datasets/models/sklearn_forest_proba.json").unwrap())
+            .unwrap();
+    let values = |key: &str| -> Vec<f32> {
+        let rows = expected[key].as_array().unwrap().iter();
+        rows.flat_map(|row| row.as_array().unwrap())
+            .map(|x| x.as_f64().map_or(f32::NAN, |x| x as f32))
+            .collect()
+    };
+    let (samples, proba) = (values("samples"), values("proba"));
+    let close = |found: &[f32], tolerance: f32| {
+        found.len() == proba.len()
+            && found
+                .iter()
+                .zip(proba.iter())
+                .all(|(x, y)| (x - y).abs() <= tolerance)
+    };
+    assert!(close(&forest.proba(&samples, MAX_THREADS), 1e-6));
+    assert!(close(&forest.quick_scorer().proba(&samples), 1e-6));
+    let quantized = forest.quantize(&Quantization::default());
+    // Leaves of 8 bits are within 1/255 of probabilities.
+    assert!(close(&quantized.proba(&samples), 1. / 255.));
+    let imported: rf::Classifier<BlockTree> = json::from_str(&json::to_string(&forest)).unwrap();
+    assert!(close(&imported.proba(&samples, MAX_THREADS), 1e-6));
+    let rules = forest.tree_to_text_rules(0, None);
+    assert!(rules.starts_with("|--- x[0] <= 10.55 or NaN\n"));
+    assert!(rules.contains("|--- x[0] >  10.55\n"));
+    assert!(forest
+        .to_rust_source("")
+        .contains("if -x[0] <= -10.550001 {"));
+}
+
+#[test]
+fn boosted_models() {
+    // Exports are compared with files written by earlier versions of rafor, so that changes of the
+    // exported text are noticed. Models saved in the layouts of XGBoost and LightGBM are checked
+    // against their recorded predictions below.
+    let reference = |name: &str| read_to_string(format!("datasets/models/{name}")).unwrap();
+    // Missing values go left in the first tree and right in the second one.
+    let xgboost = r#"{"version": [2, 1, 0], "learner": {
+        "gradient_booster": {"name": "gbtree", "model": {
+            "gbtree_model_param": {"num_parallel_tree": "1", "num_trees": "2"},
+            "tree_info": [0, 0],
+            "trees": [
+                {"id": 0, "tree_param": {"num_nodes": "3"}, "left_children": [1, -1, -1],
+                 "right_children": [2, -1, -1], "split_indices": [0, 0, 0],
+                 "split_conditions": [0.5, 0.4, -0.2], "default_left": [1, 0, 0]},
+                {"id": 1, "tree_param": {"num_nodes": "3"}, "left_children": [1, -1, -1],
+                 "right_children": [2, -1, -1], "split_indices": [1, 0, 0],
+                 "split_conditions": [1.0, 0.1, 0.3], "default_left": [false, false, false]}]}},
+        "learner_model_param": {"base_score": "5E-1", "num_class": "0", "num_feature": "2"},
+        "objective": {"name": "binary:logistic", "reg_loss_param": {"scale_pos_weight": "1"}}}}"#;
+    let samples = [0.2, 0.0, 0.5, 1.0, f32::NAN, f32::NAN];
+    let raw = [0.4f32 + 0.1, -0.2f32 + 0.3, 0.4f32 + 0.3];
+    let booster: Booster = Booster::from_xgboost_json(xgboost).unwrap();
+    assert_eq!(booster.objective(), Objective::LOGISTIC);
+    assert_eq!(booster.predict_raw(&samples, MAX_THREADS), raw);
+    let proba: Vec<f32> = raw.iter().map(|x| 1. / (1. + (-x).exp())).collect();
+    assert_eq!(booster.predict(&samples, 1), proba);
+    let exported: Booster<CompactTree> =
+        Booster::from_xgboost_json(&booster.to_xgboost_json()).unwrap();
+    assert_eq!(exported.predict_raw(&samples, 1), raw);
+    let exported: Booster = Booster::from_lightgbm_text(&booster.to_lightgbm_text()).unwrap();
+    assert_eq!(exported.predict(&samples, 1), proba);
+    assert_eq!(booster.to_xgboost_json(), reference("binary_xgboost.json"));
+    assert_eq!(booster.to_lightgbm_text(), reference("binary_lightgbm.txt"));
+    let cyclic = xgboost.replace(
+        "[1, -1, -1],\n                 \"right_children\": [2,",
+        "[0, -1, -1],\n                 \"right_children\": [2,",
+    );
+    assert!(matches!(
+        Booster::<BlockTree>::from_xgboost_json(&cyclic),
+        Err(io::Error::Codec(_))
+    ));
+
+    // Three classes, zeros replace missing values in the split of the first tree.
+    let lightgbm = "tree\nversion=v4\nnum_class=3\nnum_tree_per_iteration=3\nlabel_index=0\n\
+        max_feature_idx=0\nobjective=multiclass num_class:3\nfeature_names=x\nfeature_infos=none\n\n\
+        Tree=0\nnum_leaves=3\nnum_cat=0\nsplit_feature=0 0\nthreshold=1 -1\ndecision_type=0 8\n\
+        left_child=1 -1\nright_child=-3 -2\nleaf_value=1 2 3\nshrinkage=1\n\n\
+        Tree=1\nnum_leaves=1\nnum_cat=0\nleaf_value=0.5\n\n\
+        Tree=2\nnum_leaves=1\nnum_cat=0\nleaf_value=-0.5\n\n\
+        Tree=3\nnum_leaves=1\nnum_cat=0\nleaf_value=0.25\n\n\
+        end of trees\n";
+    let booster: Booster = Booster::from_lightgbm_text(lightgbm).unwrap();
+    assert_eq!((booster.num_outputs(), booster.num_trees()), (3, 4));
+    let samples = [-2.0, 0.0, f32::NAN, 5.0];
+    let raw = booster.predict_raw(&samples, MAX_THREADS);
+    assert_eq!(
+        raw,
+        [1.25, 0.5, -0.5, 2.25, 0.5, -0.5, 2.25, 0.5, -0.5, 3.25, 0.5, -0.5]
+    );
+    let proba = booster.predict(&samples, 1);
+    for p in proba.chunks(3) {
+        assert!((p.iter().sum::<f32>() - 1.).abs() < 1e-6);
+        assert!(p[0] > p[1] && p[1] > p[2]);
+    }
+    let exported: Booster<CompactTree> =
+        Booster::from_xgboost_json(&booster.to_xgboost_json()).unwrap();
+    assert_eq!(exported.predict_raw(&samples, 1), raw);
+    let exported: Booster = Booster::from_lightgbm_text(&booster.to_lightgbm_text()).unwrap();
+    assert_eq!(exported.predict_raw(&samples, 1), raw);
+    assert_eq!(
+        booster.to_xgboost_json(),
+        reference("multiclass_xgboost.json")
+    );
+    assert_eq!(
+        booster.to_lightgbm_text(),
+        reference("multiclass_lightgbm.txt")
+    );
+    for (from, to) in [
+        ("decision_type=0 8", "decision_type=4 8"),
+        ("num_cat=0", "num_cat=1"),
+    ] {
+        assert!(matches!(
+            Booster::<BlockTree>::from_lightgbm_text(&lightgbm.replacen(from, to, 1)),
+            Err(io::Error::Codec(_))
+        ));
+    }
+
+    // Models in the layouts saved by XGBoost 1.7 and 2.1 and LightGBM 4, with boolean
+    // `default_left`, bracketed base scores, tree sizes, feature ranges and parameters.
+    let expected: serde_json::Value =
+        serde_json::from_str(&reference("boosted_predictions.json")).unwrap();
+    let values = |value: &serde_json::Value| -> Vec<f32> {
+        let value = value.as_array().unwrap().iter();
+        value
+            .flat_map(|v| v.as_array().map_or(vec![v.clone()], |row| row.clone()))
+            .map(|x| x.as_f64().map_or(f32::NAN, |x| x as f32))
+            .collect()
+    };
+    let samples = values(&expected["samples"]);
+    let close = |found: Vec<f32>, name: &str| {
+        let expected = values(&expected[name]);
+        found.len() == expected.len()
+            && found
+                .iter()
+                .zip(expected.iter())
+                .all(|(x, y)| (x - y).abs() < 1e-6)
+    };
+    for name in ["xgboost_binary.json", "xgboost_multiclass.json"] {
+        let block: Booster = Booster::from_xgboost_json(&reference(name)).unwrap();
+        let compact: Booster<CompactTree> = Booster::from_xgboost_json(&reference(name)).unwrap();
+        assert!(close(block.predict(&samples, MAX_THREADS), name));
+        assert!(close(compact.predict(&samples, 1), name));
+    }
+    let name = "lightgbm_regression.txt";
+    let booster: Booster = Booster::from_lightgbm_text(&reference(name)).unwrap();
+    assert_eq!(booster.objective(), Objective::IDENTITY);
+    assert!(close(booster.predict(&samples, MAX_THREADS), name));
+}
+
+// A field value of a protobuf message: a varint or bytes of a length-delimited value.
+enum Proto<'a> {
+    Int(u64),
+    Bytes(&'a [u8]),
+}
+
+fn read_varint(bytes: &mut &[u8]) -> u64 {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let b = bytes[0];
+        *bytes = &bytes[1..];
+        value |= ((b & 0x7f) as u64) << shift;
+        if b < 0x80 {
+            break;
+        }
+    }
+    value
+}
+
+fn proto_fields(mut bytes: &[u8]) -> Vec<(u64, Proto<'_>)> {
+    let mut fields = Vec::new();
+    while !bytes.is_empty() {
+        let key = read_varint(&mut bytes);
+        let value = match key & 7 {
+            0 => Proto::Int(read_varint(&mut bytes)),
+            2 => {
+                let len = read_varint(&mut bytes) as usize;
+                let (value, rest) = bytes.split_at(len);
+                bytes = rest;
+                Proto::Bytes(value)
+            }
+            wire_type => panic!("Unexpected wire type {wire_type}."),
+        };
+        fields.push((key >> 3, value));
+    }
+    fields
+}
+
+fn proto_bytes<'a>(fields: &[(u64, Proto<'a>)], field: u64) -> Vec<&'a [u8]> {
+    fields
+        .iter()
+        .filter_map(|(f, v)| match v {
+            Proto::Bytes(b) if *f == field => Some(*b),
+            _ => None,
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct OnnxAttribute {
+    s: String,
+    floats: Vec<f32>,
+    ints: Vec<i64>,
+    strings: Vec<String>,
+}
+
+// Returns the op type, attributes and output names of the single node of an ONNX model.
+fn onnx_node(model: &[u8]) -> (String, HashMap<String, OnnxAttribute>, Vec<String>) {
+    let text = |b: &[u8]| String::from_utf8(b.to_vec()).unwrap();
+    let graph = proto_fields(proto_bytes(&proto_fields(model), 7)[0]);
+    let node = proto_fields(proto_bytes(&graph, 1)[0]);
+    let outputs = proto_bytes(&node, 2).into_iter().map(text).collect();
+    let mut attributes = HashMap::new();
+    for attribute in proto_bytes(&node, 5) {
+        let fields = proto_fields(attribute);
+        let mut a = OnnxAttribute::default();
+        for (field, value) in fields.iter() {
+            match (field, value) {
+                (3, Proto::Int(i)) => a.ints.push(*i as i64),
+                (4, Proto::Bytes(b)) => a.s = text(b),
+                (7, Proto::Bytes(b)) => {
+                    let floats = b
+                        .chunks(4)
+                        .map(|c| f32::from_le_bytes(c.try_into().unwrap()));
+                    a.floats = floats.collect();
+                }
+                (8, Proto::Bytes(b)) => {
+                    let mut b = *b;
+                    while !b.is_empty() {
+                        a.ints.push(read_varint(&mut b) as i64);
+                    }
+                }
+                (9, Proto::Bytes(b)) => a.strings.push(text(b)),
+                _ => {}
+            }
+        }
+        attributes.insert(text(proto_bytes(&fields, 1)[0]), a);
+    }
+    (text(proto_bytes(&node, 4)[0]), attributes, outputs)
+}
+
+// Evaluates a tree ensemble node, returns sums of weights of reached leaves for each output of
+// each sample. `prefix` is "class" or "target".
+fn eval_tree_ensemble(
+    attributes: &HashMap<String, OnnxAttribute>,
+    prefix: &str,
+    dataset: &[f32],
+    num_features: usize,
+    num_outputs: usize,
+) -> Vec<f32> {
+    let ints = |name: &str| &attributes[name].ints;
+    let nodes: HashMap<(i64, i64), usize> = ints("nodes_treeids")
+        .iter()
+        .zip(ints("nodes_nodeids"))
+        .enumerate()
+        .map(|(i, (&t, &n))| ((t, n), i))
+        .collect();
+    let mut trees = ints("nodes_treeids").clone();
+    trees.dedup();
+    let mut weights: HashMap<(i64, i64), Vec<(usize, f32)>> = HashMap::new();
+    for i in 0..ints(&format!("{prefix}_ids")).len() {
+        let leaf = (
+            ints(&format!("{prefix}_treeids"))[i],
+            ints(&format!("{prefix}_nodeids"))[i],
+        );
+        let output = ints(&format!("{prefix}_ids"))[i] as usize;
+        let weight = attributes[&format!("{prefix}_weights")].floats[i];
+        weights.entry(leaf).or_default().push((output, weight));
+    }
+    let mut result = vec![0.; dataset.len() / num_features * num_outputs];
+    for (sample, out) in dataset
+        .chunks(num_features)
+        .zip(result.chunks_mut(num_outputs))
+    {
+        for &tree in trees.iter() {
+            let mut node = 0;
+            loop {
+                let i = nodes[&(tree, node)];
+                if attributes["nodes_modes"].strings[i] == "LEAF" {
+                    break;
+                }
+                let x = sample[ints("nodes_featureids")[i] as usize];
+                node = if x <= attributes["nodes_values"].floats[i] {
+                    ints("nodes_truenodeids")[i]
+                } else {
+                    ints("nodes_falsenodeids")[i]
+                };
+            }
+            for &(output, weight) in weights[&(tree, node)].iter() {
+                out[output] += weight;
+            }
+        }
+    }
+    result
+}
+
+#[test]
+fn onnx_models() {
+    let (samples, targets) = load_dataset::<i64>("datasets/winequality-red.csv", ";", true);
+    let float_targets: Vec<f32> = targets.iter().map(|&t| t as f32).collect();
+    let num_features = 11;
+
+    let forest = rf::Classifier::<CompactTree>::trainer()
+        .with_trees(10)
+        .with_max_depth(8)
+        .with_threads(MAX_THREADS)
+        .train(&samples, &targets);
+    let (op_type, attributes, outputs) = onnx_node(&forest.to_onnx());
+    assert_eq!(op_type, "TreeEnsembleClassifier");
+    assert_eq!(outputs, ["label", "probabilities"]);
+    assert_eq!(attributes["post_transform"].s, "NONE");
+    assert_eq!(attributes["classlabels_int64s"].ints, [3, 4, 5, 6, 7, 8]);
+    let num_classes = forest.num_classes();
+    let proba = eval_tree_ensemble(&attributes, "class", &samples, num_features, num_classes);
+    let expected = forest.proba(&samples, MAX_THREADS);
+    assert!(proba
+        .iter()
+        .zip(expected.iter())
+        .all(|(p, e)| (p - e).abs() < 1e-5));
+
+    let labels = ["low", "high"].map(String::from);
+    let string_targets: Vec<String> = targets
+        .iter()
+        .map(|&t| labels[(t > 5) as usize].clone())
+        .collect();
+    let tree = dt::Classifier::<BlockTree, String>::trainer()
+        .with_max_depth(6)
+        .train(&samples, &string_targets);
+    let (_, attributes, _) = onnx_node(&tree.to_onnx());
+    assert_eq!(attributes["classlabels_strings"].strings, ["high", "low"]);
+    let proba = eval_tree_ensemble(&attributes, "class", &samples, num_features, 2);
+    assert_eq!(proba, tree.proba(&samples));
+
+    let forest = rf::Regressor::<BlockTree>::trainer()
+        .with_trees(10)
+        .with_max_depth(8)
+        .with_threads(MAX_THREADS)
+        .train(&samples, &float_targets);
+    let (op_type, attributes, outputs) = onnx_node(&forest.to_onnx());
+    assert_eq!(op_type, "TreeEnsembleRegressor");
+    assert_eq!(outputs, ["variable"]);
+    assert_eq!(attributes["aggregate_function"].s, "AVERAGE");
+    let sums = eval_tree_ensemble(&attributes, "target", &samples, num_features, 1);
+    let expected = forest.predict_batch(&samples, MAX_THREADS);
+    assert!(sums
+        .iter()
+        .zip(expected.iter())
+        .all(|(s, e)| (s / 10. - e).abs() < 1e-4));
+}
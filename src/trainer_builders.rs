@@ -23,6 +23,13 @@ pub trait CommonTrainerBuilder: TrainConfigProvider {
         self
     }
 
+    /// Disables considering additional features when no split found in `max_features` features
+    /// (`strict_max_features`).
+    fn with_strict_max_features(&mut self, strict: bool) -> &mut Self {
+        self.train_config().strict_max_features = strict;
+        self
+    }
+
     /// Sets minimal samples for splitting the node (`min_samples_split`).
     fn with_min_samples_split(&mut self, num_samples: usize) -> &mut Self {
         self.train_config().min_samples_split = num_samples;
@@ -58,4 +65,10 @@ pub trait EnsembleTrainerBuilder: EnsembleConfigProvider + CommonTrainerBuilder
         self.ensemble_config().num_trees = num_trees;
         self
     }
+
+    /// Sets the number of features randomly drawn for each tree (`max_features_per_tree`).
+    fn with_max_features_per_tree(&mut self, max_features: MaxFeaturesPolicy) -> &mut Self {
+        self.ensemble_config().max_features_per_tree = max_features;
+        self
+    }
 }
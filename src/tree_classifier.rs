@@ -1,8 +1,10 @@
 use crate::{
     classify,
-    decision_tree::{self, BlockTree, ClassifierModel, Predictor},
+    codegen::{self, Language, SourceLiteral},
+    decision_tree::{self, BlockTree, ClassifierModel, Predictor, Validate},
     trainer_builders::*,
-    ClassDecode, ClassesMapping, Trainset,
+    tree_render, ClassDecode, ClassTarget, ClassesMapping, Export, InvalidModel, Label, Trainset,
+    UnknownLabel,
 };
 use argminmax::ArgMinMax;
 use serde::{Deserialize, Serialize};
@@ -14,6 +16,7 @@ use serde::{Deserialize, Serialize};
 /// ```text
 /// max_depth: usize::MAX,
 /// max_features: NumFeatures::NUMBER(usize::MAX),
+/// strict_max_features: false,
 /// seed: 42,
 /// min_samples_leaf: 1,
 /// min_samples_split: 2,
@@ -30,48 +33,103 @@ use serde::{Deserialize, Serialize};
 /// assert_eq!(&predictions, &[1, 5, 1]);
 /// ```
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct Classifier<P: Predictor = BlockTree> {
+pub struct Classifier<P: Predictor = BlockTree, L = i64> {
     classifier: ClassifierModel<P>,
-    classes_map: ClassesMapping,
+    classes_map: ClassesMapping<L>,
 }
 
 /// A trainer for tree classifier.
 #[derive(Clone, PartialEq, Debug)]
-pub struct Trainer<P: Predictor> {
+pub struct Trainer<P: Predictor, L = i64> {
     config: decision_tree::TrainConfig,
-    _marker: std::marker::PhantomData<P>,
+    classes: Vec<L>,
+    _marker: std::marker::PhantomData<(P, L)>,
 }
 
-impl<P: Predictor> TrainConfigProvider for Trainer<P> {
+impl<P: Predictor, L> TrainConfigProvider for Trainer<P, L> {
     fn train_config(&mut self) -> &mut decision_tree::TrainConfig {
         &mut self.config
     }
 }
 
-impl<P: Predictor> CommonTrainerBuilder for Trainer<P> {}
+impl<P: Predictor, L> CommonTrainerBuilder for Trainer<P, L> {}
+
+impl<P: Predictor, L: Label> Trainer<P, L> {
+    /// Declares all classes of a classifier. The classes which are absent in training labels get
+    /// zero probabilities, so `proba` has the same columns for all trained models. By default
+    /// the classes are taken from training labels.
+    pub fn with_classes(&mut self, classes: &[L]) -> &mut Self {
+        self.classes = classes.to_vec();
+        self
+    }
 
-impl<P: Predictor> Trainer<P> {
     /// Trains a classifier tree with dataset given by a slice of length divisible by targets.len().
-    pub fn train(&self, data: &[f32], labels: &[i64]) -> Classifier<P> {
-        let (classes_map, encoded_labels) = ClassesMapping::with_encode(labels);
+    /// Panics if classes are declared and a label is not one of them, see `try_train`.
+    pub fn train(&self, data: &[f32], labels: &[L]) -> Classifier<P, L> {
+        self.try_train(data, labels)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Same as `train`, but returns an error if classes are declared and a label is not one of
+    /// them.
+    pub fn try_train(
+        &self,
+        data: &[f32],
+        labels: &[L],
+    ) -> Result<Classifier<P, L>, UnknownLabel<L>> {
+        let classes = if self.classes.is_empty() {
+            labels
+        } else {
+            &self.classes
+        };
+        let classes_map = ClassesMapping::with_classes(classes);
+        let encoded_labels: Vec<ClassTarget> = classes_map
+            .encode(labels)?
+            .into_iter()
+            .map(|c| c as ClassTarget)
+            .collect();
         let ts = Trainset::with_transposed(data, &encoded_labels);
-        Classifier {
+        Ok(Classifier {
             classifier: ClassifierModel::train(&ts, classes_map.num_classes(), &self.config),
             classes_map,
-        }
+        })
     }
 }
 
-impl<P: Predictor> Classifier<P> {
+impl<P: Predictor, L: Label> Classifier<P, L> {
+    pub(crate) fn from_parts(
+        classifier: ClassifierModel<P>,
+        classes_map: ClassesMapping<L>,
+    ) -> Self {
+        Self {
+            classifier,
+            classes_map,
+        }
+    }
+
     /// Predicts classes for a set of samples.
     /// Dataset is a vector of floats with length multiple of num_features().
-    pub fn predict_batch(&self, dataset: &[f32]) -> Vec<i64> {
+    pub fn predict_batch(&self, dataset: &[f32]) -> Vec<L> {
         classify(&self.proba(dataset), &self.classes_map)
     }
 
+    /// Writes predicted classes for a set of samples to `out` of length number_of_samples.
+    /// Allocates memory only to clone labels which own it, e.g. `String`.
+    pub fn predict_into(&self, dataset: &[f32], out: &mut [L]) {
+        let num_features = self.num_features();
+        assert!(dataset.len() == out.len() * num_features);
+        for (r, sample) in out.iter_mut().zip(dataset.chunks_exact(num_features)) {
+            *r = self
+                .classes_map
+                .decode(self.classifier.predict_one(sample).argmax());
+        }
+    }
+
     /// Predicts class for a single sample given by a slice of length num_features().
-    pub fn predict_one(&self, sample: &[f32]) -> i64 {
-        self.classes_map.decode(self.proba(sample).argmax())
+    pub fn predict_one(&self, sample: &[f32]) -> L {
+        assert!(sample.len() == self.num_features());
+        self.classes_map
+            .decode(self.classifier.predict_one(sample).argmax())
     }
 
     /// Predicts classes probabilities for each sample. The length of result vector is
@@ -80,11 +138,18 @@ impl<P: Predictor> Classifier<P> {
         self.classifier.predict(dataset)
     }
 
+    /// Writes classes probabilities for each sample to `out` of length
+    /// number_of_samples * num_classes() without allocating memory.
+    pub fn proba_into(&self, dataset: &[f32], out: &mut [f32]) {
+        self.classifier.predict_into(dataset, out);
+    }
+
     /// Provides trainer for training a classifier tree.
-    pub fn trainer() -> Trainer<P> {
+    pub fn trainer() -> Trainer<P, L> {
         Trainer {
             config: decision_tree::TrainConfig::default(),
-            _marker: std::marker::PhantomData::default(),
+            classes: Vec::new(),
+            _marker: std::marker::PhantomData,
         }
     }
 
@@ -92,10 +157,88 @@ impl<P: Predictor> Classifier<P> {
     pub fn num_features(&self) -> usize {
         self.classifier.num_features()
     }
+
+    pub(crate) fn model(&self) -> &ClassifierModel<P> {
+        &self.classifier
+    }
+
+    pub(crate) fn classes_map(&self) -> &ClassesMapping<L> {
+        &self.classes_map
+    }
 }
 
-impl ClassDecode for Classifier {
-    fn get_decode_table(&self) -> &[i64] {
+impl<P: Predictor + Validate, L: Label> Classifier<P, L> {
+    /// Checks structural invariants of the model, e.g. after deserialization from untrusted
+    /// source: bounds and acyclicity of trees, leaf values and probability vectors. A valid model
+    /// predicts samples of num_features() features without panics.
+    pub fn validate(&self) -> Result<(), InvalidModel> {
+        self.classifier.validate()?;
+        self.classes_map.validate(self.classifier.num_classes())
+    }
+}
+
+impl<P: Predictor + Export, L: Label> Classifier<P, L> {
+    /// Returns the tree as a Graphviz DOT graph. Leaves show the predicted class and class
+    /// probabilities. `feature_names` of num_features() length replace `x[i]` in conditions.
+    pub fn to_dot(&self, feature_names: Option<&[&str]>) -> String {
+        tree_render::check_names(feature_names, self.num_features());
+        let leaf = tree_render::class_leaf(&self.classifier, &self.classes_map);
+        tree_render::dot(
+            &self.classifier.predictor().export(),
+            self.num_features(),
+            feature_names,
+            &leaf,
+        )
+    }
+
+    /// Returns the tree as indented text rules, like `export_text` of scikit-learn.
+    /// `feature_names` of num_features() length replace `x[i]` in conditions.
+    pub fn to_text_rules(&self, feature_names: Option<&[&str]>) -> String {
+        tree_render::check_names(feature_names, self.num_features());
+        let leaf = tree_render::class_leaf(&self.classifier, &self.classes_map);
+        tree_render::text_rules(
+            &self.classifier.predictor().export(),
+            self.num_features(),
+            feature_names,
+            &leaf,
+        )
+    }
+
+    /// Removes splits whose subtrees predict the same value for any sample. Predictions do not
+    /// change.
+    pub fn simplify(&mut self) {
+        self.classifier.simplify();
+    }
+}
+
+impl<P: Predictor + Export, L: Label + SourceLiteral> Classifier<P, L> {
+    /// Generates standalone Rust source of the model with names starting with `prefix`, see
+    /// [codegen].
+    pub fn to_rust_source(&self, prefix: &str) -> String {
+        codegen::classifier(
+            std::slice::from_ref(&self.classifier),
+            self.get_decode_table(),
+            Language::Rust,
+            prefix,
+        )
+    }
+
+    /// Generates standalone C source of the model with names starting with `prefix`, see
+    /// [codegen].
+    pub fn to_c_source(&self, prefix: &str) -> String {
+        codegen::classifier(
+            std::slice::from_ref(&self.classifier),
+            self.get_decode_table(),
+            Language::C,
+            prefix,
+        )
+    }
+}
+
+impl<P: Predictor, L: Label> ClassDecode for Classifier<P, L> {
+    type Label = L;
+
+    fn get_decode_table(&self) -> &[L] {
         self.classes_map.get_decode_table()
     }
 }
@@ -1,7 +1,8 @@
 use crate::{
-    decision_tree::{self, BlockTree, Predictor, RegressorModel},
+    codegen::{self, Language},
+    decision_tree::{self, BlockTree, Predictor, RegressorModel, Validate},
     trainer_builders::*,
-    FloatTarget, Trainset,
+    tree_render, Export, FloatTarget, InvalidModel, Trainset,
 };
 
 use serde::{Deserialize, Serialize};
@@ -12,6 +13,7 @@ use serde::{Deserialize, Serialize};
 /// ```text
 /// max_depth: usize::MAX,
 /// max_features: NumFeatures::NUMBER(usize::MAX),
+/// strict_max_features: false,
 /// seed: 42,
 /// min_samples_leaf: 1,
 /// min_samples_split: 2,
@@ -51,7 +53,7 @@ impl<P: Predictor> CommonTrainerBuilder for Trainer<P> {}
 impl<P: Predictor> Trainer<P> {
     /// Trains a regression tree with dataset given by a slice of length divisible by targets.len().
     pub fn train(&self, data: &[f32], targets: &[FloatTarget]) -> Regressor<P> {
-        let trainset = Trainset::with_transposed(data, &targets);
+        let trainset = Trainset::with_transposed(data, targets);
 
         Regressor {
             regressor: RegressorModel::train(&trainset, &self.config),
@@ -60,6 +62,10 @@ impl<P: Predictor> Trainer<P> {
 }
 
 impl<P: Predictor> Regressor<P> {
+    pub(crate) fn from_parts(regressor: RegressorModel<P>) -> Self {
+        Self { regressor }
+    }
+
     /// Predicts regression values for a set of samples.
     /// Dataset is a vector of floats with length multiple of num_features().
     pub fn predict_batch(&self, dataset: &[f32]) -> Vec<FloatTarget> {
@@ -67,6 +73,12 @@ impl<P: Predictor> Regressor<P> {
         self.regressor.predict(dataset)
     }
 
+    /// Writes regression values for a set of samples to `out` of length number_of_samples without
+    /// allocating memory.
+    pub fn predict_into(&self, dataset: &[f32], out: &mut [FloatTarget]) {
+        self.regressor.predict_into(dataset, out);
+    }
+
     /// Predicts regression value for a single sample given by a slice of length num_features().
     pub fn predict_one(&self, sample: &[f32]) -> FloatTarget {
         self.regressor.predict_one(sample)
@@ -76,7 +88,7 @@ impl<P: Predictor> Regressor<P> {
     pub fn trainer() -> Trainer<P> {
         Trainer {
             config: decision_tree::TrainConfig::default(),
-            _marker: std::marker::PhantomData::default(),
+            _marker: std::marker::PhantomData,
         }
     }
 
@@ -84,4 +96,67 @@ impl<P: Predictor> Regressor<P> {
     pub fn num_features(&self) -> usize {
         self.regressor.num_features()
     }
+
+    pub(crate) fn model(&self) -> &RegressorModel<P> {
+        &self.regressor
+    }
+}
+
+impl<P: Predictor + Validate> Regressor<P> {
+    /// Checks structural invariants of the model, e.g. after deserialization from untrusted
+    /// source: bounds and acyclicity of trees, leaf values. A valid model
+    /// predicts samples of num_features() features without panics.
+    pub fn validate(&self) -> Result<(), InvalidModel> {
+        self.regressor.validate()
+    }
+}
+
+impl<P: Predictor + Export> Regressor<P> {
+    /// Returns the tree as a Graphviz DOT graph. Leaves show predicted values.
+    /// `feature_names` of num_features() length replace `x[i]` in conditions.
+    pub fn to_dot(&self, feature_names: Option<&[&str]>) -> String {
+        tree_render::check_names(feature_names, self.num_features());
+        let root = self.regressor.predictor().export();
+        tree_render::dot(
+            &root,
+            self.num_features(),
+            feature_names,
+            &tree_render::value_leaf,
+        )
+    }
+
+    /// Returns the tree as indented text rules, like `export_text` of scikit-learn.
+    /// `feature_names` of num_features() length replace `x[i]` in conditions.
+    pub fn to_text_rules(&self, feature_names: Option<&[&str]>) -> String {
+        tree_render::check_names(feature_names, self.num_features());
+        let root = self.regressor.predictor().export();
+        tree_render::text_rules(
+            &root,
+            self.num_features(),
+            feature_names,
+            &tree_render::value_leaf,
+        )
+    }
+
+    /// Removes splits whose subtrees predict the same value for any sample. Predictions do not
+    /// change.
+    pub fn simplify(&mut self) {
+        self.regressor.simplify();
+    }
+
+    /// Generates standalone Rust source of the model with names starting with `prefix`, see
+    /// [codegen].
+    pub fn to_rust_source(&self, prefix: &str) -> String {
+        codegen::regressor(
+            std::slice::from_ref(&self.regressor),
+            Language::Rust,
+            prefix,
+        )
+    }
+
+    /// Generates standalone C source of the model with names starting with `prefix`, see
+    /// [codegen].
+    pub fn to_c_source(&self, prefix: &str) -> String {
+        codegen::regressor(std::slice::from_ref(&self.regressor), Language::C, prefix)
+    }
 }
@@ -0,0 +1,161 @@
+// Rendering of trees as Graphviz DOT graphs and text rules, similar to `export_graphviz` and
+// `export_text` of scikit-learn. Trees are walked through their exported structure, so padding
+// nodes of `BlockTree` blocks are not rendered.
+use crate::{
+    decision_tree::{decode, ClassifierModel, DecodedNode, Split},
+    ClassDecode, ClassesMapping, Label, Predictor, TreeNode,
+};
+use argminmax::ArgMinMax;
+use std::fmt::Write;
+
+// Returns named values describing a leaf value of a tree.
+pub type Leaf<'a> = &'a dyn Fn(u32) -> Vec<(&'static str, String)>;
+
+// Describes leaves of a classifier tree by the most probable class and class probabilities.
+pub fn class_leaf<P: Predictor, L: Label>(
+    model: &ClassifierModel<P>,
+    classes_map: &ClassesMapping<L>,
+) -> impl Fn(u32) -> Vec<(&'static str, String)> {
+    move |value| {
+        let proba = model.leaf(value);
+        let proba_text: Vec<String> = proba.iter().map(|p| format!("{p:.3}")).collect();
+        vec![
+            ("class", format!("{:?}", classes_map.decode(proba.argmax()))),
+            ("proba", format!("[{}]", proba_text.join(", "))),
+        ]
+    }
+}
+
+// Describes a leaf of a regressor tree by its predicted value.
+pub fn value_leaf(value: u32) -> Vec<(&'static str, String)> {
+    vec![("value", f32::from_bits(value).to_string())]
+}
+
+// Checks that names are given for `num_features` features.
+pub fn check_names(feature_names: Option<&[&str]>, num_features: usize) {
+    if let Some(names) = feature_names {
+        assert!(
+            names.len() == num_features,
+            "Expected {num_features} feature names."
+        );
+    }
+}
+
+fn feature_name(feature: u16, feature_names: Option<&[&str]>) -> String {
+    match feature_names {
+        Some(names) => names[feature as usize].to_string(),
+        None => format!("x[{feature}]"),
+    }
+}
+
+// Returns conditions of the left and the right child of a split, missing values are shown on the
+// side they go to.
+fn conditions(split: &Split, feature_names: Option<&[&str]>) -> (String, String) {
+    let name = feature_name(split.feature, feature_names);
+    let threshold = split.threshold;
+    if split.missing_left {
+        (
+            format!("{name} <= {threshold} or NaN"),
+            format!("{name} >  {threshold}"),
+        )
+    } else {
+        (
+            format!("{name} <= {threshold}"),
+            format!("{name} >  {threshold}"),
+        )
+    }
+}
+
+// Returns a DOT graph of a tree of `num_features` features. Left edges are marked "True", right
+// edges "False".
+pub fn dot(
+    root: &TreeNode,
+    num_features: usize,
+    feature_names: Option<&[&str]>,
+    leaf: Leaf,
+) -> String {
+    let mut out = String::from("digraph Tree {\nnode [shape=box] ;\n");
+    let mut next_id = 0;
+    let render = Render {
+        num_features,
+        feature_names,
+        leaf,
+    };
+    dot_node(root, &render, &mut next_id, &mut out);
+    out.push_str("}\n");
+    out
+}
+
+// Parameters of rendering shared by all nodes of a tree.
+struct Render<'a> {
+    num_features: usize,
+    feature_names: Option<&'a [&'a str]>,
+    leaf: Leaf<'a>,
+}
+
+fn dot_node(node: &TreeNode, render: &Render, next_id: &mut usize, out: &mut String) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+    // DOT label lines are separated by escaped "\n".
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    match decode(node, render.num_features) {
+        DecodedNode::Leaf(value) => {
+            let lines: Vec<String> = (render.leaf)(value)
+                .into_iter()
+                .map(|(name, value)| escape(&format!("{name} = {value}")))
+                .collect();
+            writeln!(out, "{id} [label=\"{}\"] ;", lines.join("\\n")).unwrap();
+        }
+        DecodedNode::Split(split, left, right) => {
+            let condition = escape(&conditions(&split, render.feature_names).0);
+            writeln!(out, "{id} [label=\"{condition}\"] ;").unwrap();
+            for (child, angle, label) in [(left, 45, "True"), (right, -45, "False")] {
+                let child_id = dot_node(child, render, next_id, out);
+                writeln!(
+                    out,
+                    "{id} -> {child_id} [labeldistance=2.5, labelangle={angle}, headlabel=\"{label}\"] ;"
+                )
+                .unwrap();
+            }
+        }
+    }
+    id
+}
+
+// Returns rules of a tree of `num_features` features, one line per condition or leaf, indented by
+// depth.
+pub fn text_rules(
+    root: &TreeNode,
+    num_features: usize,
+    feature_names: Option<&[&str]>,
+    leaf: Leaf,
+) -> String {
+    let mut out = String::new();
+    let render = Render {
+        num_features,
+        feature_names,
+        leaf,
+    };
+    text_node(root, &render, 0, &mut out);
+    out
+}
+
+fn text_node(node: &TreeNode, render: &Render, depth: usize, out: &mut String) {
+    let indent = "|   ".repeat(depth);
+    match decode(node, render.num_features) {
+        DecodedNode::Leaf(value) => {
+            let values: Vec<String> = (render.leaf)(value)
+                .into_iter()
+                .map(|(name, value)| format!("{name}: {value}"))
+                .collect();
+            writeln!(out, "{indent}|--- {}", values.join(", ")).unwrap();
+        }
+        DecodedNode::Split(split, left, right) => {
+            let (left_condition, right_condition) = conditions(&split, render.feature_names);
+            writeln!(out, "{indent}|--- {left_condition}").unwrap();
+            text_node(left, render, depth + 1, out);
+            writeln!(out, "{indent}|--- {right_condition}").unwrap();
+            text_node(right, render, depth + 1, out);
+        }
+    }
+}